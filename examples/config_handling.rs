@@ -51,6 +51,18 @@ fn main() -> Result<()> {
         assert_eq!(file_config.root_dir, config.root_dir)
     }
 
+    if command_line_args.chunk_dir_depth.is_some() {
+        assert_eq!(command_line_args.chunk_dir_depth, config.chunk_dir_depth)
+    } else {
+        assert_eq!(file_config.chunk_dir_depth, config.chunk_dir_depth)
+    }
+
+    if command_line_args.storage_backend.is_some() {
+        assert_eq!(command_line_args.storage_backend, config.storage_backend)
+    } else {
+        assert_eq!(file_config.storage_backend, config.storage_backend)
+    }
+
     if command_line_args.verbose > 0 {
         assert_eq!(command_line_args.verbose, config.verbose)
     } else {
@@ -81,6 +93,97 @@ fn main() -> Result<()> {
         config.clear_data,
         file_config.clear_data || command_line_args.clear_data
     );
+    assert_eq!(
+        config.rescan_chunks,
+        file_config.rescan_chunks || command_line_args.rescan_chunks
+    );
+
+    if !command_line_args.data_dirs.is_empty() {
+        assert_eq!(command_line_args.data_dirs, config.data_dirs)
+    } else {
+        assert_eq!(file_config.data_dirs, config.data_dirs)
+    }
+
+    if !command_line_args.compressed_stores.is_empty() {
+        assert_eq!(
+            command_line_args.compressed_stores,
+            config.compressed_stores
+        )
+    } else {
+        assert_eq!(file_config.compressed_stores, config.compressed_stores)
+    }
+
+    if command_line_args.storage_key_file.is_some() {
+        assert_eq!(command_line_args.storage_key_file, config.storage_key_file)
+    } else {
+        assert_eq!(file_config.storage_key_file, config.storage_key_file)
+    }
+
+    if command_line_args.max_disk_ops.is_some() {
+        assert_eq!(command_line_args.max_disk_ops, config.max_disk_ops)
+    } else {
+        assert_eq!(file_config.max_disk_ops, config.max_disk_ops)
+    }
+
+    if command_line_args.chunk_cache_capacity.is_some() {
+        assert_eq!(
+            command_line_args.chunk_cache_capacity,
+            config.chunk_cache_capacity
+        )
+    } else {
+        assert_eq!(
+            file_config.chunk_cache_capacity,
+            config.chunk_cache_capacity
+        )
+    }
+
+    if command_line_args.scrub_rate.is_some() {
+        assert_eq!(command_line_args.scrub_rate, config.scrub_rate)
+    } else {
+        assert_eq!(file_config.scrub_rate, config.scrub_rate)
+    }
+
+    if command_line_args.liveness_policy.is_some() {
+        assert_eq!(command_line_args.liveness_policy, config.liveness_policy)
+    } else {
+        assert_eq!(file_config.liveness_policy, config.liveness_policy)
+    }
+
+    if command_line_args.read_strategy.is_some() {
+        assert_eq!(command_line_args.read_strategy, config.read_strategy)
+    } else {
+        assert_eq!(file_config.read_strategy, config.read_strategy)
+    }
+
+    if command_line_args.blob_storage.is_some() {
+        assert_eq!(command_line_args.blob_storage, config.blob_storage)
+    } else {
+        assert_eq!(file_config.blob_storage, config.blob_storage)
+    }
+
+    if command_line_args.blob_quota.is_some() {
+        assert_eq!(command_line_args.blob_quota, config.blob_quota)
+    } else {
+        assert_eq!(file_config.blob_quota, config.blob_quota)
+    }
+
+    if command_line_args.map_quota.is_some() {
+        assert_eq!(command_line_args.map_quota, config.map_quota)
+    } else {
+        assert_eq!(file_config.map_quota, config.map_quota)
+    }
+
+    if command_line_args.sequence_quota.is_some() {
+        assert_eq!(command_line_args.sequence_quota, config.sequence_quota)
+    } else {
+        assert_eq!(file_config.sequence_quota, config.sequence_quota)
+    }
+
+    if command_line_args.register_quota.is_some() {
+        assert_eq!(command_line_args.register_quota, config.register_quota)
+    } else {
+        assert_eq!(file_config.register_quota, config.register_quota)
+    }
 
     if command_line_args.local_addr.is_some() || command_line_args.first.is_some() {
         assert_eq!(
@@ -188,6 +291,9 @@ fn main() -> Result<()> {
         )
     }
 
+    // Never read from the config file.
+    assert_eq!(command_line_args.command, config.command);
+
     clear_disk_config()?;
 
     Ok(())
@@ -0,0 +1,388 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Export and import of a node's stored data, to back it up or move it to another machine.
+//!
+//! An archive is a tar file starting with a `MANIFEST` entry, followed by the archived files with
+//! their paths relative to the root dir.
+
+use crate::{
+    chunk_store::{
+        BlobChunkStore, MapChunkStore, RegisterChunkStore, SequenceChunkStore, StorageBackendKind,
+        StoreConfig, UsedSpace, CHUNK_STORE_DIR, QUARANTINE_DIR,
+    },
+    migrations::SCHEMA_VERSION_FILENAME,
+    state_db::{NETWORK_KEYPAIR_FILENAME, REWARD_PUBLIC_KEY_FILENAME, REWARD_SECRET_KEY_FILENAME},
+    storage_key::{self, KEY_CHECK_FILENAME},
+    transfers::store::TRANSFERS_DIR_NAME,
+    Config, Error, Result,
+};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+};
+use tar::{Archive, Builder, Header};
+
+/// Version of the archive format written by `export`.
+const ARCHIVE_VERSION: u32 = 1;
+
+const MANIFEST_NAME: &str = "MANIFEST";
+
+/// Root dir entries which are archived. Anything else, like logs, is left out.
+const ARCHIVED_ENTRIES: &[&str] = &[
+    CHUNK_STORE_DIR,
+    TRANSFERS_DIR_NAME,
+    REWARD_PUBLIC_KEY_FILENAME,
+    REWARD_SECRET_KEY_FILENAME,
+    NETWORK_KEYPAIR_FILENAME,
+    KEY_CHECK_FILENAME,
+    SCHEMA_VERSION_FILENAME,
+];
+
+/// Files being written when the node stopped are left out.
+const TEMP_FILE_EXTENSION: &str = "tmp";
+
+/// Archives only cover the root dir.
+const DATA_DIRS_REFUSED: &str = "Chunks spread over data dirs can't be archived";
+
+/// Dir in the root dir an archive is unpacked to, until all of it has been verified.
+const STAGING_DIR: &str = "import";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    node_version: String,
+    // Chunks are archived in the layout of their backend, so they must be imported into the same.
+    backend: StorageBackendKind,
+}
+
+/// Writes the data of the node configured in `config` to a new archive at `archive_path`.
+pub async fn export(config: &Config, archive_path: &Path) -> Result<()> {
+    let root_dir = config.root_dir()?;
+    let backend = config.storage_backend();
+    if backend == StorageBackendKind::Memory {
+        return Err(Error::Archive(
+            "Chunks held in memory can't be exported".to_string(),
+        ));
+    }
+    if !config.data_dirs().is_empty() {
+        return Err(Error::Archive(DATA_DIRS_REFUSED.to_string()));
+    }
+
+    let mut builder = Builder::new(File::create(archive_path)?);
+    let manifest = serde_json::to_vec(&Manifest {
+        version: ARCHIVE_VERSION,
+        node_version: env!("CARGO_PKG_VERSION").to_string(),
+        backend,
+    })
+    .map_err(|err| Error::Archive(err.to_string()))?;
+    let mut header = Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, MANIFEST_NAME, &manifest[..])?;
+
+    let quarantine_dir = Path::new(CHUNK_STORE_DIR).join(QUARANTINE_DIR);
+    let mut count = 0;
+    for file in archived_files(&root_dir)? {
+        if file.starts_with(&quarantine_dir) {
+            continue;
+        }
+        builder.append_path_with_name(root_dir.join(&file), &file)?;
+        count += 1;
+    }
+    builder.into_inner()?.sync_all()?;
+
+    info!(
+        "Exported {} files from {} to {}",
+        count,
+        root_dir.display(),
+        archive_path.display()
+    );
+    Ok(())
+}
+
+/// Restores the data in the archive at `archive_path` to the root dir of the node configured in
+/// `config`, which must not hold any data yet.
+///
+/// The archive is unpacked to a staging dir first, and only moved into place once every chunk in
+/// it has been read back intact.
+pub async fn import(config: &Config, archive_path: &Path) -> Result<()> {
+    let root_dir = config.root_dir()?;
+    if !config.data_dirs().is_empty() {
+        return Err(Error::Archive(DATA_DIRS_REFUSED.to_string()));
+    }
+    if let Some(entry) = ARCHIVED_ENTRIES
+        .iter()
+        .find(|entry| root_dir.join(entry).exists())
+    {
+        return Err(Error::Archive(format!(
+            "{} already holds node data ({}), refusing to overwrite it",
+            root_dir.display(),
+            entry
+        )));
+    }
+
+    let staging_dir = root_dir.join(STAGING_DIR);
+    if staging_dir.exists() {
+        fs::remove_dir_all(&staging_dir)?;
+    }
+    fs::create_dir_all(&staging_dir)?;
+
+    let result = match unpack(config, archive_path, &staging_dir) {
+        Ok(()) => verify(config, &staging_dir).await,
+        Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+            Err(Error::Archive(err.to_string()))
+        }
+        Err(err) => Err(err.into()),
+    };
+    if let Err(err) = result {
+        fs::remove_dir_all(&staging_dir)?;
+        return Err(err);
+    }
+
+    for entry in ARCHIVED_ENTRIES {
+        let path = staging_dir.join(entry);
+        if path.exists() {
+            fs::rename(path, root_dir.join(entry))?;
+        }
+    }
+    fs::remove_dir_all(&staging_dir)?;
+
+    info!(
+        "Imported {} to {}",
+        archive_path.display(),
+        root_dir.display()
+    );
+    Ok(())
+}
+
+// Unpacks the archive at `archive_path` to `dir`. An archive which isn't one of ours, or which
+// doesn't match the node, fails with `io::ErrorKind::InvalidData`.
+fn unpack(config: &Config, archive_path: &Path, dir: &Path) -> io::Result<()> {
+    let mut archive = Archive::new(File::open(archive_path)?);
+    let mut entries = archive.entries()?;
+
+    let mut manifest = match entries.next() {
+        Some(entry) => entry?,
+        None => return Err(invalid("Archive is empty".to_string())),
+    };
+    if manifest.path()?.as_ref() != Path::new(MANIFEST_NAME) {
+        return Err(invalid("Archive has no manifest".to_string()));
+    }
+    let mut bytes = vec![];
+    let _ = manifest.read_to_end(&mut bytes)?;
+    let manifest: Manifest =
+        serde_json::from_slice(&bytes).map_err(|err| invalid(err.to_string()))?;
+    if manifest.version > ARCHIVE_VERSION {
+        return Err(invalid(format!(
+            "Archive version {} is newer than the supported version {}",
+            manifest.version, ARCHIVE_VERSION
+        )));
+    }
+    if manifest.backend != config.storage_backend() {
+        return Err(invalid(format!(
+            "Archive holds chunks of the {} storage backend, but the node is configured for {}",
+            manifest.backend,
+            config.storage_backend()
+        )));
+    }
+
+    for entry in entries {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let archived = match path.components().next() {
+            Some(Component::Normal(name)) => ARCHIVED_ENTRIES.iter().any(|entry| name == *entry),
+            _ => false,
+        };
+        // `unpack_in` also refuses paths leading out of `dir`.
+        if !archived || !entry.unpack_in(dir)? {
+            return Err(invalid(format!(
+                "Unexpected entry in archive: {}",
+                path.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// Opens the stores unpacked in `dir`, which also rewrites their used space records, and reads back
+// every chunk.
+async fn verify(config: &Config, dir: &Path) -> Result<()> {
+    let store_config = StoreConfig {
+        storage_key: storage_key::load(dir, config)?,
+        ..config.store_config()
+    };
+    let used_space = UsedSpace::new(u64::MAX);
+
+    let mut damaged = vec![];
+    damaged.extend(
+        BlobChunkStore::new(dir, &used_space, &store_config)
+            .await?
+            .verify()
+            .await,
+    );
+    damaged.extend(
+        MapChunkStore::new(dir, &used_space, &store_config)
+            .await?
+            .verify()
+            .await,
+    );
+    damaged.extend(
+        SequenceChunkStore::new(dir, &used_space, &store_config)
+            .await?
+            .verify()
+            .await,
+    );
+    damaged.extend(
+        RegisterChunkStore::new(dir, &used_space, &store_config)
+            .await?
+            .verify()
+            .await,
+    );
+
+    match damaged.first() {
+        None => Ok(()),
+        Some(address) => Err(Error::Archive(format!(
+            "{} chunks in the archive are damaged, e.g. {:?}",
+            damaged.len(),
+            address
+        ))),
+    }
+}
+
+// Lists the files under the archived entries of `root_dir`, relative to it.
+fn archived_files(root_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut pending: Vec<PathBuf> = ARCHIVED_ENTRIES.iter().map(PathBuf::from).collect();
+    while let Some(path) = pending.pop() {
+        let full_path = root_dir.join(&path);
+        if full_path.is_dir() {
+            for entry in fs::read_dir(&full_path)? {
+                pending.push(path.join(entry?.file_name()));
+            }
+        } else if full_path.is_file()
+            && path.extension().and_then(|ext| ext.to_str()) != Some(TEMP_FILE_EXTENSION)
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use sn_data_types::{Blob, PublicBlob};
+    use tempdir::TempDir;
+
+    fn config(root_dir: &Path) -> Config {
+        let mut config = Config::default();
+        config.set_root_dir(root_dir);
+        config
+    }
+
+    #[tokio::test]
+    async fn exported_data_is_imported() -> Result<()> {
+        let source = TempDir::new("export")?;
+        let target = TempDir::new("import")?;
+        let archive = TempDir::new("archive")?;
+        let archive_path = archive.path().join("node.tar");
+
+        let blob = Blob::Public(PublicBlob::new(vec![1, 2, 3]));
+        let mut store = BlobChunkStore::new(
+            source.path(),
+            &UsedSpace::new(u64::MAX),
+            &StoreConfig::default(),
+        )
+        .await?;
+        store.put(&blob).await?;
+        drop(store);
+        fs::write(source.path().join(REWARD_PUBLIC_KEY_FILENAME), b"key")?;
+        fs::write(source.path().join("sn_node.log"), b"not archived")?;
+
+        export(&config(source.path()), &archive_path).await?;
+        import(&config(target.path()), &archive_path).await?;
+
+        let store = BlobChunkStore::new(
+            target.path(),
+            &UsedSpace::new(u64::MAX),
+            &StoreConfig::default(),
+        )
+        .await?;
+        assert_eq!(store.get(blob.address()).await?, blob);
+        assert_eq!(
+            fs::read(target.path().join(REWARD_PUBLIC_KEY_FILENAME))?,
+            b"key"
+        );
+        assert!(!target.path().join("sn_node.log").exists());
+        assert!(!target.path().join(STAGING_DIR).exists());
+
+        // The target now holds data, so it can't be imported to again.
+        assert!(matches!(
+            import(&config(target.path()), &archive_path).await,
+            Err(Error::Archive(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn damaged_chunks_fail_the_import() -> Result<()> {
+        let source = TempDir::new("export")?;
+        let target = TempDir::new("import")?;
+        let archive = TempDir::new("archive")?;
+        let archive_path = archive.path().join("node.tar");
+
+        let blob = Blob::Public(PublicBlob::new(vec![1, 2, 3]));
+        let mut store = BlobChunkStore::new(
+            source.path(),
+            &UsedSpace::new(u64::MAX),
+            &StoreConfig::default(),
+        )
+        .await?;
+        store.put(&blob).await?;
+        drop(store);
+        let blob_file = blob_file(&blob)?;
+        let chunk_file = archived_files(source.path())?
+            .into_iter()
+            .find(|file| file.starts_with(CHUNK_STORE_DIR) && file.ends_with(&blob_file))
+            .ok_or_else(|| Error::Logic("Chunk file not found".to_string()))?;
+        let mut contents = fs::read(source.path().join(&chunk_file))?;
+        if let Some(byte) = contents.last_mut() {
+            *byte ^= 1;
+        }
+        fs::write(source.path().join(&chunk_file), contents)?;
+
+        export(&config(source.path()), &archive_path).await?;
+        assert!(matches!(
+            import(&config(target.path()), &archive_path).await,
+            Err(Error::Archive(_))
+        ));
+        assert!(!target.path().join(CHUNK_STORE_DIR).exists());
+        assert!(!target.path().join(STAGING_DIR).exists());
+
+        Ok(())
+    }
+
+    // Name of the file a chunk is stored in by the filesystem backend.
+    fn blob_file(blob: &Blob) -> Result<PathBuf> {
+        Ok(PathBuf::from(hex::encode(utils::serialise(
+            blob.address(),
+        )?)))
+    }
+}
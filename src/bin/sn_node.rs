@@ -29,7 +29,9 @@
 
 use log::{self, error, info};
 use self_update::{cargo_crate_version, Status};
-use sn_node::{self, add_connection_info, set_connection_info, utils, Config, Node};
+use sn_node::{
+    self, add_connection_info, set_connection_info, utils, Command, Config, GcAction, Node,
+};
 use std::{io::Write, process};
 use structopt::{clap, StructOpt};
 
@@ -104,6 +106,48 @@ async fn run_node() {
         }
     }
 
+    if let Some(command) = config.command() {
+        let result = match command {
+            Command::Export { archive } => sn_node::export(&config, archive).await,
+            Command::Import { archive } => sn_node::import(&config, archive).await,
+            Command::Migrate { dry_run } => {
+                sn_node::migrate(&config, *dry_run).await.map(|migrations| {
+                    if migrations.is_empty() {
+                        println!("No migrations to run");
+                    }
+                    for migration in migrations {
+                        println!("{}", migration);
+                    }
+                })
+            }
+            Command::Gc { dry_run, remove } => {
+                let action = if *dry_run {
+                    GcAction::Report
+                } else if *remove {
+                    GcAction::Remove
+                } else {
+                    GcAction::Quarantine
+                };
+                sn_node::collect_garbage(&config, action)
+                    .await
+                    .map(|garbage| {
+                        println!(
+                            "Found {} files taking {} bytes which aren't chunks",
+                            garbage.count, garbage.size
+                        );
+                    })
+            }
+        };
+        match result {
+            Ok(()) => exit(0),
+            Err(e) => {
+                println!("Failed to {:?}: {}", command, e);
+                error!("Failed to {:?}: {}", command, e);
+                exit(1);
+            }
+        }
+    }
+
     let message = format!(
         "Running {} v{}",
         Config::clap().get_name(),
@@ -42,6 +42,11 @@ impl Capacity {
         self.reader.our_prefix().await
     }
 
+    /// The Adults of our section
+    pub async fn our_adults(&self) -> BTreeSet<XorName> {
+        self.reader.our_adults().await
+    }
+
     /// Whether the adult is recorded as full
     pub async fn is_full(&self, adult: &XorName) -> bool {
         self.reader.is_full(adult).await
@@ -63,6 +68,15 @@ impl Capacity {
         self.reader.get_chunk_holder_adults(target).await
     }
 
+    // Returns `XorName`s of `count` Adults to hold the shards of an erasure-coded Blob.
+    pub async fn get_shard_holder_adults(
+        &self,
+        target: &XorName,
+        count: usize,
+    ) -> BTreeSet<XorName> {
+        self.reader.get_shard_holder_adults(target, count).await
+    }
+
     pub async fn insert_full_adults(&self, full_adults: BTreeSet<XorName>) {
         self.writer.insert_full_adults(full_adults).await
     }
@@ -104,6 +118,11 @@ impl CapacityReader {
         self.reader.our_prefix().await
     }
 
+    /// The Adults of our section
+    pub async fn our_adults(&self) -> BTreeSet<XorName> {
+        self.reader.our_adults().await
+    }
+
     /// Whether the adult is recorded as full
     pub async fn is_full(&self, adult: &XorName) -> bool {
         self.adult_storage_info
@@ -138,6 +157,18 @@ impl CapacityReader {
             .non_full_adults_closest_to(&target, &full_adults, CHUNK_COPY_COUNT)
             .await
     }
+
+    // Returns `XorName`s of `count` Adults to hold the shards of an erasure-coded Blob.
+    pub async fn get_shard_holder_adults(
+        &self,
+        target: &XorName,
+        count: usize,
+    ) -> BTreeSet<XorName> {
+        let full_adults = self.adult_storage_info.full_adults.read().await;
+        self.reader
+            .non_full_adults_closest_to(target, &full_adults, count)
+            .await
+    }
 }
 
 impl CapacityWriter {
@@ -0,0 +1,185 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{collect_stray_files, is_store_file, Garbage, GcAction, StorageBackend};
+use crate::chunk_store::layout::{ChunkWalk, Layout};
+use log::warn;
+use std::{
+    ffi::OsStr,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+/// Extension of the file a chunk is written to before being renamed into place.
+pub(crate) const TEMP_FILE_EXTENSION: &str = "tmp";
+
+/// The max name length for a chunk file.
+const MAX_CHUNK_FILE_NAME_LENGTH: usize = 104;
+
+/// Stores each value in its own file, named by the hex of its key.
+///
+/// Writes to the same key must not overlap, as they go through the same temp file, which the
+/// `ChunkStore` ensures by taking `&mut self` for them.
+pub(crate) struct FsBackend {
+    dir: PathBuf,
+    quarantine_dir: PathBuf,
+    layout: Layout,
+    // The walk of the last listing, carried on by the next one if it starts where it stopped.
+    walk: Mutex<Option<ChunkWalk>>,
+}
+
+impl FsBackend {
+    /// Opens the files in `dir`, creating it if needed.
+    ///
+    /// Chunks already stored using a different directory layout than `dir_depth` are moved into
+    /// place, and leftovers of writes interrupted by a crash are removed.
+    pub fn open(dir: &Path, quarantine_dir: &Path, dir_depth: u8) -> io::Result<Self> {
+        if fs::read(dir).is_err() {
+            create_new_root(dir)?
+        }
+
+        let layout = Layout::new(dir_depth);
+        layout.migrate(dir)?;
+
+        for path in layout.chunk_files(dir)? {
+            if path.extension() == Some(OsStr::new(TEMP_FILE_EXTENSION)) {
+                warn!("Removing incomplete chunk {}", path.display());
+                fs::remove_file(&path)?;
+            }
+        }
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            quarantine_dir: quarantine_dir.to_path_buf(),
+            layout,
+            walk: Mutex::new(None),
+        })
+    }
+
+    pub(crate) fn file_path(&self, key: &[u8]) -> PathBuf {
+        self.layout.file_path(&self.dir, key)
+    }
+}
+
+impl StorageBackend for FsBackend {
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.file_path(key)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn size(&self, key: &[u8]) -> io::Result<Option<u64>> {
+        match fs::metadata(self.file_path(key)) {
+            Ok(metadata) if metadata.is_file() => Ok(Some(metadata.len())),
+            Ok(_) => Ok(None),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        write_atomically(&self.file_path(key), value)
+    }
+
+    // Shard directories are left in place when emptied, they're likely to be reused.
+    fn delete(&self, key: &[u8]) -> io::Result<()> {
+        match fs::remove_file(self.file_path(key)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    // Keys are listed in the order of their file paths, which `after` maps to whether it's stored
+    // or not. Paging through all keys walks the directories once, rather than once per page.
+    fn keys_after(&self, after: Option<&[u8]>, limit: usize) -> io::Result<Vec<Vec<u8>>> {
+        let after = after.map(|key| self.file_path(key));
+        // The walk is only a cache, so one left behind by a panic is as good as any.
+        let mut cached = self.walk.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut walk = match cached.take() {
+            Some(walk) if after.is_some() && walk.last() == after.as_deref() => walk,
+            _ => self.layout.walk_after(&self.dir, after.as_deref())?,
+        };
+        let files = walk.next_files(limit)?;
+        if files.len() == limit {
+            *cached = Some(walk);
+        }
+        Ok(files
+            .iter()
+            .filter_map(|path| hex::decode(path.file_name()?.to_str()?).ok())
+            .collect())
+    }
+
+    fn quarantine(&self, key: &[u8]) -> io::Result<()> {
+        let file_path = self.file_path(key);
+        let file_name = file_path.file_name().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Not a chunk: {}", file_path.display()),
+            )
+        })?;
+        warn!("Quarantining unreadable chunk {}", file_path.display());
+        fs::create_dir_all(&self.quarantine_dir)?;
+        fs::rename(&file_path, self.quarantine_dir.join(file_name))?;
+        Ok(())
+    }
+
+    // Stray files are quarantined at the same path within the quarantine dir as in the store.
+    fn collect_garbage(
+        &self,
+        is_chunk: fn(&[u8]) -> bool,
+        action: GcAction,
+    ) -> io::Result<Garbage> {
+        let is_stray = |path: &Path| match self.layout.chunk_file_name(&self.dir, path) {
+            Some(key) => !is_chunk(&key),
+            None => !is_store_file(&self.dir, path),
+        };
+        collect_stray_files(&self.dir, &self.quarantine_dir, is_stray, action)
+    }
+}
+
+fn create_new_root(root: &Path) -> io::Result<()> {
+    fs::create_dir_all(root)?;
+
+    // Verify that chunk files can be created.
+    let temp_file_path = root.join("0".repeat(MAX_CHUNK_FILE_NAME_LENGTH));
+    let _ = File::create(&temp_file_path)?;
+    fs::remove_file(temp_file_path)?;
+
+    Ok(())
+}
+
+// Writes `contents` to a temp file next to `path`, syncs it and then renames it over `path`, so
+// that a crash can't leave a partially written chunk behind.
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Chunk path has no parent"))?;
+    fs::create_dir_all(dir)?;
+
+    let temp_path = path.with_extension(TEMP_FILE_EXTENSION);
+    let res = File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp_path, path));
+    if res.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    res?;
+
+    // Persist the rename too. Directories can't be opened on all platforms, so this is best effort.
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
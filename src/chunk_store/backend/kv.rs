@@ -0,0 +1,167 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{collect_stray_files, is_store_file, Garbage, GcAction, StorageBackend};
+use log::warn;
+use redb::{Database, ReadableTable, TableDefinition};
+use std::{
+    fs, io,
+    ops::Bound,
+    path::{Path, PathBuf},
+};
+
+const DB_NAME: &str = "chunks.redb";
+
+const CHUNKS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("chunks");
+
+/// Stores all values in a single embedded database file, keyed by their keys in byte order.
+///
+/// Values are read from and written to disk individually, each write being committed durably
+/// before it returns, so the store can grow well beyond what fits in memory.
+pub(crate) struct KvBackend {
+    db: Database,
+    dir: PathBuf,
+    quarantine_dir: PathBuf,
+}
+
+impl KvBackend {
+    /// Opens the database in `dir`, creating it if needed. A database which can't be read is
+    /// reported rather than replaced, so that its chunks aren't silently lost.
+    pub fn open(dir: &Path, quarantine_dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let db = Database::create(dir.join(DB_NAME)).map_err(to_error)?;
+        // Created up front, so that reads needn't tell a missing table from an empty one.
+        let txn = db.begin_write().map_err(to_error)?;
+        let _ = txn.open_table(CHUNKS).map_err(to_error)?;
+        txn.commit().map_err(to_error)?;
+        Ok(Self {
+            db,
+            dir: dir.to_path_buf(),
+            quarantine_dir: quarantine_dir.to_path_buf(),
+        })
+    }
+
+    // Removes the values stored under `keys`, at once.
+    fn remove(&self, keys: &[Vec<u8>]) -> io::Result<()> {
+        let txn = self.db.begin_write().map_err(to_error)?;
+        {
+            let mut table = txn.open_table(CHUNKS).map_err(to_error)?;
+            for key in keys {
+                let _ = table.remove(key.as_slice()).map_err(to_error)?;
+            }
+        }
+        txn.commit().map_err(to_error)
+    }
+}
+
+impl StorageBackend for KvBackend {
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        let txn = self.db.begin_read().map_err(to_error)?;
+        let table = txn.open_table(CHUNKS).map_err(to_error)?;
+        let value = table.get(key).map_err(to_error)?;
+        Ok(value.map(|value| value.value().to_vec()))
+    }
+
+    fn size(&self, key: &[u8]) -> io::Result<Option<u64>> {
+        let txn = self.db.begin_read().map_err(to_error)?;
+        let table = txn.open_table(CHUNKS).map_err(to_error)?;
+        let value = table.get(key).map_err(to_error)?;
+        Ok(value.map(|value| value.value().len() as u64))
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        let txn = self.db.begin_write().map_err(to_error)?;
+        {
+            let mut table = txn.open_table(CHUNKS).map_err(to_error)?;
+            let _ = table.insert(key, value).map_err(to_error)?;
+        }
+        txn.commit().map_err(to_error)
+    }
+
+    fn delete(&self, key: &[u8]) -> io::Result<()> {
+        self.remove(&[key.to_vec()])
+    }
+
+    fn keys_after(&self, after: Option<&[u8]>, limit: usize) -> io::Result<Vec<Vec<u8>>> {
+        let txn = self.db.begin_read().map_err(to_error)?;
+        let table = txn.open_table(CHUNKS).map_err(to_error)?;
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        let range = table
+            .range::<&[u8]>((start, Bound::Unbounded))
+            .map_err(to_error)?;
+        range
+            .take(limit)
+            .map(|entry| {
+                let (key, _) = entry.map_err(to_error)?;
+                Ok(key.value().to_vec())
+            })
+            .collect()
+    }
+
+    fn quarantine(&self, key: &[u8]) -> io::Result<()> {
+        let name = hex::encode(key);
+        warn!("Quarantining unreadable chunk {}", name);
+        if let Some(value) = self.get(key)? {
+            fs::create_dir_all(&self.quarantine_dir)?;
+            fs::write(self.quarantine_dir.join(&name), value)?;
+        }
+        self.delete(key)
+    }
+
+    fn collect_garbage(
+        &self,
+        is_chunk: fn(&[u8]) -> bool,
+        action: GcAction,
+    ) -> io::Result<Garbage> {
+        let mut garbage = Garbage::default();
+        let mut stray = vec![];
+        {
+            let txn = self.db.begin_read().map_err(to_error)?;
+            let table = txn.open_table(CHUNKS).map_err(to_error)?;
+            for entry in table.iter().map_err(to_error)? {
+                let (key, value) = entry.map_err(to_error)?;
+                let (key, value) = (key.value(), value.value());
+                if is_chunk(key) {
+                    continue;
+                }
+                garbage.add(value.len() as u64);
+                let name = hex::encode(key);
+                match action {
+                    GcAction::Report => continue,
+                    GcAction::Quarantine => {
+                        warn!("Quarantining stray entry {}", name);
+                        fs::create_dir_all(&self.quarantine_dir)?;
+                        fs::write(self.quarantine_dir.join(&name), value)?;
+                    }
+                    GcAction::Remove => warn!("Removing stray entry {}", name),
+                }
+                stray.push(key.to_vec());
+            }
+        }
+        self.remove(&stray)?;
+
+        // Files left in the dir of the database, e.g. by copying data in by hand.
+        let db_path = self.dir.join(DB_NAME);
+        let is_stray = |path: &Path| path != db_path && !is_store_file(&self.dir, path);
+        garbage.merge(collect_stray_files(
+            &self.dir,
+            &self.quarantine_dir,
+            is_stray,
+            action,
+        )?);
+        Ok(garbage)
+    }
+}
+
+// I/O errors are kept as they are, so that a failing disk is noticed.
+fn to_error(err: impl Into<redb::Error>) -> io::Error {
+    match err.into() {
+        redb::Error::Io(err) => err,
+        err => io::Error::other(err),
+    }
+}
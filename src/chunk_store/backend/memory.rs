@@ -0,0 +1,95 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Garbage, GcAction, StorageBackend};
+use log::warn;
+use std::{
+    collections::BTreeMap,
+    io,
+    ops::Bound,
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+type Values = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// Holds all values in memory. Nothing survives a restart.
+#[derive(Default)]
+pub(crate) struct MemoryBackend {
+    values: RwLock<Values>,
+}
+
+impl MemoryBackend {
+    fn values(&self) -> io::Result<RwLockReadGuard<'_, Values>> {
+        self.values.read().map_err(|_| poisoned())
+    }
+
+    fn values_mut(&self) -> io::Result<RwLockWriteGuard<'_, Values>> {
+        self.values.write().map_err(|_| poisoned())
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.values()?.get(key).cloned())
+    }
+
+    fn size(&self, key: &[u8]) -> io::Result<Option<u64>> {
+        Ok(self.values()?.get(key).map(|value| value.len() as u64))
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        let _ = self.values_mut()?.insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, key: &[u8]) -> io::Result<()> {
+        let _ = self.values_mut()?.remove(key);
+        Ok(())
+    }
+
+    fn keys_after(&self, after: Option<&[u8]>, limit: usize) -> io::Result<Vec<Vec<u8>>> {
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        Ok(self
+            .values()?
+            .range::<[u8], _>((start, Bound::Unbounded))
+            .take(limit)
+            .map(|(key, _)| key.clone())
+            .collect())
+    }
+
+    // There's nowhere to keep the value, so it's dropped.
+    fn quarantine(&self, key: &[u8]) -> io::Result<()> {
+        warn!("Dropping unreadable chunk {}", hex::encode(key));
+        let _ = self.values_mut()?.remove(key);
+        Ok(())
+    }
+
+    // Nothing is kept outside of the values, and there's nowhere to quarantine them either.
+    fn collect_garbage(
+        &self,
+        is_chunk: fn(&[u8]) -> bool,
+        action: GcAction,
+    ) -> io::Result<Garbage> {
+        let mut values = self.values_mut()?;
+        let mut garbage = Garbage::default();
+        for (key, value) in values.iter() {
+            if !is_chunk(key) {
+                garbage.add(value.len() as u64);
+            }
+        }
+        if action != GcAction::Report {
+            values.retain(|key, _| is_chunk(key));
+        }
+        Ok(garbage)
+    }
+}
+
+// A thread panicked while holding the lock.
+fn poisoned() -> io::Error {
+    io::Error::other("Memory backend lock poisoned")
+}
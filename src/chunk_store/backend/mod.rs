@@ -0,0 +1,165 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Where a `ChunkStore` keeps its serialised chunks.
+
+pub(super) mod fs;
+mod kv;
+mod memory;
+
+use super::{
+    layout::{Layout, LAYOUT_FILENAME},
+    used_space::USED_SPACE_FILENAME,
+    Garbage, GcAction, StoreConfig,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsStr,
+    fmt::{self, Display, Formatter},
+    io,
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+};
+
+/// Raw storage of the serialised chunks of a single `ChunkStore`, keyed by their serialised ids.
+///
+/// Writes must be atomic: after a crash, a key either holds its previous or its new value.
+///
+/// Backends synchronise access to their storage themselves, so that they can be shared between
+/// the blocking threads running their operations without a lock around them.
+pub(crate) trait StorageBackend: Send + Sync {
+    /// Returns the value stored under `key`, if any.
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>>;
+
+    /// Returns the size of the value stored under `key`, if any.
+    fn size(&self, key: &[u8]) -> io::Result<Option<u64>>;
+
+    /// Stores `value` under `key`, replacing any previous value.
+    fn put(&self, key: &[u8], value: &[u8]) -> io::Result<()>;
+
+    /// Removes the value stored under `key`. Does nothing if there is none.
+    fn delete(&self, key: &[u8]) -> io::Result<()>;
+
+    /// Lists up to `limit` keys with a value stored under them, in the backend's own order,
+    /// starting just after `after`, or from the first key without it.
+    ///
+    /// `after` needn't still be stored, so that a listing can carry on past a key deleted since
+    /// it was returned.
+    fn keys_after(&self, after: Option<&[u8]>, limit: usize) -> io::Result<Vec<Vec<u8>>>;
+
+    /// Moves the value stored under `key` out of the store, keeping it for inspection where the
+    /// backend is persistent.
+    fn quarantine(&self, key: &[u8]) -> io::Result<()>;
+
+    /// Finds the entries which aren't chunks: values stored under a key `is_chunk` rejects, and
+    /// any file the backend didn't write itself. They're then dealt with according to `action`.
+    fn collect_garbage(&self, is_chunk: fn(&[u8]) -> bool, action: GcAction)
+        -> io::Result<Garbage>;
+}
+
+/// The kinds of `StorageBackend` a `ChunkStore` can be opened with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackendKind {
+    /// One file per chunk, spread over a directory fan-out.
+    Fs,
+    /// All chunks of a store in a single embedded key-value database.
+    Kv,
+    /// Chunks are only held in memory, and lost on restart.
+    Memory,
+}
+
+impl FromStr for StorageBackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fs" => Ok(Self::Fs),
+            "kv" => Ok(Self::Kv),
+            "memory" => Ok(Self::Memory),
+            other => Err(format!(
+                "Unknown storage backend '{}', expected one of: fs, kv, memory",
+                other
+            )),
+        }
+    }
+}
+
+impl Display for StorageBackendKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Fs => write!(f, "fs"),
+            Self::Kv => write!(f, "kv"),
+            Self::Memory => write!(f, "memory"),
+        }
+    }
+}
+
+/// Opens the backend selected in `config`, with its data in `dir` and quarantined values moved to
+/// `quarantine_dir`. Neither directory is touched by the in-memory backend.
+pub(crate) fn open(
+    dir: &Path,
+    quarantine_dir: &Path,
+    config: &StoreConfig,
+) -> io::Result<Arc<dyn StorageBackend>> {
+    Ok(match config.backend {
+        StorageBackendKind::Fs => {
+            Arc::new(fs::FsBackend::open(dir, quarantine_dir, config.dir_depth)?)
+        }
+        StorageBackendKind::Kv => Arc::new(kv::KvBackend::open(dir, quarantine_dir)?),
+        StorageBackendKind::Memory => Arc::new(memory::MemoryBackend::default()),
+    })
+}
+
+// Whether `path` is one of the files kept in the store's `dir` next to the chunks, about the store
+// itself.
+fn is_store_file(dir: &Path, path: &Path) -> bool {
+    if path.parent() != Some(dir) {
+        return false;
+    }
+    path.file_name() == Some(OsStr::new(LAYOUT_FILENAME))
+        || path.file_stem() == Some(OsStr::new(USED_SPACE_FILENAME))
+}
+
+// Finds the files under `dir` which `is_stray` picks out, and deals with them according to
+// `action`, quarantined ones keeping their path relative to `dir` under `quarantine_dir`.
+fn collect_stray_files(
+    dir: &Path,
+    quarantine_dir: &Path,
+    is_stray: impl Fn(&Path) -> bool,
+    action: GcAction,
+) -> io::Result<Garbage> {
+    let mut garbage = Garbage::default();
+    for path in Layout::all_files(dir)? {
+        if !is_stray(&path) {
+            continue;
+        }
+        garbage.add(std::fs::metadata(&path)?.len());
+        match action {
+            GcAction::Report => (),
+            GcAction::Quarantine => {
+                warn!("Quarantining stray file {}", path.display());
+                let target = match path.strip_prefix(dir) {
+                    Ok(relative) => quarantine_dir.join(relative),
+                    Err(_) => continue,
+                };
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::rename(&path, target)?;
+            }
+            GcAction::Remove => {
+                warn!("Removing stray file {}", path.display());
+                std::fs::remove_file(&path)?;
+            }
+        }
+    }
+    Ok(garbage)
+}
@@ -12,6 +12,8 @@ use sn_data_types::DataAddress;
 
 pub(crate) trait Chunk: Serialize + DeserializeOwned {
     type Id: ChunkId;
+    /// Name of the data type, as used for its quota.
+    const TYPE_NAME: &'static str;
     fn id(&self) -> &Self::Id;
 }
 
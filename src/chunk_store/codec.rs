@@ -0,0 +1,109 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Optional compression of stored chunks.
+//!
+//! A compressed chunk starts with `MAGIC` followed by a byte identifying its codec. Chunks stored
+//! uncompressed are plain bincode, which for all our chunk types starts with a small enum tag and so
+//! can't be mistaken for the header.
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use log::warn;
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8] = b"snc";
+
+/// The codecs chunks can be compressed with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Codec {
+    Deflate,
+}
+
+impl Codec {
+    fn id(self) -> u8 {
+        match self {
+            Self::Deflate => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Self::Deflate),
+            _ => None,
+        }
+    }
+}
+
+/// Returns what to store for the serialised chunk. It's only compressed, and given a header, when
+/// that makes it smaller.
+pub(crate) fn encode(codec: Option<Codec>, serialised_chunk: &[u8]) -> io::Result<Vec<u8>> {
+    let codec = match codec {
+        Some(codec) => codec,
+        None => return Ok(serialised_chunk.to_vec()),
+    };
+    let mut contents = MAGIC.to_vec();
+    contents.push(codec.id());
+    let contents = match codec {
+        Codec::Deflate => {
+            let mut encoder = DeflateEncoder::new(contents, Compression::default());
+            encoder.write_all(serialised_chunk)?;
+            encoder.finish()?
+        }
+    };
+    if contents.len() < serialised_chunk.len() {
+        Ok(contents)
+    } else {
+        Ok(serialised_chunk.to_vec())
+    }
+}
+
+/// Returns the serialised chunk from what was stored, whether it was compressed or not.
+pub(crate) fn decode(contents: Vec<u8>) -> Vec<u8> {
+    if !contents.starts_with(MAGIC) || contents.len() <= MAGIC.len() {
+        return contents;
+    }
+    let compressed = &contents[MAGIC.len() + 1..];
+    let decoded = match Codec::from_id(contents[MAGIC.len()]) {
+        Some(Codec::Deflate) => {
+            let mut decoded = vec![];
+            DeflateDecoder::new(compressed)
+                .read_to_end(&mut decoded)
+                .map(|_| decoded)
+        }
+        None => return contents,
+    };
+    // Left for deserialisation to fail on, so it's handled like any other unreadable chunk.
+    decoded.unwrap_or_else(|err| {
+        warn!("Could not decompress chunk: {}", err);
+        contents
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compressible_chunks_round_trip() -> io::Result<()> {
+        let chunk = vec![7; 1024];
+        let contents = encode(Some(Codec::Deflate), &chunk)?;
+        assert!(contents.starts_with(MAGIC));
+        assert!(contents.len() < chunk.len());
+        assert_eq!(decode(contents), chunk);
+        Ok(())
+    }
+
+    #[test]
+    fn incompressible_chunks_are_stored_as_they_are() -> io::Result<()> {
+        let chunk = vec![0, 0, 0, 0, 1];
+        assert_eq!(encode(Some(Codec::Deflate), &chunk)?, chunk);
+        assert_eq!(encode(None, &chunk)?, chunk);
+        assert_eq!(decode(chunk.clone()), chunk);
+        Ok(())
+    }
+}
@@ -11,6 +11,7 @@ use sn_data_types::{Blob, BlobAddress, DataAddress};
 
 impl Chunk for Blob {
     type Id = BlobAddress;
+    const TYPE_NAME: &'static str = "blob";
     fn id(&self) -> &Self::Id {
         match self {
             Blob::Public(ref chunk) => chunk.address(),
@@ -0,0 +1,260 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::utils;
+use log::{info, warn};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use xor_name::XorName;
+
+pub(super) const LAYOUT_FILENAME: &str = "layout";
+
+/// The number of directory levels chunk files are spread over by default.
+pub const DEFAULT_DIR_DEPTH: u8 = 2;
+
+/// Deepest fan-out supported. Also bounds how deep we look for chunks when migrating.
+const MAX_DIR_DEPTH: u8 = 4;
+
+/// Describes where chunk files live within a `ChunkStore` directory.
+///
+/// Each level is named by one byte (two hex chars) of the hash of the chunk's file name, so with
+/// a depth of 2 a chunk is stored at `ab/cd/<file name>`. A depth of 0 is the original flat layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Layout {
+    depth: u8,
+}
+
+impl Layout {
+    pub fn new(depth: u8) -> Self {
+        Self {
+            depth: depth.min(MAX_DIR_DEPTH),
+        }
+    }
+
+    /// Path of the chunk file whose (hex-decoded) name is `file_name`.
+    pub fn file_path(&self, dir: &Path, file_name: &[u8]) -> PathBuf {
+        let hash = XorName::from_content(&[file_name]);
+        let mut path = dir.to_path_buf();
+        for byte in hash.0.iter().take(self.depth as usize) {
+            path.push(hex::encode([*byte]));
+        }
+        path.push(hex::encode(file_name));
+        path
+    }
+
+    /// Lists all files sitting at the chunk level of this layout.
+    pub fn chunk_files(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut files = vec![];
+        collect_files(dir, self.depth, self.depth, &mut files)?;
+        Ok(files)
+    }
+
+    /// Starts a walk over the chunk files of this layout in `dir`, in path order, from just after
+    /// `after` (which needn't exist), or from the first one without it.
+    pub fn walk_after(&self, dir: &Path, after: Option<&Path>) -> io::Result<ChunkWalk> {
+        let mut walk = ChunkWalk {
+            after: after.map(Path::to_path_buf),
+            pending: vec![],
+        };
+        walk.push_entries(dir, self.depth)?;
+        Ok(walk)
+    }
+
+    /// Returns the (hex-decoded) name of the chunk file at `path`, if it's named after a chunk and
+    /// is where this layout keeps it within `dir`.
+    pub fn chunk_file_name(&self, dir: &Path, path: &Path) -> Option<Vec<u8>> {
+        let file_name = hex::decode(path.file_name()?.to_str()?).ok()?;
+        if self.file_path(dir, &file_name) == path {
+            Some(file_name)
+        } else {
+            None
+        }
+    }
+
+    /// Lists all files under `dir`, however deep.
+    pub fn all_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut files = vec![];
+        collect_files(dir, u8::MAX, 0, &mut files)?;
+        Ok(files)
+    }
+
+    /// Moves every chunk found under `dir` to where this layout expects it, unless the layout
+    /// recorded in `dir` already matches.
+    ///
+    /// The layout marker is only written once all chunks are in place, so an interrupted
+    /// migration is simply picked up again on the next open.
+    pub fn migrate(&self, dir: &Path) -> io::Result<()> {
+        let marker = dir.join(LAYOUT_FILENAME);
+        if let Ok(bytes) = fs::read(&marker) {
+            if utils::deserialise::<u8>(&bytes).ok() == Some(self.depth) {
+                return Ok(());
+            }
+        }
+
+        let mut files = vec![];
+        collect_files(dir, MAX_DIR_DEPTH, 0, &mut files)?;
+
+        let mut moved = 0_usize;
+        for path in files {
+            let file_name = match path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| hex::decode(name).ok())
+            {
+                Some(file_name) => file_name,
+                None => continue,
+            };
+            let target = self.file_path(dir, &file_name);
+            if target == path {
+                continue;
+            }
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(&path, &target)?;
+            moved += 1;
+        }
+
+        if moved > 0 {
+            info!(
+                "Moved {} chunks in {} to a layout of depth {}",
+                moved,
+                dir.display(),
+                self.depth
+            );
+        }
+        remove_empty_dirs(dir);
+
+        let depth = bincode::serialize(&self.depth)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(marker, depth)
+    }
+}
+
+// Pushes the files found `levels` directories below `dir`. When `min_level` is below `levels`,
+// files found on the way down (from `min_level` onwards) are pushed as well.
+fn collect_files(
+    dir: &Path,
+    levels: u8,
+    min_level: u8,
+    files: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() && levels > 0 {
+            collect_files(
+                &entry.path(),
+                levels - 1,
+                min_level.saturating_sub(1),
+                files,
+            )?;
+        } else if file_type.is_file() && min_level == 0 {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// A walk over the chunk files of a layout in path order, which can be carried on a page at a
+/// time.
+///
+/// Each directory is read once, when the walk enters it, and only the entries of the directories
+/// it's in the middle of are held. Files stored in those since aren't listed, while files deleted
+/// since are skipped.
+pub(crate) struct ChunkWalk {
+    // The last file listed, or the one the walk started after.
+    after: Option<PathBuf>,
+    // Entries still to visit, with the number of levels below each, the next one last.
+    pending: Vec<(PathBuf, u8)>,
+}
+
+impl ChunkWalk {
+    /// The last file listed, or the one the walk started after.
+    pub fn last(&self) -> Option<&Path> {
+        self.after.as_deref()
+    }
+
+    /// Lists up to `limit` more chunk files. Files not named after a chunk, like temp files, are
+    /// left out.
+    pub fn next_files(&mut self, limit: usize) -> io::Result<Vec<PathBuf>> {
+        let mut files = vec![];
+        while files.len() < limit {
+            let (path, levels) = match self.pending.pop() {
+                Some(entry) => entry,
+                None => break,
+            };
+            if levels > 0 {
+                self.push_entries(&path, levels - 1)?;
+            } else if path.is_file() {
+                self.after = Some(path.clone());
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
+    // Queues the entries of `dir` which may hold, or be, chunk files after `self.after`.
+    fn push_entries(&mut self, dir: &Path, levels: u8) -> io::Result<()> {
+        let mut entries = vec![];
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            let wanted = if levels > 0 {
+                file_type.is_dir()
+            } else {
+                file_type.is_file() && is_chunk_file(&path)
+            };
+            // Directories holding `after` are entered too, to carry on from within them.
+            let is_after = match &self.after {
+                Some(after) => path > *after || (levels > 0 && after.starts_with(&path)),
+                None => true,
+            };
+            if wanted && is_after {
+                entries.push(path);
+            }
+        }
+        entries.sort();
+        self.pending
+            .extend(entries.into_iter().rev().map(|path| (path, levels)));
+        Ok(())
+    }
+}
+
+fn is_chunk_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| hex::decode(name).ok())
+        .is_some()
+}
+
+// Best effort removal of the shard directories left empty by a migration.
+fn remove_empty_dirs(dir: &Path) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        if !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            continue;
+        }
+        let path = entry.path();
+        remove_empty_dirs(&path);
+        let is_empty = fs::read_dir(&path)
+            .map(|mut entries| entries.next().is_none())
+            .unwrap_or(false);
+        if is_empty {
+            if let Err(err) = fs::remove_dir(&path) {
+                warn!("Could not remove {}: {}", path.display(), err);
+            }
+        }
+    }
+}
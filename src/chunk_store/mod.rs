@@ -6,48 +6,173 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-//! A simple, persistent, disk-based key-value store.
+//! A simple, persistent key-value store of chunks, over a pluggable storage backend.
 
+mod backend;
 mod chunk;
+mod codec;
 mod immutable;
+mod layout;
 mod mutable;
 mod register;
 mod sequence;
 #[cfg(test)]
 mod tests;
 mod used_space;
+mod volume;
 
 use crate::error::{Error, Result};
+use crate::storage_key::{self, StorageKey};
 use crate::utils;
+use backend::StorageBackend;
+pub use backend::StorageBackendKind;
 use chunk::{Chunk, ChunkId};
-use log::{info, trace};
-use sn_data_types::{register::Register, Blob, Map, Sequence};
+use codec::Codec;
+use futures::{stream, Stream, StreamExt};
+pub use layout::DEFAULT_DIR_DEPTH;
+use log::{error, info, trace, warn};
+use sn_data_types::{register::Register, Blob, DataAddress, Map, Sequence};
 use std::{
-    fs::{self, DirEntry, File, Metadata},
-    io::{Read, Write},
+    collections::{BTreeMap, VecDeque},
+    io,
     marker::PhantomData,
-    path::{Path, PathBuf},
+    path::Path,
+    sync::Arc,
 };
-use used_space::StoreId;
+use tokio::{sync::Semaphore, task};
 pub use used_space::UsedSpace;
+pub use volume::DataDir;
+use volume::Volume;
 
-const CHUNK_STORE_DIR: &str = "chunks";
+pub(crate) const CHUNK_STORE_DIR: &str = "chunks";
 
-/// The max name length for a chunk file.
-const MAX_CHUNK_FILE_NAME_LENGTH: usize = 104;
+/// Chunks which can't be read back are moved to `CHUNK_STORE_DIR/QUARANTINE_DIR/<chunk type>`.
+pub(crate) const QUARANTINE_DIR: &str = "quarantine";
+
+/// Default maximum number of disk operations run at once by the stores sharing a `StoreConfig`.
+pub const DEFAULT_MAX_DISK_OPS: usize = 32;
+
+/// Number of keys listed at once when going through all the keys of a store.
+const KEYS_PAGE_SIZE: usize = 1000;
 
 pub(crate) type BlobChunkStore = ChunkStore<Blob>;
 pub(crate) type MapChunkStore = ChunkStore<Map>;
 pub(crate) type SequenceChunkStore = ChunkStore<Sequence>;
 pub(crate) type RegisterChunkStore = ChunkStore<Register>;
 
-/// `ChunkStore` is a store of data held as serialised files on disk, implementing a maximum disk
-/// usage to restrict storage.
+/// Settings shared by all the `ChunkStore`s of a node.
+#[derive(Clone, Debug)]
+pub struct StoreConfig {
+    /// Where chunks are stored.
+    pub backend: StorageBackendKind,
+    /// Number of directory levels chunk files are spread over within a store, when stored on the
+    /// filesystem.
+    pub dir_depth: u8,
+    /// Whether every chunk is read back when a store is opened, quarantining those which can't be
+    /// deserialised. Otherwise only their sizes are checked, quarantining empty ones.
+    pub full_rescan: bool,
+    /// Names of the stores whose new chunks are compressed, e.g. "mutable". Chunks are read back
+    /// whether they were compressed or not.
+    pub compressed_stores: Vec<String>,
+    /// Key new chunks are encrypted with, if any. Chunks stored in the clear are still read back.
+    pub storage_key: Option<StorageKey>,
+    /// Maximum space in bytes the chunks of a data type may take, by type name, e.g. "blob".
+    /// Types without a quota may take all of the space left.
+    pub quotas: BTreeMap<String, u64>,
+    /// Dirs chunks are spread over, each with its own capacity. Without any, chunks are stored
+    /// under the root dir the store is opened at. Not used by the in-memory backend.
+    pub data_dirs: Vec<DataDir>,
+    /// Permits for the disk operations run at once, shared by all stores opened with this config
+    /// or a clone of it.
+    pub disk_ops: Arc<Semaphore>,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        Self {
+            backend: StorageBackendKind::Fs,
+            dir_depth: DEFAULT_DIR_DEPTH,
+            full_rescan: false,
+            compressed_stores: vec![],
+            storage_key: None,
+            quotas: BTreeMap::new(),
+            data_dirs: vec![],
+            disk_ops: Arc::new(Semaphore::new(DEFAULT_MAX_DISK_OPS)),
+        }
+    }
+}
+
+type Backend = Arc<dyn StorageBackend>;
+
+/// Where a listing of the keys of a `ChunkStore` carries on from: just after the last key listed,
+/// on the volume it was listed from.
+#[derive(Clone, Debug)]
+pub(crate) struct KeysCursor {
+    volume: usize,
+    after: Option<Vec<u8>>,
+}
+
+/// A page of the keys of a `ChunkStore`.
+#[derive(Debug)]
+pub(crate) struct KeysPage<Id> {
+    pub ids: Vec<Id>,
+    /// Where the next page starts, unless this is the last one.
+    pub next: Option<KeysCursor>,
+}
+
+/// What to do with the entries of a store which aren't chunks.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GcAction {
+    /// Only report them.
+    Report,
+    /// Move them to `CHUNK_STORE_DIR/QUARANTINE_DIR/<chunk type>`, where the backend keeps one.
+    Quarantine,
+    /// Remove them.
+    Remove,
+}
+
+/// The entries found by a garbage collection pass which aren't chunks.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Garbage {
+    /// Number of entries.
+    pub count: u64,
+    /// Their total size in bytes.
+    pub size: u64,
+}
+
+impl Garbage {
+    pub(crate) fn add(&mut self, size: u64) {
+        self.count += 1;
+        self.size = self.size.saturating_add(size);
+    }
+
+    pub(crate) fn merge(&mut self, other: Garbage) {
+        self.count += other.count;
+        self.size = self.size.saturating_add(other.size);
+    }
+}
+
+/// `ChunkStore` is a store of serialised data chunks, implementing a maximum disk usage to
+/// restrict storage.
+///
+/// Chunks are spread over the data dirs of the config, if any, each one being a `Volume` of the
+/// store. A volume whose dir can no longer be read, e.g. because its disk failed, is given up on:
+/// the chunks on it are lost, and left to the network to replicate again, while the other volumes
+/// carry on.
+///
+/// All backend operations run on tokio's blocking thread pool, so that a slow disk doesn't hold up
+/// the node's event loop, and are limited in number by the `disk_ops` permits of its config.
 pub(crate) struct ChunkStore<T: Chunk> {
-    dir: PathBuf,
-    // Maximum space allowed for all `ChunkStore`s to consume.
+    volumes: Vec<Volume>,
+    disk_ops: Arc<Semaphore>,
+    // Codec new chunks are compressed with, if any.
+    codec: Option<Codec>,
+    // Key new chunks are encrypted with, if any.
+    storage_key: Option<StorageKey>,
+    // Space used by all the `ChunkStore`s of the node, shared with them.
     used_space: UsedSpace,
-    id: StoreId,
+    // Maximum space allowed for this `ChunkStore` to consume, if less than all that's left.
+    quota: Option<u64>,
     _phantom: PhantomData<T>,
 }
 
@@ -56,92 +181,232 @@ where
     T: Chunk,
     Self: Subdir,
 {
-    /// Creates a new `ChunkStore` at location `root/CHUNK_STORE_DIR/<chunk type>`.
+    /// Creates a new `ChunkStore` at location `root/CHUNK_STORE_DIR/<chunk type>`, using the
+    /// storage backend selected in `config`. When `config` has data dirs, the store is spread
+    /// over `<data dir>/CHUNK_STORE_DIR/<chunk type>` instead.
     ///
     /// If the location specified already exists, the previous ChunkStore there is opened, otherwise
-    /// the required folder structure is created.
+    /// the required folder structure is created. An in-memory store doesn't touch `root`. A data
+    /// dir which can't be opened is left out, as long as another one can.
     ///
-    /// The maximum storage space is defined by `max_capacity`.  This specifies the max usable by
-    /// _all_ `ChunkStores`, not per `ChunkStore`.
-    pub async fn new<P: AsRef<Path>>(root: P, max_capacity: u64) -> Result<Self> {
-        let dir = root.as_ref().join(CHUNK_STORE_DIR).join(Self::subdir());
-
-        if fs::read(&dir).is_err() {
-            Self::create_new_root(&dir)?
+    /// The store is tracked in `used_space`, which the other `ChunkStore`s of the node should share
+    /// so that its `max_capacity` is the max usable by _all_ of them, not per `ChunkStore`. The
+    /// store may further be limited to the quota of its data type in `config`.
+    ///
+    /// Chunks left empty by an interrupted write are quarantined, and the space used by the store is
+    /// recomputed from the sizes of the others, the record of it being rewritten if it had drifted.
+    /// With `config.full_rescan`, every chunk is read back as well, and only those which can be
+    /// deserialised are kept and counted.
+    pub async fn new<P: AsRef<Path>>(
+        root: P,
+        used_space: &UsedSpace,
+        config: &StoreConfig,
+    ) -> Result<Self> {
+        let roots = if config.data_dirs.is_empty() || config.backend == StorageBackendKind::Memory {
+            vec![(root.as_ref().to_path_buf(), None)]
+        } else {
+            config
+                .data_dirs
+                .iter()
+                .map(|data_dir| (data_dir.path.clone(), Some(data_dir.max_capacity)))
+                .collect()
+        };
+
+        let mut volumes = vec![];
+        let mut last_error = None;
+        for (root, capacity) in &roots {
+            match Self::open_volume(root, *capacity, used_space, config).await {
+                Ok(volume) => volumes.push(volume),
+                Err(err) if roots.len() > 1 => {
+                    error!(
+                        "Leaving data dir {} out of the {} store: {}",
+                        root.display(),
+                        Self::subdir().display(),
+                        err
+                    );
+                    last_error = Some(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        if volumes.is_empty() {
+            return Err(last_error
+                .unwrap_or_else(|| Error::Logic("No dirs to store chunks in".to_string())));
         }
 
-        let used_space = UsedSpace::new(max_capacity);
-        let id = used_space.add_local_store(&dir).await?;
-        Ok(ChunkStore {
-            dir,
-            used_space,
-            id,
+        let codec = if config
+            .compressed_stores
+            .iter()
+            .any(|name| Path::new(name) == Self::subdir())
+        {
+            Some(Codec::Deflate)
+        } else {
+            None
+        };
+
+        let mut store = ChunkStore {
+            volumes,
+            disk_ops: config.disk_ops.clone(),
+            codec,
+            storage_key: config.storage_key.clone(),
+            used_space: used_space.clone(),
+            quota: config.quotas.get(T::TYPE_NAME).copied(),
             _phantom: PhantomData,
-        })
+        };
+        store.reconcile_used_space(config.full_rescan).await?;
+        Ok(store)
     }
-}
 
-impl<T: Chunk> ChunkStore<T> {
-    fn create_new_root(root: &Path) -> Result<()> {
-        fs::create_dir_all(root)?;
-
-        // Verify that chunk files can be created.
-        let temp_file_path = root.join("0".repeat(MAX_CHUNK_FILE_NAME_LENGTH));
-        let _ = File::create(&temp_file_path)?;
-        fs::remove_file(temp_file_path)?;
+    async fn open_volume(
+        root: &Path,
+        capacity: Option<u64>,
+        used_space: &UsedSpace,
+        config: &StoreConfig,
+    ) -> Result<Volume> {
+        let dir = root.join(CHUNK_STORE_DIR).join(Self::subdir());
+        let quarantine_dir = root
+            .join(CHUNK_STORE_DIR)
+            .join(QUARANTINE_DIR)
+            .join(Self::subdir());
+
+        let backend = {
+            let (dir, config) = (dir.clone(), config.clone());
+            run_blocking(&config.disk_ops.clone(), move || {
+                backend::open(&dir, &quarantine_dir, &config)
+            })
+            .await?
+        };
+        let id = match config.backend {
+            StorageBackendKind::Memory => used_space.add_volatile_store().await,
+            _ => used_space.add_local_store(&dir).await?,
+        };
+        Ok(Volume::new(root, dir, backend, id, capacity))
+    }
 
+    // The record of used space can drift from what is actually stored after a crash, or after
+    // chunks have been removed by hand.
+    async fn reconcile_used_space(&mut self, deep: bool) -> Result<()> {
+        for index in self.healthy_volumes() {
+            let actual = match self.recover(index, deep).await {
+                Ok(actual) => actual,
+                Err(_) if self.volumes[index].is_failed() => continue,
+                Err(err) => return Err(err),
+            };
+            let recorded = self
+                .used_space
+                .reconcile(self.volumes[index].id, actual)
+                .await?;
+            if recorded != actual {
+                warn!(
+                    "Used space of the {} store in {} was recorded as {} bytes, but its chunks take {} bytes",
+                    Self::subdir().display(),
+                    self.volumes[index].root.display(),
+                    recorded,
+                    actual
+                );
+            }
+        }
         Ok(())
     }
+}
 
+impl<T: Chunk> ChunkStore<T> {
     /// Stores a new data chunk.
     ///
-    /// If there is not enough storage space available, returns `Error::NotEnoughSpace`.  In case of
-    /// an IO error, it returns `Error::Io`.
+    /// A new chunk is placed on the volume with the most space left. If there is not enough
+    /// storage space available, returns `Error::NotEnoughSpace`, or `Error::NotEnoughSpaceFor` if
+    /// the chunk would exceed the quota of its type.  In case of an IO error, it returns
+    /// `Error::Io`.
     ///
-    /// If a chunk with the same id already exists, it will be overwritten.
+    /// If a chunk with the same id already exists, it will be overwritten, on the volume it's on.
+    ///
+    /// The space consumed is that of the chunk as stored, i.e. after any compression and
+    /// encryption.
     pub async fn put(&mut self, chunk: &T) -> Result<()> {
         info!("Writing chunk");
-        let serialised_chunk = utils::serialise(chunk)?;
-        let consumed_space = serialised_chunk.len() as u64;
+        let mut contents = codec::encode(self.codec, &utils::serialise(chunk)?)?;
+        if let Some(key) = &self.storage_key {
+            contents = key.encrypt(&contents)?;
+        }
+        let consumed_space = contents.len() as u64;
 
         info!("consumed space: {:?}", consumed_space);
         info!("max : {:?}", self.used_space.max_capacity().await);
         info!("use space total : {:?}", self.used_space.total().await);
 
-        let file_path = self.file_path(chunk.id())?;
-        self.do_delete(&file_path).await?;
+        let key = utils::serialise(chunk.id())?;
+        // Any chunk being overwritten stays in place until the new one replaces it, so only the
+        // growth needs reserving up front, and any shrinkage is released after the write.
+        let (index, replaced_space) = match self.find(&key).await? {
+            Some(found) => found,
+            None => (self.place(consumed_space).await?, 0),
+        };
+        let reserved_space = consumed_space.saturating_sub(replaced_space);
+        let released_space = replaced_space.saturating_sub(consumed_space);
+
+        if let Some(quota) = self.quota {
+            let local = self.local_used_space().await;
+            if local.saturating_add(reserved_space) > quota {
+                return Err(Error::NotEnoughSpaceFor(T::TYPE_NAME.to_string()));
+            }
+        }
 
         // pre-reserve space
-        self.used_space.increase(self.id, consumed_space).await?;
+        let id = self.volumes[index].id;
+        match self.volumes[index].capacity {
+            Some(capacity) => {
+                self.used_space
+                    .increase_within(id, reserved_space, &self.volumes[index].root, capacity)
+                    .await?
+            }
+            None => self.used_space.increase(id, reserved_space).await?,
+        }
         trace!(
             "use space total after add: {:?}",
             self.used_space.total().await
         );
 
-        let res = File::create(&file_path).and_then(|mut file| {
-            file.write_all(&serialised_chunk)?;
-            file.sync_all()
-        });
-
-        match res {
-            Ok(_) => {
+        match self
+            .with_backend(index, move |backend| backend.put(&key, &contents))
+            .await
+        {
+            Ok(()) => {
                 info!("Writing chunk succeeded!");
+                if released_space > 0 {
+                    self.used_space.decrease(id, released_space).await?;
+                }
                 Ok(())
             }
             Err(e) => {
                 info!("Writing chunk failed!");
-                self.used_space.decrease(self.id, consumed_space).await?;
-                Err(e.into())
+                self.used_space.decrease(id, reserved_space).await?;
+                Err(e)
             }
         }
     }
 
+    /// Returns the size of the chunk stored under `id` as stored, i.e. after any compression and
+    /// encryption, if it's stored.
+    pub async fn stored_size(&self, id: &T::Id) -> Result<Option<u64>> {
+        let key = utils::serialise(id)?;
+        Ok(self.find(&key).await?.map(|(_, size)| size))
+    }
+
     /// Deletes the data chunk stored under `id`.
     ///
     /// If the data doesn't exist, it does nothing and returns `Ok`.  In the case of an IO error, it
     /// returns `Error::Io`.
     pub async fn delete(&mut self, id: &T::Id) -> Result<()> {
-        self.do_delete(&self.file_path(id)?).await
+        let key = utils::serialise(id)?;
+        if let Some((index, size)) = self.find(&key).await? {
+            // The space is only released once the chunk is gone, so that a failed delete leaves
+            // it accounted for.
+            self.with_backend(index, move |backend| backend.delete(&key))
+                .await?;
+            self.used_space.decrease(self.volumes[index].id, size).await
+        } else {
+            Ok(())
+        }
     }
 
     /// Used space to max space ratio.
@@ -157,61 +422,403 @@ impl<T: Chunk> ChunkStore<T> {
 
     /// Returns a data chunk previously stored under `id`.
     ///
-    /// If the data file can't be accessed, it returns `Error::NoSuchChunk`.
-    pub fn get(&self, id: &T::Id) -> Result<T> {
-        let mut file = File::open(self.file_path(id)?)
-            .map_err(|_| Error::NoSuchChunk(id.to_data_address()))?;
-        let mut contents = vec![];
-        let _ = file.read_to_end(&mut contents)?;
-        let chunk = bincode::deserialize::<T>(&contents)?;
-        // Check it's the requested chunk variant.
-        if chunk.id() == id {
-            Ok(chunk)
+    /// If the chunk isn't stored, it returns `Error::NoSuchChunk`. If the chunk read back doesn't
+    /// match `id`, or fails to decrypt, it returns `Error::CorruptChunk`.
+    pub async fn get(&self, id: &T::Id) -> Result<T> {
+        let key = utils::serialise(id)?;
+        let mut contents = None;
+        for index in self.healthy_volumes() {
+            let key = key.clone();
+            match self
+                .with_backend(index, move |backend| backend.get(&key))
+                .await
+            {
+                Ok(Some(found)) => {
+                    contents = Some(found);
+                    break;
+                }
+                Ok(None) => (),
+                Err(_) if self.volumes[index].is_failed() => (),
+                Err(err) => return Err(err),
+            }
+        }
+        let contents = contents.ok_or_else(|| Error::NoSuchChunk(id.to_data_address()))?;
+        let contents = match self.decrypt(contents) {
+            Err(Error::Decryption) => return Err(Error::CorruptChunk(id.to_data_address())),
+            result => result?,
+        };
+        let chunk = bincode::deserialize::<T>(&codec::decode(contents))?;
+        // The key is derived from the id, so a mismatch means the content has changed in storage.
+        // Blobs derive their name from their content when deserialised, which makes this a full
+        // integrity check for them.
+        if chunk.id() != id {
+            Err(Error::CorruptChunk(id.to_data_address()))
         } else {
-            Err(Error::NoSuchChunk(id.to_data_address()))
+            Ok(chunk)
         }
     }
 
+    /// Reads back every stored chunk, returning the addresses of those which aren't intact.
+    pub async fn verify(&self) -> Vec<DataAddress> {
+        let mut damaged = vec![];
+        let ids = self.key_stream();
+        futures::pin_mut!(ids);
+        while let Some(id) = ids.next().await {
+            let result = match id {
+                Ok(id) => self
+                    .get(&id)
+                    .await
+                    .map_err(|err| (id.to_data_address(), err)),
+                Err(err) => {
+                    warn!("Could not list the chunks to verify: {}", err);
+                    break;
+                }
+            };
+            if let Err((address, err)) = result {
+                warn!("Chunk {:?} failed verification: {}", address, err);
+                damaged.push(address);
+            }
+        }
+        damaged
+    }
+
     pub async fn total_used_space(&self) -> u64 {
         self.used_space.total().await
     }
 
     /// Tests if a data chunk has been previously stored under `id`.
-    pub fn has(&self, id: &T::Id) -> bool {
-        if let Ok(path) = self.file_path(id) {
-            fs::metadata(path)
-                .as_ref()
-                .map(Metadata::is_file)
-                .unwrap_or(false)
-        } else {
-            false
-        }
+    pub async fn has(&self, id: &T::Id) -> bool {
+        let key = match utils::serialise(id) {
+            Ok(key) => key,
+            Err(_) => return false,
+        };
+        matches!(self.find(&key).await, Ok(Some(_)))
     }
 
     /// Lists all keys of currently stored data.
-    #[cfg_attr(not(test), allow(unused))]
-    pub fn keys(&self) -> Vec<T::Id> {
-        fs::read_dir(&self.dir)
-            .map(|entries| {
-                entries
-                    .filter_map(|entry| to_chunk_id(&entry.ok()?))
-                    .collect()
-            })
-            .unwrap_or_else(|_| Vec::new())
+    #[cfg(test)]
+    pub async fn keys(&self) -> Result<Vec<T::Id>> {
+        futures::TryStreamExt::try_collect(self.key_stream()).await
     }
 
-    async fn do_delete(&mut self, file_path: &Path) -> Result<()> {
-        if let Ok(metadata) = fs::metadata(file_path) {
-            self.used_space.decrease(self.id, metadata.len()).await?;
-            fs::remove_file(file_path).map_err(From::from)
+    /// Lists up to `limit` keys of currently stored data, starting at `cursor`, or from the
+    /// first one without it.
+    ///
+    /// Keys are listed one volume after the other, in the order of the backend, which only depends
+    /// on the keys themselves, so a listing can be carried on while chunks are stored and deleted.
+    /// Chunks stored meanwhile are only listed if they fall after the cursor.
+    pub async fn keys_page(
+        &self,
+        cursor: Option<&KeysCursor>,
+        limit: usize,
+    ) -> Result<KeysPage<T::Id>> {
+        let (mut index, mut after) = match cursor {
+            Some(cursor) => (cursor.volume, cursor.after.clone()),
+            None => (0, None),
+        };
+        let mut keys = vec![];
+        while index < self.volumes.len() && keys.len() < limit {
+            let wanted = limit - keys.len();
+            let page = if self.volumes[index].is_failed() {
+                vec![]
+            } else {
+                let after = after.clone();
+                match self
+                    .with_backend(index, move |backend| {
+                        backend.keys_after(after.as_deref(), wanted)
+                    })
+                    .await
+                {
+                    Ok(page) => page,
+                    Err(_) if self.volumes[index].is_failed() => vec![],
+                    Err(err) => return Err(err),
+                }
+            };
+            if page.len() < wanted {
+                index += 1;
+                after = None;
+            } else {
+                after = page.last().cloned();
+            }
+            keys.extend(page);
+        }
+
+        let next = if keys.len() < limit {
+            None
         } else {
-            Ok(())
+            Some(KeysCursor {
+                volume: index,
+                after,
+            })
+        };
+        Ok(KeysPage {
+            ids: keys
+                .iter()
+                .filter_map(|key| bincode::deserialize(key).ok())
+                .collect(),
+            next,
+        })
+    }
+
+    /// Streams all keys of currently stored data, listing them a page at a time. Ends after the
+    /// first error.
+    pub fn key_stream(&self) -> impl Stream<Item = Result<T::Id>> + '_ {
+        let start = (Some(None), VecDeque::new());
+        stream::try_unfold(start, move |(mut cursor, mut ids)| async move {
+            loop {
+                if let Some(id) = ids.pop_front() {
+                    return Ok(Some((id, (cursor, ids))));
+                }
+                let current: Option<KeysCursor> = match cursor {
+                    Some(current) => current,
+                    None => return Ok(None),
+                };
+                let page = self.keys_page(current.as_ref(), KEYS_PAGE_SIZE).await?;
+                ids = page.ids.into();
+                cursor = page.next.map(Some);
+            }
+        })
+    }
+
+    // Moves the chunks of the volume at `index` which can't be read back out of the store, and
+    // returns the space taken by the others. Empty chunks are always moved out, while with `deep`
+    // every chunk is read back in full.
+    async fn recover(&self, index: usize, deep: bool) -> Result<u64> {
+        let mut size = 0_u64;
+        let mut after = None;
+        loop {
+            let (chunks, last) = self
+                .with_backend(index, move |backend| {
+                    let keys = backend.keys_after(after.as_deref(), KEYS_PAGE_SIZE)?;
+                    let mut chunks = vec![];
+                    for key in keys.iter().filter(|key| is_chunk_key::<T>(key)) {
+                        if let Some(size) = backend.size(key)? {
+                            chunks.push((key.clone(), size));
+                        }
+                    }
+                    Ok((chunks, keys.last().cloned()))
+                })
+                .await?;
+            for (key, chunk_size) in chunks {
+                let intact = chunk_size > 0 && (!deep || self.is_intact(index, &key).await?);
+                if intact {
+                    size = size.saturating_add(chunk_size);
+                } else {
+                    self.with_backend(index, move |backend| backend.quarantine(&key))
+                        .await?;
+                }
+            }
+            after = match last {
+                Some(last) => Some(last),
+                None => return Ok(size),
+            };
+        }
+    }
+
+    // Whether the chunk stored under `key` on the volume at `index` reads back as the chunk with
+    // the id serialised in `key`.
+    async fn is_intact(&self, index: usize, key: &[u8]) -> Result<bool> {
+        let id = match bincode::deserialize::<T::Id>(key) {
+            Ok(id) => id,
+            Err(_) => return Ok(false),
+        };
+        let contents = {
+            let key = key.to_vec();
+            match self
+                .with_backend(index, move |backend| backend.get(&key))
+                .await?
+            {
+                Some(contents) => contents,
+                // Deleted since it was listed.
+                None => return Ok(true),
+            }
+        };
+        Ok(match self.decrypt(contents) {
+            Err(Error::Decryption) => false,
+            result => {
+                let chunk = bincode::deserialize::<T>(&codec::decode(result?)).ok();
+                matches!(chunk, Some(chunk) if *chunk.id() == id)
+            }
+        })
+    }
+
+    /// Finds the entries of the store which aren't chunks, on all of its volumes, and deals with
+    /// them according to `action`. They're not counted in the used space either way.
+    pub async fn collect_garbage(&mut self, action: GcAction) -> Result<Garbage> {
+        let mut garbage = Garbage::default();
+        for index in self.healthy_volumes() {
+            garbage.merge(
+                self.with_backend(index, move |backend| {
+                    backend.collect_garbage(is_chunk_key::<T>, action)
+                })
+                .await?,
+            );
+        }
+        if garbage.count > 0 {
+            info!(
+                "Found {} entries taking {} bytes in the {} store which aren't chunks",
+                garbage.count,
+                garbage.size,
+                T::TYPE_NAME
+            );
+        }
+        Ok(garbage)
+    }
+
+    // Returns the index of the volume holding the chunk stored under `key`, and its size.
+    async fn find(&self, key: &[u8]) -> Result<Option<(usize, u64)>> {
+        for index in self.healthy_volumes() {
+            let key = key.to_vec();
+            match self
+                .with_backend(index, move |backend| backend.size(&key))
+                .await
+            {
+                Ok(Some(size)) => return Ok(Some((index, size))),
+                Ok(None) => (),
+                Err(_) if self.volumes[index].is_failed() => (),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(None)
+    }
+
+    // Returns the index of the volume with the most space left, as long as `size` fits in it.
+    async fn place(&self, size: u64) -> Result<usize> {
+        let mut best = None;
+        for index in self.healthy_volumes() {
+            let volume = &self.volumes[index];
+            let free = match volume.capacity {
+                Some(capacity) => {
+                    capacity.saturating_sub(self.used_space.used_under(&volume.root).await)
+                }
+                None => u64::MAX,
+            };
+            let better = match best {
+                Some((_, most)) => free > most,
+                None => true,
+            };
+            if free >= size && better {
+                best = Some((index, free));
+            }
+        }
+        best.map(|(index, _)| index).ok_or(Error::NotEnoughSpace)
+    }
+
+    // Space used by this store, over all its volumes.
+    async fn local_used_space(&self) -> u64 {
+        let mut used = 0_u64;
+        for volume in &self.volumes {
+            used = used.saturating_add(self.used_space.local(volume.id).await);
+        }
+        used
+    }
+
+    fn healthy_volumes(&self) -> Vec<usize> {
+        (0..self.volumes.len())
+            .filter(|index| !self.volumes[*index].is_failed())
+            .collect()
+    }
+
+    // Runs `op` on the backend of the volume at `index`, checking the volume after an I/O error.
+    async fn with_backend<F, R>(&self, index: usize, op: F) -> Result<R>
+    where
+        F: FnOnce(&dyn StorageBackend) -> io::Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let backend = self.volumes[index].backend.clone();
+        let result = run_blocking(&self.disk_ops, move || op(backend.as_ref())).await;
+        if let Err(Error::Io(_)) = &result {
+            self.check_volume(index).await;
+        }
+        result
+    }
+
+    // Called after an I/O error on the volume at `index`. If its dir can't even be read anymore,
+    // the volume is given up on, and the space its chunks took is released. A store with a single
+    // volume keeps returning the errors instead.
+    async fn check_volume(&self, index: usize) {
+        let volume = &self.volumes[index];
+        if self.volumes.len() < 2
+            || tokio::fs::read_dir(&volume.dir).await.is_ok()
+            || !volume.fail()
+        {
+            return;
+        }
+        error!(
+            "Can't read {} anymore, giving up on the chunks stored in it",
+            volume.dir.display()
+        );
+        self.used_space.remove_local_store(volume.id).await;
+    }
+
+    // Returns the stored contents in the clear. Chunks stored before encryption was enabled are
+    // returned as they are.
+    fn decrypt(&self, contents: Vec<u8>) -> Result<Vec<u8>> {
+        if !storage_key::is_encrypted(&contents) {
+            return Ok(contents);
+        }
+        match &self.storage_key {
+            Some(key) => key.decrypt(&contents),
+            None => Err(Error::StorageKeyRequired),
         }
     }
+}
 
-    fn file_path(&self, id: &T::Id) -> Result<PathBuf> {
-        Ok(self.dir.join(&hex::encode(utils::serialise(id)?)))
+// Whether `key` is the serialised id of a `T`, rather than something else stored alongside.
+fn is_chunk_key<T: Chunk>(key: &[u8]) -> bool {
+    bincode::deserialize::<T::Id>(key).is_ok()
+}
+
+/// Runs `op` on the blocking thread pool once one of the `disk_ops` permits is free.
+///
+/// The permit is held by `op` itself, for as long as it runs, even if the caller stops waiting on
+/// it. Backends synchronise their own storage, so nothing else is locked meanwhile.
+async fn run_blocking<F, R>(disk_ops: &Arc<Semaphore>, op: F) -> Result<R>
+where
+    F: FnOnce() -> io::Result<R> + Send + 'static,
+    R: Send + 'static,
+{
+    let permit = disk_ops
+        .clone()
+        .acquire_owned()
+        .await
+        .map_err(|_| Error::Logic("No more disk operations are permitted".to_string()))?;
+    task::spawn_blocking(move || {
+        let _permit = permit;
+        op()
+    })
+    .await
+    .map_err(|err| Error::Logic(format!("Disk operation did not complete: {}", err)))?
+    .map_err(Error::Io)
+}
+
+/// Gives a generation to the used space records of the stores under `root` which were written
+/// before the journal was introduced. Returns the number of records rewritten.
+pub(crate) async fn upgrade_used_space_records(root: &Path) -> Result<usize> {
+    let mut upgraded = 0;
+    for subdir in &[
+        BlobChunkStore::subdir(),
+        MapChunkStore::subdir(),
+        SequenceChunkStore::subdir(),
+        RegisterChunkStore::subdir(),
+    ] {
+        let dir = root.join(CHUNK_STORE_DIR).join(subdir);
+        if used_space::upgrade_legacy_record(&dir).await? {
+            upgraded += 1;
+        }
     }
+    Ok(upgraded)
+}
+
+/// Whether `name` is the name of one of the node's stores, as used for its directory.
+pub(crate) fn is_store_name(name: &str) -> bool {
+    [
+        BlobChunkStore::subdir(),
+        MapChunkStore::subdir(),
+        SequenceChunkStore::subdir(),
+        RegisterChunkStore::subdir(),
+    ]
+    .contains(&Path::new(name))
 }
 
 pub(crate) trait Subdir {
@@ -241,10 +848,3 @@ impl Subdir for RegisterChunkStore {
         Path::new("register")
     }
 }
-
-fn to_chunk_id<T: ChunkId>(entry: &DirEntry) -> Option<T> {
-    let file_name = entry.file_name();
-    let file_name = file_name.into_string().ok()?;
-    let bytes = hex::decode(file_name).ok()?;
-    bincode::deserialize(&bytes).ok()
-}
@@ -11,6 +11,7 @@ use sn_data_types::{DataAddress, Map, MapAddress};
 
 impl Chunk for Map {
     type Id = MapAddress;
+    const TYPE_NAME: &'static str = "map";
     fn id(&self) -> &Self::Id {
         match self {
             Map::Seq(ref chunk) => chunk.address(),
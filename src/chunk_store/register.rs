@@ -14,6 +14,7 @@ use sn_data_types::{
 
 impl Chunk for Register {
     type Id = Address;
+    const TYPE_NAME: &'static str = "register";
     fn id(&self) -> &Self::Id {
         self.address()
     }
@@ -11,6 +11,7 @@ use sn_data_types::{DataAddress, Sequence, SequenceAddress};
 
 impl Chunk for Sequence {
     type Id = SequenceAddress;
+    const TYPE_NAME: &'static str = "sequence";
     fn id(&self) -> &Self::Id {
         self.address()
     }
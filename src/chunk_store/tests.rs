@@ -7,16 +7,25 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
+    backend::fs::TEMP_FILE_EXTENSION,
     chunk::{Chunk, ChunkId},
-    ChunkStore, Result as ChunkStoreResult, Subdir,
+    layout::Layout,
+    ChunkStore, DataDir, Garbage, GcAction, Result as ChunkStoreResult, StorageBackendKind,
+    StoreConfig, Subdir, UsedSpace, CHUNK_STORE_DIR, QUARANTINE_DIR,
 };
-use crate::{to_db_key::ToDbKey, Error, Result};
+use crate::{storage_key::StorageKey, to_db_key::ToDbKey, utils, Error, Result};
 use rand::{distributions::Standard, rngs::ThreadRng, Rng};
 use serde::{Deserialize, Serialize};
-use sn_data_types::{BlobAddress, DataAddress};
+use sn_data_types::{Blob, BlobAddress, DataAddress, PublicBlob};
 use sn_routing::XorName;
-use std::{path::Path, u64};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+    u64,
+};
 use tempdir::TempDir;
+use tokio::sync::Semaphore;
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
 struct Data {
@@ -26,6 +35,7 @@ struct Data {
 
 impl Chunk for Data {
     type Id = Id;
+    const TYPE_NAME: &'static str = "test";
 
     fn id(&self) -> &Self::Id {
         &self.id
@@ -59,6 +69,21 @@ fn temp_dir() -> ChunkStoreResult<TempDir> {
     TempDir::new("test").map_err(|e| Error::TempDirCreationFailed(e.to_string()))
 }
 
+fn store_dir<T: Chunk>(root: &Path) -> PathBuf
+where
+    ChunkStore<T>: Subdir,
+{
+    root.join(CHUNK_STORE_DIR).join(ChunkStore::<T>::subdir())
+}
+
+// Path of the file a store on the filesystem backend keeps the chunk with `id` in.
+fn file_path<T: Chunk>(root: &Path, config: &StoreConfig, id: &T::Id) -> Result<PathBuf>
+where
+    ChunkStore<T>: Subdir,
+{
+    Ok(Layout::new(config.dir_depth).file_path(&store_dir::<T>(root), &utils::serialise(id)?))
+}
+
 struct Chunks {
     data_and_sizes: Vec<(Vec<u8>, u64)>,
     total_size: u64,
@@ -94,7 +119,12 @@ async fn successful_put() -> Result<()> {
     let chunks = Chunks::gen(&mut rng)?;
 
     let root = temp_dir()?;
-    let mut chunk_store = ChunkStore::<Data>::new(root.path(), u64::MAX).await?;
+    let mut chunk_store = ChunkStore::<Data>::new(
+        root.path(),
+        &UsedSpace::new(u64::MAX),
+        &StoreConfig::default(),
+    )
+    .await?;
 
     for (index, (data, size)) in chunks.data_and_sizes.iter().enumerate().rev() {
         let the_data = &Data {
@@ -102,17 +132,17 @@ async fn successful_put() -> Result<()> {
             value: data.clone(),
         };
         let used_space_before = chunk_store.total_used_space().await;
-        assert!(!chunk_store.has(&the_data.id));
+        assert!(!chunk_store.has(&the_data.id).await);
         chunk_store.put(the_data).await?;
         let used_space_after = chunk_store.total_used_space().await;
         assert_eq!(used_space_after, used_space_before + size);
-        assert!(chunk_store.has(&the_data.id));
+        assert!(chunk_store.has(&the_data.id).await);
         assert!(used_space_after <= chunks.total_size);
     }
 
     assert_eq!(chunk_store.total_used_space().await, chunks.total_size);
 
-    let mut keys = chunk_store.keys();
+    let mut keys = chunk_store.keys().await?;
     keys.sort();
     assert_eq!(
         (0..chunks.data_and_sizes.len())
@@ -129,7 +159,12 @@ async fn failed_put_when_not_enough_space() -> Result<()> {
     let mut rng = new_rng();
     let root = temp_dir()?;
     let capacity = 32;
-    let mut chunk_store = ChunkStore::new(root.path(), capacity).await?;
+    let mut chunk_store = ChunkStore::new(
+        root.path(),
+        &UsedSpace::new(capacity),
+        &StoreConfig::default(),
+    )
+    .await?;
 
     let data = Data {
         id: Id(rng.gen()),
@@ -141,9 +176,64 @@ async fn failed_put_when_not_enough_space() -> Result<()> {
 
     match chunk_store.put(&data).await {
         Err(Error::NotEnoughSpace) => (),
-        x => return Err(crate::Error::Logic(format!("Unexpected: {:?}", x))),
+        x => return Err(Error::Logic(format!("Unexpected: {:?}", x))),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn stores_share_the_used_space() -> Result<()> {
+    let root = temp_dir()?;
+    let data = Data {
+        id: Id(0),
+        value: vec![0; 32],
+    };
+    let capacity = bincode::serialized_size(&data).map_err(Error::Bincode)? + 10;
+    let used_space = UsedSpace::new(capacity);
+    let config = StoreConfig::default();
+    let mut data_store = ChunkStore::<Data>::new(root.path(), &used_space, &config).await?;
+    let mut blob_store = ChunkStore::<Blob>::new(root.path(), &used_space, &config).await?;
+
+    data_store.put(&data).await?;
+    assert_eq!(
+        blob_store.total_used_space().await,
+        used_space.total().await
+    );
+    let blob = Blob::Public(PublicBlob::new(vec![1; 10]));
+    assert!(matches!(
+        blob_store.put(&blob).await,
+        Err(Error::NotEnoughSpace)
+    ));
+
+    data_store.delete(&data.id).await?;
+    blob_store.put(&blob).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_put_when_over_quota() -> Result<()> {
+    let root = temp_dir()?;
+    let mut config = StoreConfig::default();
+    let _ = config.quotas.insert(Data::TYPE_NAME.to_string(), 32);
+    let used_space = UsedSpace::new(u64::MAX);
+    let mut data_store = ChunkStore::<Data>::new(root.path(), &used_space, &config).await?;
+    let mut blob_store = ChunkStore::<Blob>::new(root.path(), &used_space, &config).await?;
+
+    let data = Data {
+        id: Id(0),
+        value: vec![0; 32],
+    };
+    match data_store.put(&data).await {
+        Err(Error::NotEnoughSpaceFor(name)) => assert_eq!(name, Data::TYPE_NAME),
+        x => return Err(Error::Logic(format!("Unexpected: {:?}", x))),
     }
+    assert_eq!(used_space.total().await, 0);
 
+    // Other data types aren't limited by the quota.
+    blob_store
+        .put(&Blob::Public(PublicBlob::new(vec![1; 32])))
+        .await?;
     Ok(())
 }
 
@@ -153,7 +243,12 @@ async fn delete() -> Result<()> {
     let chunks = Chunks::gen(&mut rng)?;
 
     let root = temp_dir()?;
-    let mut chunk_store = ChunkStore::new(root.path(), u64::MAX).await?;
+    let mut chunk_store = ChunkStore::new(
+        root.path(),
+        &UsedSpace::new(u64::MAX),
+        &StoreConfig::default(),
+    )
+    .await?;
 
     for (index, (data, size)) in chunks.data_and_sizes.iter().enumerate() {
         let the_data = &Data {
@@ -162,9 +257,9 @@ async fn delete() -> Result<()> {
         };
         chunk_store.put(the_data).await?;
         assert_eq!(chunk_store.total_used_space().await, *size);
-        assert!(chunk_store.has(&the_data.id));
+        assert!(chunk_store.has(&the_data.id).await);
         chunk_store.delete(&the_data.id).await?;
-        assert!(!chunk_store.has(&the_data.id));
+        assert!(!chunk_store.has(&the_data.id).await);
         assert_eq!(chunk_store.total_used_space().await, 0);
     }
 
@@ -177,7 +272,12 @@ async fn put_and_get_value_should_be_same() -> Result<()> {
     let chunks = Chunks::gen(&mut rng)?;
 
     let root = temp_dir()?;
-    let mut chunk_store = ChunkStore::new(root.path(), u64::MAX).await?;
+    let mut chunk_store = ChunkStore::new(
+        root.path(),
+        &UsedSpace::new(u64::MAX),
+        &StoreConfig::default(),
+    )
+    .await?;
 
     for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
         chunk_store
@@ -189,7 +289,7 @@ async fn put_and_get_value_should_be_same() -> Result<()> {
     }
 
     for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
-        let retrieved_value = chunk_store.get(&Id(index as u64))?;
+        let retrieved_value = chunk_store.get(&Id(index as u64)).await?;
         assert_eq!(*data, retrieved_value.value);
     }
 
@@ -202,7 +302,12 @@ async fn overwrite_value() -> Result<()> {
     let chunks = Chunks::gen(&mut rng)?;
 
     let root = temp_dir()?;
-    let mut chunk_store = ChunkStore::new(root.path(), u64::MAX).await?;
+    let mut chunk_store = ChunkStore::new(
+        root.path(),
+        &UsedSpace::new(u64::MAX),
+        &StoreConfig::default(),
+    )
+    .await?;
 
     for (data, size) in chunks.data_and_sizes {
         chunk_store
@@ -212,7 +317,7 @@ async fn overwrite_value() -> Result<()> {
             })
             .await?;
         assert_eq!(chunk_store.total_used_space().await, size);
-        let retrieved_data = chunk_store.get(&Id(0))?;
+        let retrieved_data = chunk_store.get(&Id(0)).await?;
         assert_eq!(data, retrieved_data.value);
     }
 
@@ -222,12 +327,17 @@ async fn overwrite_value() -> Result<()> {
 #[tokio::test]
 async fn get_fails_when_key_does_not_exist() -> Result<()> {
     let root = temp_dir()?;
-    let chunk_store: ChunkStore<Data> = ChunkStore::new(root.path(), u64::MAX).await?;
+    let chunk_store: ChunkStore<Data> = ChunkStore::new(
+        root.path(),
+        &UsedSpace::new(u64::MAX),
+        &StoreConfig::default(),
+    )
+    .await?;
 
     let id = Id(new_rng().gen());
-    match chunk_store.get(&id) {
+    match chunk_store.get(&id).await {
         Err(Error::NoSuchChunk(_)) => (),
-        x => return Err(crate::Error::Logic(format!("Unexpected {:?}", x))),
+        x => return Err(Error::Logic(format!("Unexpected {:?}", x))),
     }
 
     Ok(())
@@ -239,11 +349,16 @@ async fn keys() -> Result<()> {
     let chunks = Chunks::gen(&mut rng)?;
 
     let root = temp_dir()?;
-    let mut chunk_store = ChunkStore::new(root.path(), u64::MAX).await?;
+    let mut chunk_store = ChunkStore::new(
+        root.path(),
+        &UsedSpace::new(u64::MAX),
+        &StoreConfig::default(),
+    )
+    .await?;
 
     for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
         let id = Id(index as u64);
-        assert!(!chunk_store.keys().contains(&id));
+        assert!(!chunk_store.keys().await?.contains(&id));
         chunk_store
             .put(&Data {
                 id,
@@ -251,7 +366,7 @@ async fn keys() -> Result<()> {
             })
             .await?;
 
-        let keys = chunk_store.keys();
+        let keys = chunk_store.keys().await?;
         assert!(keys.contains(&id));
         assert_eq!(keys.len(), index + 1);
     }
@@ -259,13 +374,841 @@ async fn keys() -> Result<()> {
     for (index, _) in chunks.data_and_sizes.iter().enumerate() {
         let id = Id(index as u64);
 
-        assert!(chunk_store.keys().contains(&id));
+        assert!(chunk_store.keys().await?.contains(&id));
         chunk_store.delete(&id).await?;
 
-        let keys = chunk_store.keys();
+        let keys = chunk_store.keys().await?;
         assert!(!keys.contains(&id));
         assert_eq!(keys.len(), chunks.data_and_sizes.len() - index - 1);
     }
 
     Ok(())
 }
+
+#[tokio::test]
+async fn chunks_are_spread_over_dir_depth() -> Result<()> {
+    let root = temp_dir()?;
+    let config = StoreConfig {
+        dir_depth: 2,
+        ..StoreConfig::default()
+    };
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+
+    let id = Id(new_rng().gen());
+    chunk_store
+        .put(&Data {
+            id,
+            value: vec![1, 2, 3],
+        })
+        .await?;
+
+    let path = file_path::<Data>(root.path(), &config, &id)?;
+    assert!(path.is_file());
+    assert_eq!(
+        path.ancestors().nth(3),
+        Some(store_dir::<Data>(root.path()).as_path())
+    );
+    assert_eq!(chunk_store.keys().await?, vec![id]);
+
+    chunk_store.delete(&id).await?;
+    assert!(!chunk_store.has(&id).await);
+    assert!(chunk_store.keys().await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn existing_chunks_are_moved_when_dir_depth_changes() -> Result<()> {
+    let mut rng = new_rng();
+    let chunks = Chunks::gen(&mut rng)?;
+    let root = temp_dir()?;
+
+    let flat = StoreConfig {
+        dir_depth: 0,
+        ..StoreConfig::default()
+    };
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &flat).await?;
+    for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+        chunk_store
+            .put(&Data {
+                id: Id(index as u64),
+                value: data.clone(),
+            })
+            .await?;
+    }
+    drop(chunk_store);
+
+    for dir_depth in &[2, 1, 0] {
+        let config = StoreConfig {
+            dir_depth: *dir_depth,
+            ..StoreConfig::default()
+        };
+        let chunk_store =
+            ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+        assert_eq!(chunk_store.keys().await?.len(), chunks.data_and_sizes.len());
+        for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+            let id = Id(index as u64);
+            assert!(chunk_store.has(&id).await);
+            assert_eq!(&chunk_store.get(&id).await?.value, data);
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn interrupted_writes_are_cleaned_up_on_open() -> Result<()> {
+    let root = temp_dir()?;
+    let config = StoreConfig::default();
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+
+    let data = Data {
+        id: Id(new_rng().gen()),
+        value: vec![1, 2, 3],
+    };
+    chunk_store.put(&data).await?;
+    let temp_path =
+        file_path::<Data>(root.path(), &config, &data.id)?.with_extension(TEMP_FILE_EXTENSION);
+    std::fs::write(&temp_path, [0; 8])?;
+    drop(chunk_store);
+
+    let chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+    assert!(!temp_path.exists());
+    assert_eq!(chunk_store.get(&data.id).await?, data);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unreadable_chunks_are_quarantined_on_full_rescan() -> Result<()> {
+    let root = temp_dir()?;
+    let config = StoreConfig {
+        full_rescan: true,
+        ..StoreConfig::default()
+    };
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+
+    let intact = Data {
+        id: Id(0),
+        value: vec![1, 2, 3],
+    };
+    let truncated = Data {
+        id: Id(1),
+        value: vec![4, 5, 6],
+    };
+    chunk_store.put(&intact).await?;
+    chunk_store.put(&truncated).await?;
+    let used_space = chunk_store.local_used_space().await;
+
+    let truncated_path = file_path::<Data>(root.path(), &config, &truncated.id)?;
+    let contents = std::fs::read(&truncated_path)?;
+    std::fs::write(&truncated_path, &contents[..contents.len() / 2])?;
+    drop(chunk_store);
+
+    let chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+    assert_eq!(chunk_store.keys().await?, vec![intact.id]);
+    assert!(!chunk_store.has(&truncated.id).await);
+    assert!(root
+        .path()
+        .join(CHUNK_STORE_DIR)
+        .join(QUARANTINE_DIR)
+        .join(ChunkStore::<Data>::subdir())
+        .join(truncated_path.file_name().unwrap_or_default())
+        .is_file());
+    assert_eq!(
+        chunk_store.local_used_space().await,
+        used_space - contents.len() as u64
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn empty_chunks_are_quarantined_on_open() -> Result<()> {
+    let root = temp_dir()?;
+    let config = StoreConfig::default();
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+
+    let intact = Data {
+        id: Id(0),
+        value: vec![1, 2, 3],
+    };
+    let emptied = Data {
+        id: Id(1),
+        value: vec![4, 5, 6],
+    };
+    chunk_store.put(&intact).await?;
+    chunk_store.put(&emptied).await?;
+    let emptied_path = file_path::<Data>(root.path(), &config, &emptied.id)?;
+    let emptied_size = std::fs::metadata(&emptied_path)?.len();
+    let used_space = chunk_store.local_used_space().await;
+    std::fs::write(&emptied_path, [])?;
+    drop(chunk_store);
+
+    let chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+    assert_eq!(chunk_store.keys().await?, vec![intact.id]);
+    assert!(root
+        .path()
+        .join(CHUNK_STORE_DIR)
+        .join(QUARANTINE_DIR)
+        .join(ChunkStore::<Data>::subdir())
+        .join(emptied_path.file_name().unwrap_or_default())
+        .is_file());
+    assert_eq!(
+        chunk_store.local_used_space().await,
+        used_space - emptied_size
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn used_space_is_reconciled_on_open() -> Result<()> {
+    let mut rng = new_rng();
+    let chunks = Chunks::gen(&mut rng)?;
+    let root = temp_dir()?;
+    let config = StoreConfig::default();
+
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+    for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+        chunk_store
+            .put(&Data {
+                id: Id(index as u64),
+                value: data.clone(),
+            })
+            .await?;
+    }
+    drop(chunk_store);
+
+    // Lose a chunk behind the store's back.
+    let mut expected = chunks.total_size;
+    if let Some((_, size)) = chunks.data_and_sizes.first() {
+        std::fs::remove_file(file_path::<Data>(root.path(), &config, &Id(0))?)?;
+        expected -= size;
+    }
+
+    let chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+    assert_eq!(chunk_store.local_used_space().await, expected);
+    assert_eq!(chunk_store.total_used_space().await, expected);
+    drop(chunk_store);
+
+    // An unparseable record is rebuilt too.
+    std::fs::write(
+        store_dir::<Data>(root.path()).join(super::used_space::USED_SPACE_FILENAME),
+        [0xff; 3],
+    )?;
+    let chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+    assert_eq!(chunk_store.total_used_space().await, expected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn corrupt_blobs_are_detected_on_read() -> Result<()> {
+    let root = temp_dir()?;
+    let config = StoreConfig::default();
+    let mut chunk_store =
+        ChunkStore::<Blob>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+
+    let blob = Blob::Public(PublicBlob::new(vec![1, 2, 3]));
+    chunk_store.put(&blob).await?;
+
+    let path = file_path::<Blob>(root.path(), &config, blob.address())?;
+    let mut contents = std::fs::read(&path)?;
+    if let Some(byte) = contents.last_mut() {
+        *byte ^= 1;
+    }
+    std::fs::write(&path, &contents)?;
+
+    match chunk_store.get(blob.address()).await {
+        Err(Error::CorruptChunk(_)) => (),
+        x => return Err(Error::Logic(format!("Unexpected {:?}", x))),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn all_backends_store_and_delete_chunks() -> Result<()> {
+    let mut rng = new_rng();
+    let chunks = Chunks::gen(&mut rng)?;
+
+    for backend in &[
+        StorageBackendKind::Fs,
+        StorageBackendKind::Kv,
+        StorageBackendKind::Memory,
+    ] {
+        let root = temp_dir()?;
+        let config = StoreConfig {
+            backend: *backend,
+            ..StoreConfig::default()
+        };
+        let mut chunk_store =
+            ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+
+        for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+            chunk_store
+                .put(&Data {
+                    id: Id(index as u64),
+                    value: data.clone(),
+                })
+                .await?;
+        }
+        assert_eq!(chunk_store.total_used_space().await, chunks.total_size);
+        assert_eq!(chunk_store.keys().await?.len(), chunks.data_and_sizes.len());
+
+        for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+            let id = Id(index as u64);
+            assert_eq!(&chunk_store.get(&id).await?.value, data);
+            chunk_store.delete(&id).await?;
+            assert!(!chunk_store.has(&id).await);
+        }
+        assert_eq!(chunk_store.total_used_space().await, 0);
+        assert!(chunk_store.keys().await?.is_empty());
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn keys_are_listed_a_page_at_a_time() -> Result<()> {
+    for backend in &[
+        StorageBackendKind::Fs,
+        StorageBackendKind::Kv,
+        StorageBackendKind::Memory,
+    ] {
+        let root = temp_dir()?;
+        let config = StoreConfig {
+            backend: *backend,
+            ..StoreConfig::default()
+        };
+        let mut chunk_store =
+            ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+        for index in 0..10 {
+            chunk_store
+                .put(&Data {
+                    id: Id(index),
+                    value: vec![index as u8],
+                })
+                .await?;
+        }
+
+        let mut listed = vec![];
+        let mut cursor = None;
+        loop {
+            let page = chunk_store.keys_page(cursor.as_ref(), 3).await?;
+            assert!(page.ids.len() <= 3);
+            // Chunks deleted after being listed don't upset the listing.
+            for id in &page.ids {
+                chunk_store.delete(id).await?;
+            }
+            listed.extend(page.ids);
+            cursor = match page.next {
+                Some(next) => Some(next),
+                None => break,
+            };
+        }
+        listed.sort();
+        assert_eq!(listed, (0..10).map(Id).collect::<Vec<_>>());
+        assert!(chunk_store.keys().await?.is_empty());
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn interleaved_key_listings_each_list_all_keys() -> Result<()> {
+    let root = temp_dir()?;
+    let mut chunk_store = ChunkStore::<Data>::new(
+        root.path(),
+        &UsedSpace::new(u64::MAX),
+        &StoreConfig::default(),
+    )
+    .await?;
+    for index in 0..10 {
+        chunk_store
+            .put(&Data {
+                id: Id(index),
+                value: vec![index as u8],
+            })
+            .await?;
+    }
+
+    // Each listing carries on from its own cursor, although the other one moved meanwhile.
+    let mut cursors = [Some(None), Some(None)];
+    let mut listed = [vec![], vec![]];
+    while cursors.iter().any(Option::is_some) {
+        for (index, limit) in [(0, 3), (1, 4)].iter() {
+            if let Some(cursor) = cursors[*index].take() {
+                let page = chunk_store.keys_page(cursor.as_ref(), *limit).await?;
+                listed[*index].extend(page.ids);
+                cursors[*index] = page.next.map(Some);
+            }
+        }
+    }
+    let [first, second] = listed;
+    assert_eq!(first.len(), 10);
+    assert_eq!(first, second);
+    Ok(())
+}
+
+#[tokio::test]
+async fn key_listing_errors_are_returned() -> Result<()> {
+    let root = temp_dir()?;
+    let chunk_store = ChunkStore::<Data>::new(
+        root.path(),
+        &UsedSpace::new(u64::MAX),
+        &StoreConfig::default(),
+    )
+    .await?;
+    std::fs::remove_dir_all(store_dir::<Data>(root.path()))?;
+
+    assert!(matches!(
+        chunk_store.keys_page(None, 3).await,
+        Err(Error::Io(_))
+    ));
+    assert!(matches!(chunk_store.keys().await, Err(Error::Io(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn chunk_lookup_errors_are_returned() -> Result<()> {
+    let root = temp_dir()?;
+    let config = StoreConfig::default();
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+    let path = file_path::<Data>(root.path(), &config, &Id(1))?;
+    let shard_dir = path
+        .parent()
+        .ok_or_else(|| Error::Logic("Chunk file has no parent dir".to_string()))?;
+    if shard_dir.exists() {
+        std::fs::remove_dir_all(shard_dir)?;
+    } else if let Some(parent) = shard_dir.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(shard_dir, b"")?;
+
+    // An error looking the chunk up isn't taken for it not being stored.
+    assert!(matches!(
+        chunk_store.stored_size(&Id(1)).await,
+        Err(Error::Io(_))
+    ));
+    assert!(matches!(
+        chunk_store
+            .put(&Data {
+                id: Id(1),
+                value: vec![1; 10],
+            })
+            .await,
+        Err(Error::Io(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn kv_backend_persists_chunks() -> Result<()> {
+    let root = temp_dir()?;
+    let config = StoreConfig {
+        backend: StorageBackendKind::Kv,
+        ..StoreConfig::default()
+    };
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+
+    let data = Data {
+        id: Id(new_rng().gen()),
+        value: vec![1, 2, 3],
+    };
+    chunk_store.put(&data).await?;
+    drop(chunk_store);
+
+    let chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+    assert_eq!(chunk_store.keys().await?, vec![data.id]);
+    assert_eq!(chunk_store.get(&data.id).await?, data);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn kv_backend_refuses_an_unreadable_database() -> Result<()> {
+    let root = temp_dir()?;
+    let config = StoreConfig {
+        backend: StorageBackendKind::Kv,
+        ..StoreConfig::default()
+    };
+    let dir = store_dir::<Data>(root.path());
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join("chunks.redb"), vec![1; 4096])?;
+
+    assert!(
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config)
+            .await
+            .is_err()
+    );
+    // The database is left for inspection.
+    assert_eq!(std::fs::read(dir.join("chunks.redb"))?, vec![1; 4096]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn kv_backend_collects_stray_files() -> Result<()> {
+    let root = temp_dir()?;
+    let config = StoreConfig {
+        backend: StorageBackendKind::Kv,
+        ..StoreConfig::default()
+    };
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+    let data = Data {
+        id: Id(1),
+        value: vec![1; 10],
+    };
+    chunk_store.put(&data).await?;
+
+    let dir = store_dir::<Data>(root.path());
+    std::fs::write(dir.join("notes.txt"), [0; 7])?;
+    std::fs::create_dir_all(dir.join("ab"))?;
+    std::fs::write(dir.join("ab").join("partial.tmp"), [0; 11])?;
+
+    let garbage = Garbage { count: 2, size: 18 };
+    assert_eq!(
+        chunk_store.collect_garbage(GcAction::Quarantine).await?,
+        garbage
+    );
+    let quarantine_dir = root
+        .path()
+        .join(CHUNK_STORE_DIR)
+        .join(QUARANTINE_DIR)
+        .join(ChunkStore::<Data>::subdir());
+    assert!(quarantine_dir.join("ab").join("partial.tmp").exists());
+    assert!(!dir.join("notes.txt").exists());
+
+    // The database and the store's own files were left alone.
+    assert_eq!(
+        chunk_store.collect_garbage(GcAction::Remove).await?,
+        Garbage::default()
+    );
+    assert_eq!(chunk_store.get(&Id(1)).await?, data);
+    Ok(())
+}
+
+#[tokio::test]
+async fn memory_backend_leaves_root_untouched() -> Result<()> {
+    let root = Path::new("memory_backend_leaves_root_untouched");
+    let config = StoreConfig {
+        backend: StorageBackendKind::Memory,
+        ..StoreConfig::default()
+    };
+    let mut chunk_store = ChunkStore::<Data>::new(root, &UsedSpace::new(u64::MAX), &config).await?;
+
+    let data = Data {
+        id: Id(new_rng().gen()),
+        value: vec![1, 2, 3],
+    };
+    chunk_store.put(&data).await?;
+    assert_eq!(chunk_store.get(&data.id).await?, data);
+    assert!(!root.exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn compressed_and_uncompressed_chunks_are_read_back() -> Result<()> {
+    let root = temp_dir()?;
+    let uncompressed = StoreConfig::default();
+    let compressed = StoreConfig {
+        compressed_stores: vec![ChunkStore::<Data>::subdir().display().to_string()],
+        ..StoreConfig::default()
+    };
+    let plain = Data {
+        id: Id(0),
+        value: vec![7; 1024],
+    };
+    let deflated = Data {
+        id: Id(1),
+        value: vec![7; 1024],
+    };
+    let serialised_size = bincode::serialized_size(&plain).map_err(Error::Bincode)?;
+
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &uncompressed).await?;
+    chunk_store.put(&plain).await?;
+    assert_eq!(chunk_store.total_used_space().await, serialised_size);
+    drop(chunk_store);
+
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &compressed).await?;
+    chunk_store.put(&deflated).await?;
+    let compressed_size = chunk_store.total_used_space().await - serialised_size;
+    assert!(compressed_size < serialised_size);
+    assert_eq!(chunk_store.get(&plain.id).await?, plain);
+    assert_eq!(chunk_store.get(&deflated.id).await?, deflated);
+    drop(chunk_store);
+
+    let chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &uncompressed).await?;
+    assert_eq!(chunk_store.get(&plain.id).await?, plain);
+    assert_eq!(chunk_store.get(&deflated.id).await?, deflated);
+    assert_eq!(
+        chunk_store.total_used_space().await,
+        serialised_size + compressed_size
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn encrypted_and_plain_chunks_are_read_back() -> Result<()> {
+    let root = temp_dir()?;
+    let plain_config = StoreConfig::default();
+    let encrypted_config = StoreConfig {
+        storage_key: Some(StorageKey::derive(root.path(), b"passphrase")?),
+        ..StoreConfig::default()
+    };
+    let plain = Data {
+        id: Id(0),
+        value: vec![7; 1024],
+    };
+    let encrypted = Data {
+        id: Id(1),
+        value: vec![7; 1024],
+    };
+
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &plain_config).await?;
+    chunk_store.put(&plain).await?;
+    drop(chunk_store);
+
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &encrypted_config).await?;
+    chunk_store.put(&encrypted).await?;
+    assert_eq!(chunk_store.get(&plain.id).await?, plain);
+    assert_eq!(chunk_store.get(&encrypted.id).await?, encrypted);
+
+    let path = file_path::<Data>(root.path(), &encrypted_config, &encrypted.id)?;
+    let contents = std::fs::read(&path)?;
+    assert!(!contents
+        .windows(plain.value.len())
+        .any(|window| window == &plain.value[..]));
+    drop(chunk_store);
+
+    let chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &plain_config).await?;
+    assert_eq!(chunk_store.get(&plain.id).await?, plain);
+    assert!(matches!(
+        chunk_store.get(&encrypted.id).await,
+        Err(Error::StorageKeyRequired)
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn disk_operations_wait_for_a_permit() -> Result<()> {
+    let root = temp_dir()?;
+    let config = StoreConfig {
+        disk_ops: Arc::new(Semaphore::new(1)),
+        ..StoreConfig::default()
+    };
+    let data = Data {
+        id: Id(0),
+        value: vec![7; 16],
+    };
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), &UsedSpace::new(u64::MAX), &config).await?;
+    chunk_store.put(&data).await?;
+
+    let permit = config
+        .disk_ops
+        .acquire()
+        .await
+        .map_err(|_| Error::Logic("Semaphore closed".to_string()))?;
+    let read = tokio::time::timeout(Duration::from_millis(100), chunk_store.get(&data.id)).await;
+    assert!(read.is_err());
+
+    drop(permit);
+    assert_eq!(chunk_store.get(&data.id).await?, data);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn chunks_are_spread_over_data_dirs() -> Result<()> {
+    let root = temp_dir()?;
+    let disks = [temp_dir()?, temp_dir()?];
+    let data = |index: u64| Data {
+        id: Id(index),
+        value: vec![0; 84],
+    };
+    // Room for three chunks per data dir.
+    let chunk_size = bincode::serialized_size(&data(0)).map_err(Error::Bincode)?;
+    let config = StoreConfig {
+        data_dirs: disks
+            .iter()
+            .map(|disk| DataDir {
+                path: disk.path().to_path_buf(),
+                max_capacity: 3 * chunk_size,
+            })
+            .collect(),
+        ..StoreConfig::default()
+    };
+    let used_space = UsedSpace::new(6 * chunk_size);
+    let mut chunk_store = ChunkStore::<Data>::new(root.path(), &used_space, &config).await?;
+
+    for index in 0..6 {
+        chunk_store.put(&data(index)).await?;
+    }
+    assert!(matches!(
+        chunk_store.put(&data(6)).await,
+        Err(Error::NotEnoughSpace)
+    ));
+    for disk in &disks {
+        assert_eq!(used_space.used_under(disk.path()).await, 3 * chunk_size);
+    }
+    assert!(!store_dir::<Data>(root.path()).exists());
+
+    let mut keys = chunk_store.keys().await?;
+    keys.sort();
+    assert_eq!(keys, (0..6).map(Id).collect::<Vec<_>>());
+    for index in 0..6 {
+        assert_eq!(chunk_store.get(&Id(index)).await?, data(index));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_data_dir_only_loses_its_chunks() -> Result<()> {
+    let root = temp_dir()?;
+    let disks = [temp_dir()?, temp_dir()?];
+    let not_a_dir = root.path().join("not_a_dir");
+    std::fs::write(&not_a_dir, b"")?;
+    let mut config = StoreConfig::default();
+    for path in &[disks[0].path(), disks[1].path(), not_a_dir.as_path()] {
+        config.data_dirs.push(DataDir {
+            path: path.to_path_buf(),
+            max_capacity: u64::MAX,
+        });
+    }
+    let used_space = UsedSpace::new(u64::MAX);
+    // The data dir which can't be opened is left out.
+    let mut chunk_store = ChunkStore::<Data>::new(root.path(), &used_space, &config).await?;
+
+    for index in 0..10 {
+        chunk_store
+            .put(&Data {
+                id: Id(index),
+                value: vec![index as u8; 10],
+            })
+            .await?;
+    }
+    let on_failed_disk: Vec<_> = (0..10)
+        .map(Id)
+        .filter(|id| {
+            matches!(
+                file_path::<Data>(disks[0].path(), &config, id),
+                Ok(path) if path.exists()
+            )
+        })
+        .collect();
+    assert!(!on_failed_disk.is_empty() && on_failed_disk.len() < 10);
+
+    std::fs::remove_dir_all(disks[0].path().join(CHUNK_STORE_DIR))?;
+    let keys = chunk_store.keys().await?;
+    assert_eq!(keys.len(), 10 - on_failed_disk.len());
+    for id in keys {
+        assert!(!on_failed_disk.contains(&id));
+        let _ = chunk_store.get(&id).await?;
+    }
+    for id in &on_failed_disk {
+        assert!(matches!(
+            chunk_store.get(id).await,
+            Err(Error::NoSuchChunk(_))
+        ));
+    }
+    assert_eq!(
+        used_space.total().await,
+        used_space.used_under(disks[1].path()).await
+    );
+
+    // New chunks go to the remaining data dir.
+    chunk_store
+        .put(&Data {
+            id: on_failed_disk[0],
+            value: vec![1; 10],
+        })
+        .await?;
+    assert!(file_path::<Data>(disks[1].path(), &config, &on_failed_disk[0])?.exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn stray_files_are_collected() -> Result<()> {
+    let root = temp_dir()?;
+    let config = StoreConfig::default();
+    let used_space = UsedSpace::new(u64::MAX);
+    let mut chunk_store = ChunkStore::<Data>::new(root.path(), &used_space, &config).await?;
+    let data = Data {
+        id: Id(1),
+        value: vec![1; 10],
+    };
+    chunk_store.put(&data).await?;
+    let chunk_size = chunk_store.local_used_space().await;
+    drop(chunk_store);
+
+    // A file named like a chunk, but not after an id, and files not named like chunks at all.
+    let dir = store_dir::<Data>(root.path());
+    let misnamed = Layout::new(config.dir_depth).file_path(&dir, &[1, 2, 3]);
+    std::fs::create_dir_all(misnamed.parent().unwrap_or(&dir))?;
+    std::fs::write(&misnamed, [0; 5])?;
+    std::fs::write(dir.join("notes.txt"), [0; 7])?;
+    std::fs::create_dir_all(dir.join("ab"))?;
+    std::fs::write(dir.join("ab").join("partial.tmp"), [0; 11])?;
+
+    let used_space = UsedSpace::new(u64::MAX);
+    let mut chunk_store = ChunkStore::<Data>::new(root.path(), &used_space, &config).await?;
+    assert_eq!(chunk_store.local_used_space().await, chunk_size);
+
+    let garbage = Garbage { count: 3, size: 23 };
+    assert_eq!(
+        chunk_store.collect_garbage(GcAction::Report).await?,
+        garbage
+    );
+    assert!(misnamed.exists());
+    assert_eq!(
+        chunk_store.collect_garbage(GcAction::Quarantine).await?,
+        garbage
+    );
+    assert!(!misnamed.exists());
+    let quarantine_dir = root
+        .path()
+        .join(CHUNK_STORE_DIR)
+        .join(QUARANTINE_DIR)
+        .join(ChunkStore::<Data>::subdir());
+    assert!(quarantine_dir.join("ab").join("partial.tmp").exists());
+
+    assert_eq!(
+        chunk_store.collect_garbage(GcAction::Remove).await?,
+        Garbage::default()
+    );
+    assert_eq!(chunk_store.get(&Id(1)).await?, data);
+    assert_eq!(chunk_store.local_used_space().await, chunk_size);
+    drop(chunk_store);
+
+    // The store's own files were left alone.
+    let chunk_store = ChunkStore::<Data>::new(root.path(), &used_space, &config).await?;
+    assert_eq!(chunk_store.keys().await?, vec![Id(1)]);
+    Ok(())
+}
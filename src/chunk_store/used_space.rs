@@ -7,14 +7,19 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{Error, Result};
-use std::{path::Path, sync::Arc};
-use tokio::{io::AsyncSeekExt, sync::Mutex};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
 
-const USED_SPACE_FILENAME: &str = "used_space";
+pub(super) const USED_SPACE_FILENAME: &str = "used_space";
 
 /// This holds a record (in-memory and on-disk) of the space used by a single `ChunkStore`, and also
 /// an in-memory record of the total space used by all `ChunkStore`s.
-#[derive(Debug)]
+///
+/// Clones share the same records, so all the stores of a node draw on one `max_capacity`.
+#[derive(Clone, Debug)]
 pub struct UsedSpace {
     inner: Arc<Mutex<inner::UsedSpace>>,
 }
@@ -59,7 +64,6 @@ impl UsedSpace {
     /// Note, due to the async nature of this, the value
     /// may be stale by the time it is read if there are multiple
     /// writers
-    #[allow(dead_code)]
     pub async fn local(&self, id: StoreId) -> u64 {
         self.inner.lock().await.local(id)
     }
@@ -71,25 +75,68 @@ impl UsedSpace {
         self.inner.lock().await.add_local_store(dir).await
     }
 
+    /// Add a `ChunkStore` whose used space is only tracked in memory,
+    /// for stores which don't persist their chunks either
+    pub async fn add_volatile_store(&self) -> StoreId {
+        self.inner.lock().await.add_volatile_store()
+    }
+
+    /// Returns the space used by the persistent local stores under `dir`, e.g. on one disk
+    pub async fn used_under(&self, dir: &Path) -> u64 {
+        self.inner.lock().await.used_under(dir)
+    }
+
     /// Increase the used amount of a single chunk store and the global used value
     pub async fn increase(&self, id: StoreId, consumed: u64) -> Result<()> {
-        self.inner.lock().await.increase(id, consumed).await
+        self.inner.lock().await.increase(id, consumed, None).await
+    }
+
+    /// Increase the used amount of a single chunk store and the global used value, as long as
+    /// the stores under `dir` keep within `capacity` too
+    pub async fn increase_within(
+        &self,
+        id: StoreId,
+        consumed: u64,
+        dir: &Path,
+        capacity: u64,
+    ) -> Result<()> {
+        self.inner
+            .lock()
+            .await
+            .increase(id, consumed, Some((dir, capacity)))
+            .await
     }
 
     /// Decrease the used amount of a single chunk store and the global used value
     pub async fn decrease(&self, id: StoreId, released: u64) -> Result<()> {
         self.inner.lock().await.decrease(id, released).await
     }
+
+    /// Overwrite the used amount of a single chunk store with the space its chunks
+    /// actually take, adjusting the global used value to match.
+    /// Returns the previously recorded amount
+    pub async fn reconcile(&self, id: StoreId, actual: u64) -> Result<u64> {
+        self.inner.lock().await.reconcile(id, actual).await
+    }
+
+    /// Stops tracking a local store, releasing the space it used from the global used value
+    /// without touching its on-disk record, e.g. after the disk holding it failed
+    pub async fn remove_local_store(&self, id: StoreId) {
+        self.inner.lock().await.remove_local_store(id)
+    }
+}
+
+/// Rewrites the record in `dir` if it was written before the journal was introduced, giving it
+/// a generation. Returns whether it was rewritten
+pub(super) async fn upgrade_legacy_record(dir: &Path) -> Result<bool> {
+    journal::upgrade_legacy_snapshot(dir).await
 }
 
 mod inner {
 
+    use super::journal::{Entry, Journal};
     use super::*;
-    use std::{collections::HashMap, io::SeekFrom};
-    use tokio::{
-        fs::{File, OpenOptions},
-        io::{AsyncReadExt, AsyncWriteExt},
-    };
+    use std::collections::HashMap;
 
     /// Tracks the Used Space of all `ChunkStore` objects
     /// registered with it, as well as the combined amount
@@ -110,11 +157,10 @@ mod inner {
     struct LocalUsedSpace {
         // Space consumed by this one `ChunkStore`.
         pub local_value: u64,
-        // File used to maintain on-disk record of `local_value`.
-        // TODO: maybe a good idea to maintain a journal that is only flushed occasionally
-        // to ensure stale entries aren't recorded, and to avoid holding the lock for the
-        // whole inner::UsedSpace struct during the entirety of the file write.
-        pub local_record: File,
+        // On-disk record of `local_value`, if the store is persistent.
+        pub local_record: Option<Journal>,
+        // Directory of the store, if it's persistent.
+        pub dir: Option<PathBuf>,
     }
 
     impl UsedSpace {
@@ -134,7 +180,9 @@ mod inner {
             self.total_value = 0;
             for (_id, local_used_space) in self.local_stores.iter_mut() {
                 local_used_space.local_value = 0;
-                Self::write_local_to_file(&mut local_used_space.local_record, 0).await?;
+                if let Some(journal) = &mut local_used_space.local_record {
+                    journal.compact(0).await?;
+                }
             }
             Ok(())
         }
@@ -159,39 +207,48 @@ mod inner {
         /// of a local chunk store as part of the global
         /// used amount tracking
         pub async fn add_local_store<T: AsRef<Path>>(&mut self, dir: T) -> Result<StoreId> {
-            let mut local_record = OpenOptions::new()
-                .read(true)
-                .write(true)
-                .create(true)
-                .open(dir.as_ref().join(USED_SPACE_FILENAME))
-                .await?;
+            let (journal, local_value) = Journal::open(dir.as_ref()).await?;
+            self.total_value = self.total_value.saturating_add(local_value);
+            Ok(self.insert(LocalUsedSpace {
+                local_value,
+                local_record: Some(journal),
+                dir: Some(dir.as_ref().to_path_buf()),
+            }))
+        }
 
-            // try read
-            let mut buffer = vec![];
-            let could_read = local_record.read_to_end(&mut buffer).await.is_ok();
-            let has_value = !buffer.is_empty();
-            let local_value = if could_read && has_value {
-                // TODO - if this can't be parsed, we should consider emptying `dir` of any chunks.
-                bincode::deserialize::<u64>(&buffer)?
-            } else {
-                let mut bytes = Vec::<u8>::new();
-                bincode::serialize_into(&mut bytes, &0_u64)?;
-                local_record.write_all(&bytes).await?;
-                0
-            };
+        /// Adds a new record for tracking a local chunk store
+        /// without keeping an on-disk record of it
+        pub fn add_volatile_store(&mut self) -> StoreId {
+            self.insert(LocalUsedSpace {
+                local_value: 0,
+                local_record: None,
+                dir: None,
+            })
+        }
 
-            let local_store = LocalUsedSpace {
-                local_value,
-                local_record,
-            };
+        fn insert(&mut self, local_store: LocalUsedSpace) -> StoreId {
             let id = self.next_id;
             self.next_id += 1;
             let _ = self.local_stores.insert(id, local_store);
-            Ok(id)
+            id
         }
 
-        /// Increase used space in a local store and globally at the same time
-        pub async fn increase(&mut self, id: StoreId, consumed: u64) -> Result<()> {
+        /// Returns the used space of the persistent local stores under `dir`
+        pub fn used_under(&self, dir: &Path) -> u64 {
+            self.local_stores
+                .values()
+                .filter(|store| matches!(&store.dir, Some(store_dir) if store_dir.starts_with(dir)))
+                .fold(0, |used, store| used.saturating_add(store.local_value))
+        }
+
+        /// Increase used space in a local store and globally at the same time, keeping the
+        /// stores under the dir of `limit`, if any, within its capacity
+        pub async fn increase(
+            &mut self,
+            id: StoreId,
+            consumed: u64,
+            limit: Option<(&Path, u64)>,
+        ) -> Result<()> {
             let new_total = self
                 .total_value
                 .checked_add(consumed)
@@ -199,77 +256,309 @@ mod inner {
             if new_total > self.max_capacity {
                 return Err(Error::NotEnoughSpace);
             }
-            let new_local = self
-                .local_stores
-                .get(&id)
-                .ok_or(Error::NoStoreId)?
+            if let Some((dir, capacity)) = limit {
+                if self.used_under(dir).saturating_add(consumed) > capacity {
+                    return Err(Error::NotEnoughSpace);
+                }
+            }
+            let local_store = self.local_stores.get_mut(&id).ok_or(Error::NoStoreId)?;
+            let new_local = local_store
                 .local_value
                 .checked_add(consumed)
                 .ok_or(Error::NotEnoughSpace)?;
 
-            {
-                let record = &mut self
-                    .local_stores
-                    .get_mut(&id)
-                    .ok_or(Error::NoStoreId)?
-                    .local_record;
-                Self::write_local_to_file(record, new_local).await?;
+            if let Some(journal) = &mut local_store.local_record {
+                journal.append(Entry::Increase(consumed), new_local).await?;
             }
             self.total_value = new_total;
-            self.local_stores
-                .get_mut(&id)
-                .ok_or(Error::NoStoreId)?
-                .local_value = new_local;
+            local_store.local_value = new_local;
 
             Ok(())
         }
 
         /// Decrease used space in a local store and globally at the same time
         pub async fn decrease(&mut self, id: StoreId, released: u64) -> Result<()> {
-            let new_local = self
-                .local_stores
-                .get_mut(&id)
-                .ok_or(Error::NoStoreId)?
-                .local_value
-                .saturating_sub(released);
-
+            let local_store = self.local_stores.get_mut(&id).ok_or(Error::NoStoreId)?;
+            let new_local = local_store.local_value.saturating_sub(released);
             let new_total = self.total_value.saturating_sub(released);
-            {
-                let record = &mut self
-                    .local_stores
-                    .get_mut(&id)
-                    .ok_or(Error::NoStoreId)?
-                    .local_record;
-                Self::write_local_to_file(record, new_local).await?;
+
+            if let Some(journal) = &mut local_store.local_record {
+                journal.append(Entry::Decrease(released), new_local).await?;
             }
             self.total_value = new_total;
-            self.local_stores
-                .get_mut(&id)
-                .ok_or(Error::NoStoreId)?
-                .local_value = new_local;
+            local_store.local_value = new_local;
+            Ok(())
+        }
+
+        /// Whether entries were appended to the journal of a local store which no sync has been
+        /// started for yet
+        #[cfg(test)]
+        pub fn has_unsynced_entries(&self, id: StoreId) -> bool {
+            matches!(
+                self.local_stores.get(&id),
+                Some(LocalUsedSpace { local_record: Some(journal), .. })
+                    if journal.has_unsynced_entries()
+            )
+        }
+
+        /// Stop tracking a local store, releasing the space it used from the total
+        pub fn remove_local_store(&mut self, id: StoreId) {
+            if let Some(local_store) = self.local_stores.remove(&id) {
+                self.total_value = self.total_value.saturating_sub(local_store.local_value);
+            }
+        }
+
+        /// Set the used space of a local store, adjusting the total by the difference
+        pub async fn reconcile(&mut self, id: StoreId, actual: u64) -> Result<u64> {
+            let local_store = self.local_stores.get_mut(&id).ok_or(Error::NoStoreId)?;
+            let recorded = local_store.local_value;
+            if let Some(journal) = &mut local_store.local_record {
+                journal.compact(actual).await?;
+            }
+            local_store.local_value = actual;
+            self.total_value = self
+                .total_value
+                .saturating_sub(recorded)
+                .saturating_add(actual);
+            Ok(recorded)
+        }
+    }
+}
+
+mod journal {
+
+    use super::*;
+    use crate::utils;
+    use log::warn;
+    use serde::{Deserialize, Serialize};
+    use std::{
+        io,
+        path::PathBuf,
+        sync::atomic::{AtomicBool, Ordering},
+        time::{Duration, Instant},
+    };
+    use tokio::{
+        fs::{self, File, OpenOptions},
+        io::AsyncWriteExt,
+    };
+
+    const JOURNAL_EXTENSION: &str = "journal";
+    const TEMP_EXTENSION: &str = "tmp";
+
+    /// Longest time appended entries go without being synced to disk
+    pub(super) const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Number of entries after which the journal is folded into the snapshot
+    const MAX_ENTRIES: usize = 10_000;
+
+    /// A change to the used space of a `ChunkStore`
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Entry {
+        Increase(u64),
+        Decrease(u64),
+    }
+
+    impl Entry {
+        fn apply(&self, value: u64) -> u64 {
+            match self {
+                Self::Increase(consumed) => value.saturating_add(*consumed),
+                Self::Decrease(released) => value.saturating_sub(*released),
+            }
+        }
+    }
+
+    /// On-disk record of the used space of a single `ChunkStore`: a snapshot of the
+    /// value, and an append-only journal of the changes made since.
+    ///
+    /// Entries are appended without waiting for the disk, and synced in batches: the
+    /// first entry after a sync schedules the next one, so that entries don't go
+    /// unsynced for longer than `SYNC_INTERVAL` once the store stops being written to.
+    /// A crash can thus lose the latest changes, but not leave a corrupt record;
+    /// the owning `ChunkStore` reconciles the value with its chunks when reopened.
+    ///
+    /// The snapshot and the journal both carry a generation. Compaction writes a
+    /// new snapshot before restarting the journal, so a journal left behind by an
+    /// interrupted compaction is recognised as stale and not replayed twice.
+    #[derive(Debug)]
+    pub struct Journal {
+        snapshot_path: PathBuf,
+        file: File,
+        generation: u64,
+        entries: usize,
+        last_sync: Instant,
+        // Whether entries were appended since the scheduled sync started, if any.
+        unsynced: Arc<AtomicBool>,
+    }
+
+    impl Journal {
+        /// Opens the record in `dir`, replaying and compacting its journal.
+        /// Returns the journal and the recorded value
+        pub async fn open(dir: &Path) -> Result<(Self, u64)> {
+            let snapshot_path = dir.join(USED_SPACE_FILENAME);
+            let journal_path = snapshot_path.with_extension(JOURNAL_EXTENSION);
+
+            let (value, generation) = read_snapshot(&snapshot_path).await?;
+            let value = replay(&journal_path, generation, value).await?;
+
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&journal_path)
+                .await?;
+            let mut journal = Self {
+                snapshot_path,
+                file,
+                generation,
+                entries: 0,
+                last_sync: Instant::now(),
+                unsynced: Arc::new(AtomicBool::new(false)),
+            };
+            journal.compact(value).await?;
+            Ok((journal, value))
+        }
+
+        /// Appends `entry`, which results in the recorded value being `value`
+        pub async fn append(&mut self, entry: Entry, value: u64) -> Result<()> {
+            if self.entries >= MAX_ENTRIES {
+                return self.compact(value).await;
+            }
+
+            self.file.write_all(&utils::serialise(&entry)?).await?;
+            self.file.flush().await?;
+            self.entries += 1;
+
+            if self.last_sync.elapsed() >= SYNC_INTERVAL {
+                self.file.sync_data().await?;
+                self.last_sync = Instant::now();
+            } else if !self.unsynced.swap(true, Ordering::AcqRel) {
+                self.schedule_sync().await?;
+            }
+            Ok(())
+        }
+
+        // Syncs the journal once `SYNC_INTERVAL` has passed, covering the entries appended until
+        // then. The sync goes ahead even if the journal is dropped in the meantime.
+        async fn schedule_sync(&self) -> Result<()> {
+            let file = self.file.try_clone().await?;
+            let unsynced = self.unsynced.clone();
+            // Detached, the sync doesn't hold up the write which scheduled it.
+            drop(tokio::spawn(async move {
+                tokio::time::sleep(SYNC_INTERVAL).await;
+                // Entries appended from here on schedule another sync.
+                unsynced.store(false, Ordering::Release);
+                if let Err(err) = file.sync_data().await {
+                    warn!("Could not sync the used space journal: {}", err);
+                }
+            }));
             Ok(())
         }
 
-        /// helper to write the contents of local to file
-        /// NOTE: For now, you should hold the lock on the inner while doing this
-        /// It's slow, but maintains behaviour from the previous implementation
-        async fn write_local_to_file(record: &mut File, local: u64) -> Result<()> {
-            record.set_len(0).await?;
-            let _ = record.seek(SeekFrom::Start(0)).await?;
+        /// Whether entries were appended which no sync has been started for yet.
+        #[cfg(test)]
+        pub fn has_unsynced_entries(&self) -> bool {
+            self.unsynced.load(Ordering::Acquire)
+        }
 
-            let mut contents = Vec::<u8>::new();
-            bincode::serialize_into(&mut contents, &local)?;
-            record.write_all(&contents).await?;
-            record.sync_all().await?;
+        /// Replaces the snapshot with `value` and restarts the journal
+        pub async fn compact(&mut self, value: u64) -> Result<()> {
+            let generation = self.generation.wrapping_add(1);
 
+            let temp_path = self.snapshot_path.with_extension(TEMP_EXTENSION);
+            let mut snapshot = File::create(&temp_path).await?;
+            snapshot
+                .write_all(&utils::serialise(&(value, generation))?)
+                .await?;
+            snapshot.sync_all().await?;
+            fs::rename(&temp_path, &self.snapshot_path).await?;
+
+            // From here on, the entries of the previous generation are ignored.
+            self.file.set_len(0).await?;
+            self.file.write_all(&utils::serialise(&generation)?).await?;
+            self.file.flush().await?;
+            self.file.sync_data().await?;
+
+            self.generation = generation;
+            self.entries = 0;
+            self.last_sync = Instant::now();
             Ok(())
         }
     }
+
+    /// Rewrites a snapshot holding only the value, as written before the journal
+    /// was introduced, as a snapshot of generation 0. Returns whether it was rewritten
+    pub async fn upgrade_legacy_snapshot(dir: &Path) -> Result<bool> {
+        let snapshot_path = dir.join(USED_SPACE_FILENAME);
+        let bytes = match fs::read(&snapshot_path).await {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err.into()),
+        };
+        if bytes.len() != 8 {
+            return Ok(false);
+        }
+        let value = utils::deserialise::<u64>(&bytes)?;
+
+        let temp_path = snapshot_path.with_extension(TEMP_EXTENSION);
+        let mut snapshot = File::create(&temp_path).await?;
+        snapshot
+            .write_all(&utils::serialise(&(value, 0_u64))?)
+            .await?;
+        snapshot.sync_all().await?;
+        fs::rename(&temp_path, &snapshot_path).await?;
+        Ok(true)
+    }
+
+    // Returns the value and generation of the snapshot. Records written before the
+    // journal was introduced hold only the value.
+    async fn read_snapshot(path: &Path) -> Result<(u64, u64)> {
+        let bytes = match fs::read(path).await {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok((0, 0)),
+            Err(err) => return Err(err.into()),
+        };
+        if bytes.is_empty() {
+            return Ok((0, 0));
+        }
+        let record = match bytes.len() {
+            16 => utils::deserialise::<(u64, u64)>(&bytes),
+            8 => utils::deserialise::<u64>(&bytes).map(|value| (value, 0)),
+            len => Err(Error::Logic(format!("Unexpected record length {}", len))),
+        };
+        // An unparseable record is left for the owning `ChunkStore` to reconcile.
+        Ok(record.unwrap_or_else(|err| {
+            warn!(
+                "Could not parse the used space record {}: {}",
+                path.display(),
+                err
+            );
+            (0, 0)
+        }))
+    }
+
+    // Applies the journal entries of `generation` to `value`. A partially written
+    // last entry is ignored.
+    async fn replay(path: &Path, generation: u64, mut value: u64) -> Result<u64> {
+        let bytes = match fs::read(path).await {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(value),
+            Err(err) => return Err(err.into()),
+        };
+        let mut reader = bytes.as_slice();
+        match bincode::deserialize_from::<_, u64>(&mut reader) {
+            Ok(journal_generation) if journal_generation == generation => (),
+            _ => return Ok(value),
+        }
+        while let Ok(entry) = bincode::deserialize_from::<_, Entry>(&mut reader) {
+            value = entry.apply(value);
+        }
+        Ok(value)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Error, Result, UsedSpace};
+    use super::{journal::SYNC_INTERVAL, Error, Result, UsedSpace, USED_SPACE_FILENAME};
+    use std::{fs, io::Write};
     use tempdir::TempDir;
 
     const TEST_STORE_MAX_SIZE: u64 = u64::MAX;
@@ -299,9 +588,9 @@ mod tests {
         let id = used_space.add_local_store(&store_dir).await?;
         // get a random vec of u64 by adding u32 (avoid overflow)
         let mut rng = rand::thread_rng();
-        let bytes = crate::utils::random_vec(&mut rng, std::mem::size_of::<u32>() * NUMS_TO_ADD);
+        let bytes = crate::utils::random_vec(&mut rng, size_of::<u32>() * NUMS_TO_ADD);
         let mut nums = Vec::new();
-        for chunk in bytes.as_slice().chunks_exact(std::mem::size_of::<u32>()) {
+        for chunk in bytes.as_slice().chunks_exact(size_of::<u32>()) {
             let mut num = 0u32;
             for (i, component) in chunk.iter().enumerate() {
                 num |= (*component as u32) << (i * 8);
@@ -332,4 +621,96 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn journal_is_replayed_on_reopen() -> Result<()> {
+        let root_dir = create_temp_root()?;
+        let store_dir = create_temp_store(&root_dir)?;
+
+        let used_space = UsedSpace::new(TEST_STORE_MAX_SIZE);
+        let id = used_space.add_local_store(&store_dir).await?;
+        used_space.increase(id, 100).await?;
+        used_space.increase(id, 50).await?;
+        used_space.decrease(id, 30).await?;
+        drop(used_space);
+
+        // A partially written entry is ignored.
+        let journal_path = store_dir
+            .path()
+            .join(USED_SPACE_FILENAME)
+            .with_extension("journal");
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&journal_path)?
+            .write_all(&[1, 0])?;
+
+        let used_space = UsedSpace::new(TEST_STORE_MAX_SIZE);
+        let id = used_space.add_local_store(&store_dir).await?;
+        assert_eq!(120, used_space.local(id).await);
+        assert_eq!(120, used_space.total().await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn journal_of_interrupted_compaction_is_not_replayed() -> Result<()> {
+        let root_dir = create_temp_root()?;
+        let store_dir = create_temp_store(&root_dir)?;
+        let journal_path = store_dir
+            .path()
+            .join(USED_SPACE_FILENAME)
+            .with_extension("journal");
+
+        let used_space = UsedSpace::new(TEST_STORE_MAX_SIZE);
+        let id = used_space.add_local_store(&store_dir).await?;
+        used_space.increase(id, 100).await?;
+        drop(used_space);
+        let stale_journal = fs::read(&journal_path)?;
+
+        // Reopening folds the journal into the snapshot. Restoring the journal as it
+        // was simulates a crash before it could be restarted.
+        let used_space = UsedSpace::new(TEST_STORE_MAX_SIZE);
+        let _ = used_space.add_local_store(&store_dir).await?;
+        drop(used_space);
+        fs::write(&journal_path, stale_journal)?;
+
+        let used_space = UsedSpace::new(TEST_STORE_MAX_SIZE);
+        let id = used_space.add_local_store(&store_dir).await?;
+        assert_eq!(100, used_space.local(id).await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn entries_are_synced_once_writes_stop() -> Result<()> {
+        let root_dir = create_temp_root()?;
+        let store_dir = create_temp_store(&root_dir)?;
+
+        // Opening the record syncs it, so the entries which follow are synced later on.
+        let used_space = UsedSpace::new(TEST_STORE_MAX_SIZE);
+        let id = used_space.add_local_store(&store_dir).await?;
+        used_space.increase(id, 100).await?;
+        used_space.decrease(id, 30).await?;
+        assert!(used_space.inner.lock().await.has_unsynced_entries(id));
+
+        tokio::time::sleep(SYNC_INTERVAL * 2).await;
+        assert!(!used_space.inner.lock().await.has_unsynced_entries(id));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn record_without_journal_is_read() -> Result<()> {
+        let root_dir = create_temp_root()?;
+        let store_dir = create_temp_store(&root_dir)?;
+        fs::write(
+            store_dir.path().join(USED_SPACE_FILENAME),
+            bincode::serialize(&42_u64)?,
+        )?;
+
+        let used_space = UsedSpace::new(TEST_STORE_MAX_SIZE);
+        let id = used_space.add_local_store(&store_dir).await?;
+        assert_eq!(42, used_space.local(id).await);
+
+        Ok(())
+    }
 }
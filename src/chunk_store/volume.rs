@@ -0,0 +1,122 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{used_space::StoreId, Backend};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A dir chunks are stored under, e.g. on a disk of its own, with the space they may take there.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DataDir {
+    /// Chunks are stored under `path/CHUNK_STORE_DIR`.
+    pub path: PathBuf,
+    /// Maximum space in bytes the chunks of all stores may take in the dir.
+    pub max_capacity: u64,
+}
+
+/// Parses `<path>=<max capacity>`.
+impl FromStr for DataDir {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.rsplitn(2, '=');
+        let max_capacity = parts.next().unwrap_or_default();
+        let path = match parts.next() {
+            Some(path) if !path.is_empty() => path,
+            _ => {
+                return Err(format!(
+                    "Invalid data dir '{}', expected <path>=<max capacity>",
+                    s
+                ))
+            }
+        };
+        let max_capacity = max_capacity
+            .parse()
+            .map_err(|err| format!("Invalid capacity of data dir '{}': {}", s, err))?;
+        Ok(Self {
+            path: PathBuf::from(path),
+            max_capacity,
+        })
+    }
+}
+
+impl Display for DataDir {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.path.display(), self.max_capacity)
+    }
+}
+
+/// The part of a `ChunkStore` under one data dir, or under the node's root dir when no data dirs
+/// are configured.
+pub(super) struct Volume {
+    /// The data dir or root dir.
+    pub root: PathBuf,
+    /// Dir the chunks of the store are kept in, within `root`.
+    pub dir: PathBuf,
+    pub backend: Backend,
+    /// Id of the store's used space on this volume.
+    pub id: StoreId,
+    /// Maximum space all stores may take under `root`, when limited apart from the node's
+    /// `max_capacity`.
+    pub capacity: Option<u64>,
+    // Once failed, the volume is left alone, along with the chunks on it.
+    failed: AtomicBool,
+}
+
+impl Volume {
+    pub fn new(
+        root: &Path,
+        dir: PathBuf,
+        backend: Backend,
+        id: StoreId,
+        capacity: Option<u64>,
+    ) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            dir,
+            backend,
+            id,
+            capacity,
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.failed.load(Ordering::SeqCst)
+    }
+
+    /// Marks the volume as failed. Returns whether it wasn't already.
+    pub fn fail(&self) -> bool {
+        !self.failed.swap(true, Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DataDir;
+    use std::path::PathBuf;
+
+    #[test]
+    fn data_dirs_are_parsed() {
+        assert_eq!(
+            "/mnt/disk=1=2".parse(),
+            Ok(DataDir {
+                path: PathBuf::from("/mnt/disk=1"),
+                max_capacity: 2,
+            })
+        );
+        assert!("/mnt/disk".parse::<DataDir>().is_err());
+        assert!("=2".parse::<DataDir>().is_err());
+        assert!("/mnt/disk=big".parse::<DataDir>().is_err());
+    }
+}
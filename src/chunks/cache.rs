@@ -0,0 +1,162 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use log::{trace, warn};
+use sn_data_types::{Blob, BlobAddress};
+use std::{
+    collections::{BTreeMap, HashMap},
+    mem,
+    sync::{Mutex, MutexGuard},
+};
+
+/// In-memory cache of chunks, holding at most `capacity` bytes of chunk payloads. When full, the
+/// least recently used chunks are evicted first.
+///
+/// Cached chunks aren't counted against the node's `max_capacity`.
+pub(crate) struct ChunkCache {
+    capacity: u64,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<BlobAddress, Entry>,
+    // Addresses of the cached chunks, by the tick they were last used at.
+    recency: BTreeMap<u64, BlobAddress>,
+    size: u64,
+    tick: u64,
+}
+
+struct Entry {
+    blob: Blob,
+    last_used: u64,
+}
+
+impl ChunkCache {
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Returns the chunk cached under `address`, if any, marking it as the most recently used.
+    pub fn get(&self, address: &BlobAddress) -> Option<Blob> {
+        let mut inner = self.lock()?;
+        let tick = inner.next_tick();
+        let last_used = {
+            let entry = inner.entries.get_mut(address)?;
+            mem::replace(&mut entry.last_used, tick)
+        };
+        let _ = inner.recency.remove(&last_used);
+        let _ = inner.recency.insert(tick, *address);
+        inner.entries.get(address).map(|entry| entry.blob.clone())
+    }
+
+    /// Caches `blob`, evicting the least recently used chunks to make room for it. Chunks larger
+    /// than the whole cache aren't cached.
+    pub fn insert(&self, blob: Blob) {
+        let size = blob.value().len() as u64;
+        if size > self.capacity {
+            return;
+        }
+        let mut inner = match self.lock() {
+            Some(inner) => inner,
+            None => return,
+        };
+        inner.remove(blob.address());
+        while inner.size + size > self.capacity {
+            let address = match inner.recency.values().next() {
+                Some(address) => *address,
+                None => break,
+            };
+            trace!("Evicting cached chunk {:?}", address);
+            inner.remove(&address);
+        }
+        let tick = inner.next_tick();
+        let _ = inner.recency.insert(tick, *blob.address());
+        inner.size += size;
+        let _ = inner.entries.insert(
+            *blob.address(),
+            Entry {
+                blob,
+                last_used: tick,
+            },
+        );
+    }
+
+    /// Removes the chunk cached under `address`, if any.
+    pub fn remove(&self, address: &BlobAddress) {
+        if let Some(mut inner) = self.lock() {
+            inner.remove(address);
+        }
+    }
+
+    // The cache is only an optimisation, so it's skipped if a panic left it poisoned.
+    fn lock(&self) -> Option<MutexGuard<'_, Inner>> {
+        self.inner
+            .lock()
+            .map_err(|_| warn!("Chunk cache lock poisoned"))
+            .ok()
+    }
+}
+
+impl Inner {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn remove(&mut self, address: &BlobAddress) {
+        if let Some(entry) = self.entries.remove(address) {
+            let _ = self.recency.remove(&entry.last_used);
+            self.size -= entry.blob.value().len() as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChunkCache;
+    use sn_data_types::{Blob, PublicBlob};
+
+    fn blob(size: usize, byte: u8) -> Blob {
+        Blob::Public(PublicBlob::new(vec![byte; size]))
+    }
+
+    #[test]
+    fn least_recently_used_chunks_are_evicted() {
+        let cache = ChunkCache::new(30);
+        let (a, b, c) = (blob(10, 1), blob(10, 2), blob(10, 3));
+        cache.insert(a.clone());
+        cache.insert(b.clone());
+        cache.insert(c.clone());
+        assert_eq!(cache.get(a.address()), Some(a.clone()));
+
+        let d = blob(10, 4);
+        cache.insert(d.clone());
+        assert_eq!(cache.get(b.address()), None);
+        assert_eq!(cache.get(a.address()), Some(a));
+        assert_eq!(cache.get(c.address()), Some(c));
+        assert_eq!(cache.get(d.address()), Some(d));
+    }
+
+    #[test]
+    fn chunks_larger_than_the_cache_are_not_cached() {
+        let cache = ChunkCache::new(10);
+        let small = blob(5, 1);
+        cache.insert(small.clone());
+        let large = blob(11, 2);
+        cache.insert(large.clone());
+        assert_eq!(cache.get(large.address()), None);
+        assert_eq!(cache.get(small.address()), Some(small.clone()));
+
+        cache.remove(small.address());
+        assert_eq!(cache.get(small.address()), None);
+    }
+}
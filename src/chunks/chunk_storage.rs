@@ -6,18 +6,26 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::{
+    cache::ChunkCache,
+    index::ChunkIndex,
+    storage_proof::{existence_response, is_existence_check, proof_response, storage_proof},
+};
 use crate::node_ops::MsgType;
 use crate::{
-    chunk_store::BlobChunkStore,
+    chunk_store::{
+        BlobChunkStore, KeysCursor, KeysPage, StorageBackendKind, StoreConfig, UsedSpace,
+        CHUNK_STORE_DIR,
+    },
     node_ops::{NodeDuty, OutgoingMsg},
     Error, Result,
 };
-use log::{error, info};
+use log::{error, info, warn};
 use sn_data_types::{Blob, BlobAddress, DataAddress, PublicKey};
 use sn_messaging::{
     client::Error as ErrorMessage,
     node::{NodeDataQueryResponse, NodeMsg, NodeQueryResponse},
-    Aggregation, DstLocation, MessageId,
+    Aggregation, DstLocation, MessageId, SrcLocation,
 };
 use std::{
     fmt::{self, Display, Formatter},
@@ -27,17 +35,42 @@ use std::{
 /// Storage of data chunks.
 pub(crate) struct ChunkStorage {
     chunks: BlobChunkStore,
+    // Chunks we stopped holding, and recently read ones.
+    cache: ChunkCache,
+    // Facts about the stored chunks.
+    index: ChunkIndex,
 }
 
+/// File the chunk index is kept in, within `CHUNK_STORE_DIR`. It's kept out of the dir of the
+/// chunk store, which only holds what the store writes itself.
+const INDEX_FILENAME: &str = "chunk_index.db";
+
 impl ChunkStorage {
     #[allow(dead_code)]
-    pub(crate) async fn new(path: &Path, max_capacity: u64) -> Result<Self> {
-        let chunks = BlobChunkStore::new(path, max_capacity).await?;
-        Ok(Self { chunks })
+    pub(crate) async fn new(
+        path: &Path,
+        used_space: &UsedSpace,
+        cache_capacity: u64,
+        config: &StoreConfig,
+    ) -> Result<Self> {
+        let chunks = BlobChunkStore::new(path, used_space, config).await?;
+        let index = match config.backend {
+            StorageBackendKind::Memory => ChunkIndex::in_memory(),
+            _ => ChunkIndex::open(&path.join(CHUNK_STORE_DIR).join(INDEX_FILENAME))?,
+        };
+        Ok(Self {
+            chunks,
+            cache: ChunkCache::new(cache_capacity),
+            index,
+        })
     }
 
-    pub fn keys(&self) -> Vec<BlobAddress> {
-        self.chunks.keys()
+    pub async fn keys_page(
+        &self,
+        cursor: Option<&KeysCursor>,
+        limit: usize,
+    ) -> Result<KeysPage<BlobAddress>> {
+        self.chunks.keys_page(cursor, limit).await
     }
 
     pub(crate) async fn store(&mut self, data: &Blob) -> Result<NodeDuty> {
@@ -47,7 +80,7 @@ impl ChunkStorage {
     }
 
     async fn try_store(&mut self, data: &Blob) -> Result<()> {
-        if self.chunks.has(data.address()) {
+        if self.chunks.has(data.address()).await {
             info!(
                 "{}: Immutable chunk already exists, not storing: {:?}",
                 self,
@@ -55,21 +88,66 @@ impl ChunkStorage {
             );
             return Err(Error::DataExists);
         }
-        self.chunks.put(&data).await
+        self.chunks.put(data).await?;
+        self.index_stored(data.address()).await;
+        Ok(())
     }
 
-    pub(crate) fn get_chunk(&self, address: &BlobAddress) -> Result<Blob> {
-        self.chunks.get(address)
+    pub(crate) async fn get_chunk(&self, address: &BlobAddress) -> Result<Blob> {
+        self.chunks.get(address).await
     }
 
     pub(crate) async fn delete_chunk(&mut self, address: &BlobAddress) -> Result<()> {
-        self.chunks.delete(&address).await
+        self.chunks.delete(address).await?;
+        self.index.removed(address);
+        Ok(())
     }
 
-    pub(crate) fn get(&self, address: &BlobAddress, msg_id: MessageId) -> NodeDuty {
-        let result = self
-            .get_chunk(address)
-            .map_err(|_| ErrorMessage::DataNotFound(DataAddress::Blob(*address)));
+    /// Stops holding the chunk at `address`. A public chunk is kept around for reads, outside of
+    /// our storage capacity, if it was read while we held it, as it's then likely to be read again.
+    /// Private chunks aren't, as their deletions only reach their holders.
+    pub(crate) async fn release_chunk(&mut self, blob: Blob) -> Result<()> {
+        let address = *blob.address();
+        // Chunks stored before the index was kept may well have been read.
+        let was_read = match self.index.get(&address) {
+            Some(meta) => meta.reads > 0,
+            None => true,
+        };
+        self.delete_chunk(&address).await?;
+        if was_read && blob.is_public() {
+            self.cache.insert(blob);
+        } else {
+            self.cache.remove(&address);
+        }
+        Ok(())
+    }
+
+    /// Responds to the section of `origin`, the Elder which sent the read, with the chunk at
+    /// `address`, serving it from the cache when possible.
+    pub(crate) async fn get(
+        &self,
+        address: &BlobAddress,
+        msg_id: MessageId,
+        origin: SrcLocation,
+    ) -> NodeDuty {
+        let result = match self.cache.get(address) {
+            Some(blob) => {
+                // The cache also holds chunks which aren't stored anymore, which aren't indexed.
+                let _ = self.index.read(address);
+                Ok(blob)
+            }
+            None => {
+                let result = self.get_chunk(address).await;
+                if let Ok(blob) = &result {
+                    if !self.index.read(address) {
+                        self.index_unindexed(address).await;
+                    }
+                    self.cache.insert(blob.clone());
+                }
+                result
+            }
+        }
+        .map_err(|_| ErrorMessage::DataNotFound(DataAddress::Blob(*address)));
 
         NodeDuty::Send(OutgoingMsg {
             msg: MsgType::Node(NodeMsg::NodeQueryResponse {
@@ -78,14 +156,41 @@ impl ChunkStorage {
                 correlation_id: msg_id,
             }),
             section_source: false, // sent as single node
-            dst: DstLocation::Section(*address.name()),
+            // That of the blob for shards, rather than their own.
+            dst: DstLocation::Section(origin.name()),
+            aggregation: Aggregation::None,
+        })
+    }
+
+    /// Responds to the section of `origin` to its storage challenge `msg_id` for the chunk at
+    /// `address`, with an empty proof if we don't hold it. Reads for proofs aren't indexed, nor
+    /// cached. Existence checks are answered without reading the chunk.
+    pub(crate) async fn prove(
+        &self,
+        address: &BlobAddress,
+        msg_id: MessageId,
+        origin: SrcLocation,
+    ) -> NodeDuty {
+        let msg = if is_existence_check(&msg_id) {
+            existence_response(msg_id, self.chunks.has(address).await)
+        } else {
+            let chunk = self.get_chunk(address).await.ok();
+            let proof = chunk.map(|chunk| storage_proof(&msg_id, chunk.value()));
+            proof_response(msg_id, proof)
+        };
+
+        NodeDuty::Send(OutgoingMsg {
+            msg: MsgType::Node(msg),
+            section_source: false, // sent as single node
+            // That of the blob for shards, rather than their own.
+            dst: DstLocation::Section(origin.name()),
             aggregation: Aggregation::None,
         })
     }
 
     /// Stores a chunk that Elders sent to it for replication.
     pub async fn store_for_replication(&mut self, blob: Blob) -> Result<()> {
-        if self.chunks.has(blob.address()) {
+        if self.chunks.has(blob.address()).await {
             info!(
                 "{}: Immutable chunk already exists, not storing: {:?}",
                 self,
@@ -95,10 +200,29 @@ impl ChunkStorage {
         }
 
         self.chunks.put(&blob).await?;
+        self.index_stored(blob.address()).await;
 
         Ok(())
     }
 
+    // Indexes the chunk just stored at `address`, with its size as stored.
+    async fn index_stored(&self, address: &BlobAddress) {
+        match self.chunks.stored_size(address).await {
+            Ok(Some(size)) => self.index.stored(address, size),
+            Ok(None) => (),
+            Err(err) => warn!("Could not index chunk {:?}: {}", address, err),
+        }
+    }
+
+    // Indexes the chunk at `address`, stored before the index was kept, on its first read.
+    async fn index_unindexed(&self, address: &BlobAddress) {
+        match self.chunks.stored_size(address).await {
+            Ok(Some(size)) => self.index.read_unindexed(address, size),
+            Ok(None) => (),
+            Err(err) => warn!("Could not index chunk {:?}: {}", address, err),
+        }
+    }
+
     pub async fn used_space_ratio(&self) -> f64 {
         self.chunks.used_space_ratio().await
     }
@@ -109,12 +233,14 @@ impl ChunkStorage {
         msg_id: MessageId,
         requester: PublicKey,
     ) -> Result<NodeDuty> {
-        if !self.chunks.has(&address) {
+        // Whether we hold the chunk or not, it's no longer served from the cache.
+        self.cache.remove(&address);
+        if !self.chunks.has(&address).await {
             info!("{}: Immutable chunk doesn't exist: {:?}", self, address);
             return Ok(NodeDuty::NoOp);
         }
 
-        match self.chunks.get(&address) {
+        match self.chunks.get(&address).await {
             Ok(Blob::Private(data)) => {
                 if data.owner() == &requester {
                     self.delete_chunk(&address)
@@ -150,16 +276,21 @@ impl Display for ChunkStorage {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::error::Result;
+    use crate::{chunks::index::ChunkMeta, error::Result};
     use bls::SecretKey;
     use sn_data_types::{PrivateBlob, PublicBlob, PublicKey};
     use std::path::PathBuf;
     use tempdir::TempDir;
+    use xor_name::XorName;
 
     fn temp_dir() -> Result<TempDir> {
         TempDir::new("test").map_err(|e| Error::TempDirCreationFailed(e.to_string()))
     }
 
+    fn origin() -> SrcLocation {
+        SrcLocation::Node(XorName::random())
+    }
+
     fn get_random_pk() -> PublicKey {
         PublicKey::from(SecretKey::random().public_key())
     }
@@ -167,11 +298,12 @@ mod tests {
     #[tokio::test]
     pub async fn try_store_stores_public_blob() -> Result<()> {
         let path = PathBuf::from(temp_dir()?.path());
-        let mut storage = ChunkStorage::new(&path, u64::MAX).await?;
+        let mut storage =
+            ChunkStorage::new(&path, &UsedSpace::new(u64::MAX), 0, &StoreConfig::default()).await?;
         let value = "immutable data value".to_owned().into_bytes();
         let blob = Blob::Public(PublicBlob::new(value));
         assert!(storage.try_store(&blob).await.is_ok());
-        assert!(storage.chunks.has(blob.address()));
+        assert!(storage.chunks.has(blob.address()).await);
 
         Ok(())
     }
@@ -179,13 +311,122 @@ mod tests {
     #[tokio::test]
     pub async fn try_store_stores_private_blob() -> Result<()> {
         let path = PathBuf::from(temp_dir()?.path());
-        let mut storage = ChunkStorage::new(&path, u64::MAX).await?;
+        let mut storage =
+            ChunkStorage::new(&path, &UsedSpace::new(u64::MAX), 0, &StoreConfig::default()).await?;
         let value = "immutable data value".to_owned().into_bytes();
         let key = get_random_pk();
         let blob = Blob::Private(PrivateBlob::new(value, key));
         assert!(storage.try_store(&blob).await.is_ok());
-        assert!(storage.chunks.has(blob.address()));
+        assert!(storage.chunks.has(blob.address()).await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn stored_chunks_are_indexed() -> Result<()> {
+        let path = PathBuf::from(temp_dir()?.path());
+        let mut storage =
+            ChunkStorage::new(&path, &UsedSpace::new(u64::MAX), 0, &StoreConfig::default()).await?;
+        let key = get_random_pk();
+        let blob = Blob::Private(PrivateBlob::new(vec![1; 10], key));
+        let _ = storage.store(&blob).await?;
+        let size = storage.chunks.stored_size(blob.address()).await?;
+        let meta = storage.index.get(blob.address());
+        assert!(matches!(
+            meta,
+            Some(ChunkMeta {
+                size: stored,
+                stored_at: Some(_),
+                last_read: None,
+                reads: 0
+            }) if Some(stored) == size
+        ));
+
+        let _ = storage
+            .get(blob.address(), MessageId::new(), origin())
+            .await;
+        let _ = storage
+            .get(blob.address(), MessageId::new(), origin())
+            .await;
+        assert!(matches!(
+            storage.index.get(blob.address()),
+            Some(ChunkMeta {
+                reads: 2,
+                last_read: Some(_),
+                ..
+            })
+        ));
+
+        let _ = storage
+            .delete(*blob.address(), MessageId::new(), key)
+            .await?;
+        assert_eq!(storage.index.get(blob.address()), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn only_read_chunks_are_cached_once_released() -> Result<()> {
+        let path = PathBuf::from(temp_dir()?.path());
+        let mut storage = ChunkStorage::new(
+            &path,
+            &UsedSpace::new(u64::MAX),
+            u64::MAX,
+            &StoreConfig::default(),
+        )
+        .await?;
+        let (read, unread) = (
+            Blob::Public(PublicBlob::new(vec![1; 10])),
+            Blob::Public(PublicBlob::new(vec![2; 10])),
+        );
+        let _ = storage.store(&read).await?;
+        let _ = storage.store(&unread).await?;
+        let _ = storage
+            .get(read.address(), MessageId::new(), origin())
+            .await;
+        storage.cache.remove(read.address());
+
+        storage.release_chunk(read.clone()).await?;
+        storage.release_chunk(unread.clone()).await?;
+        assert!(!storage.chunks.has(read.address()).await);
+        assert!(!storage.chunks.has(unread.address()).await);
+        assert_eq!(storage.cache.get(read.address()), Some(read));
+        assert_eq!(storage.cache.get(unread.address()), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn deleted_private_chunks_are_not_served() -> Result<()> {
+        let path = PathBuf::from(temp_dir()?.path());
+        let mut storage = ChunkStorage::new(
+            &path,
+            &UsedSpace::new(u64::MAX),
+            u64::MAX,
+            &StoreConfig::default(),
+        )
+        .await?;
+        let owner = get_random_pk();
+        let (released, cached) = (
+            Blob::Private(PrivateBlob::new(vec![1; 10], owner)),
+            Blob::Private(PrivateBlob::new(vec![2; 10], owner)),
+        );
+        for blob in &[&released, &cached] {
+            let _ = storage.store(blob).await?;
+            let _ = storage
+                .get(blob.address(), MessageId::new(), origin())
+                .await;
+        }
+
+        // Deletions of private chunks we no longer hold would be missed.
+        storage.release_chunk(released.clone()).await?;
+        assert_eq!(storage.cache.get(released.address()), None);
 
+        // A chunk only left in the cache is dropped from it when deleted.
+        storage.delete_chunk(cached.address()).await?;
+        assert!(storage.cache.get(cached.address()).is_some());
+        let _ = storage
+            .delete(*cached.address(), MessageId::new(), owner)
+            .await?;
+        assert_eq!(storage.cache.get(cached.address()), None);
         Ok(())
     }
 }
@@ -0,0 +1,298 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sn_data_types::BlobAddress;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, Weak},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    task::{self, JoinHandle},
+    time,
+};
+
+/// Max time changes to the index are held in memory before being written to disk.
+const DUMP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What's known about a stored chunk, beyond its contents.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ChunkMeta {
+    /// Size of the chunk as stored in bytes, i.e. after any compression and encryption.
+    pub size: u64,
+    /// When the chunk was stored, in seconds since the Unix epoch. Unknown for chunks stored
+    /// before the index was kept.
+    pub stored_at: Option<u64>,
+    /// When the chunk was last read, in seconds since the Unix epoch.
+    pub last_read: Option<u64>,
+    /// Number of times the chunk was read.
+    pub reads: u64,
+}
+
+/// Index of the chunks held by an Adult, so that their sizes, ages and reads are known without
+/// touching their files.
+///
+/// The index is held in memory, and written to disk in the background at most `DUMP_INTERVAL`
+/// apart, and when it's dropped, so the latest reads may be lost if the node crashes. Chunks
+/// missing from the index, e.g. stored before it was kept, are added when they're next read.
+pub(crate) struct ChunkIndex {
+    shared: Arc<Shared>,
+    // Writes the index to disk periodically, unless it's only kept in memory.
+    dumper: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    // Where the index is written to, unless it's only kept in memory.
+    path: Option<PathBuf>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<BlobAddress, ChunkMeta>,
+    // Whether there are changes not written to disk yet.
+    dirty: bool,
+}
+
+impl ChunkIndex {
+    /// Opens the index kept at `path`, or starts a new one if there's none or it can't be read.
+    /// It's then written to disk periodically, until dropped.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let entries = match fs::read(path) {
+            Ok(bytes) => bincode::deserialize(&bytes).unwrap_or_else(|err| {
+                warn!(
+                    "Starting a new chunk index, as {} can't be read: {}",
+                    path.display(),
+                    err
+                );
+                HashMap::new()
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let shared = Arc::new(Shared {
+            path: Some(path.to_path_buf()),
+            state: Mutex::new(State {
+                entries,
+                dirty: false,
+            }),
+        });
+        let dumper = tokio::spawn(dump_periodically(Arc::downgrade(&shared)));
+        Ok(Self {
+            shared,
+            dumper: Some(dumper),
+        })
+    }
+
+    /// Starts an index which is never written to disk.
+    pub fn in_memory() -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                path: None,
+                state: Mutex::new(State::default()),
+            }),
+            dumper: None,
+        }
+    }
+
+    /// Returns what's known about the chunk at `address`, if it's indexed.
+    pub fn get(&self, address: &BlobAddress) -> Option<ChunkMeta> {
+        self.shared.lock()?.entries.get(address).cloned()
+    }
+
+    /// Records that the chunk at `address`, taking `size` bytes as stored, was just stored.
+    pub fn stored(&self, address: &BlobAddress, size: u64) {
+        self.update(|entries| {
+            let _ = entries.insert(
+                *address,
+                ChunkMeta {
+                    size,
+                    stored_at: Some(now()),
+                    last_read: None,
+                    reads: 0,
+                },
+            );
+        })
+    }
+
+    /// Records a read of the chunk at `address`. Returns whether it's indexed at all.
+    pub fn read(&self, address: &BlobAddress) -> bool {
+        let mut indexed = false;
+        self.update(|entries| {
+            if let Some(meta) = entries.get_mut(address) {
+                meta.last_read = Some(now());
+                meta.reads += 1;
+                indexed = true;
+            }
+        });
+        indexed
+    }
+
+    /// Adds the chunk at `address`, taking `size` bytes as stored, which was stored before the
+    /// index was kept and has just been read.
+    pub fn read_unindexed(&self, address: &BlobAddress, size: u64) {
+        self.update(|entries| {
+            let _ = entries.insert(
+                *address,
+                ChunkMeta {
+                    size,
+                    stored_at: None,
+                    last_read: Some(now()),
+                    reads: 1,
+                },
+            );
+        })
+    }
+
+    /// Records that the chunk at `address` isn't stored anymore.
+    pub fn removed(&self, address: &BlobAddress) {
+        self.update(|entries| {
+            let _ = entries.remove(address);
+        })
+    }
+
+    // The chunks are what matters, so an index which can't be updated is left behind.
+    fn update(&self, op: impl FnOnce(&mut HashMap<BlobAddress, ChunkMeta>)) {
+        if let Some(mut state) = self.shared.lock() {
+            op(&mut state.entries);
+            state.dirty = true;
+        }
+    }
+}
+
+impl Drop for ChunkIndex {
+    fn drop(&mut self) {
+        if let Some(dumper) = &self.dumper {
+            dumper.abort();
+        }
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> Option<MutexGuard<'_, State>> {
+        self.state
+            .lock()
+            .map_err(|_| warn!("Chunk index lock poisoned"))
+            .ok()
+    }
+
+    // Writes the index to disk if it changed. Only serialising it holds the lock, so that reads
+    // aren't held up by the disk.
+    fn dump(&self) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        let bytes = {
+            let mut state = match self.lock() {
+                Some(state) => state,
+                None => return,
+            };
+            if !state.dirty {
+                return;
+            }
+            match bincode::serialize(&state.entries) {
+                Ok(bytes) => {
+                    state.dirty = false;
+                    bytes
+                }
+                Err(err) => {
+                    warn!("Could not serialise the chunk index: {}", err);
+                    return;
+                }
+            }
+        };
+        // Written next to the index first, so that a crash can't leave half an index behind.
+        let temp_path = path.with_extension("tmp");
+        if let Err(err) = fs::write(&temp_path, bytes).and_then(|()| fs::rename(&temp_path, path)) {
+            warn!(
+                "Could not write the chunk index to {}: {}",
+                path.display(),
+                err
+            );
+            if let Some(mut state) = self.lock() {
+                state.dirty = true;
+            }
+        }
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        self.dump()
+    }
+}
+
+// Writes the index to disk every `DUMP_INTERVAL`, on the blocking thread pool.
+async fn dump_periodically(shared: Weak<Shared>) {
+    loop {
+        time::sleep(DUMP_INTERVAL).await;
+        let shared = match shared.upgrade() {
+            Some(shared) => shared,
+            None => return,
+        };
+        let _ = task::spawn_blocking(move || shared.dump()).await;
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChunkIndex;
+    use crate::{Error, Result};
+    use sn_data_types::{Blob, PublicBlob};
+    use tempdir::TempDir;
+
+    fn not_indexed() -> Error {
+        Error::Logic("Chunk not indexed".to_string())
+    }
+
+    #[tokio::test]
+    async fn index_is_kept_across_restarts() -> Result<()> {
+        let dir =
+            TempDir::new("chunk_index").map_err(|e| Error::TempDirCreationFailed(e.to_string()))?;
+        let path = dir.path().join("index.db");
+        let (a, b, c) = (
+            Blob::Public(PublicBlob::new(vec![1; 10])),
+            Blob::Public(PublicBlob::new(vec![2; 20])),
+            Blob::Public(PublicBlob::new(vec![3; 30])),
+        );
+        {
+            let index = ChunkIndex::open(&path)?;
+            index.stored(a.address(), 10);
+            index.stored(b.address(), 20);
+            assert!(index.read(a.address()));
+            index.removed(b.address());
+            assert!(!index.read(c.address()));
+            index.read_unindexed(c.address(), 30);
+        }
+
+        let index = ChunkIndex::open(&path)?;
+        let a_meta = index.get(a.address()).ok_or_else(not_indexed)?;
+        assert_eq!(a_meta.size, 10);
+        assert_eq!(a_meta.reads, 1);
+        assert!(a_meta.stored_at.is_some() && a_meta.last_read.is_some());
+        assert_eq!(index.get(b.address()), None);
+        let c_meta = index.get(c.address()).ok_or_else(not_indexed)?;
+        assert_eq!((c_meta.size, c_meta.reads, c_meta.stored_at), (30, 1, None));
+        Ok(())
+    }
+}
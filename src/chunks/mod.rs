@@ -6,54 +6,101 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod cache;
 mod chunk_storage;
+mod index;
+pub(crate) mod storage_proof;
 
 use crate::{
+    chunk_store::{KeysCursor, KeysPage, StoreConfig, UsedSpace},
     node_ops::{NodeDuties, NodeDuty},
-    Result,
+    Error, Result,
 };
 use chunk_storage::ChunkStorage;
-use log::info;
+use log::{info, warn};
 use sn_data_types::{Blob, BlobAddress, PublicKey};
 use sn_messaging::{
     client::{BlobRead, BlobWrite},
-    MessageId,
+    MessageId, SrcLocation,
 };
 use std::{
     fmt::{self, Display, Formatter},
     path::Path,
+    time::Duration,
 };
 
 /// At 50% full, the node will report that it's reaching full capacity.
 pub const MAX_STORAGE_USAGE_RATIO: f64 = 0.5;
 
+/// Back under 40% full, the node will stop reporting that it's full.
+pub const MIN_STORAGE_USAGE_RATIO: f64 = 0.4;
+
+/// Time between two reports of still being full, which keep the Elders counting the node as such.
+pub const FULL_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Time between two scrub passes over the stored chunks.
+pub const SCRUB_INTERVAL: Duration = Duration::from_secs(10);
+
 /// Operations on data chunks.
 pub(crate) struct Chunks {
     chunk_storage: ChunkStorage,
+    // Where the current scrub round carries on from, unless it's yet to start.
+    scrub_cursor: Option<KeysCursor>,
+    // Whether we've reported reaching full capacity, and not freed up enough since.
+    reported_full: bool,
 }
 
 impl Chunks {
-    pub async fn new(path: &Path, max_capacity: u64) -> Result<Self> {
+    /// Opens the chunk storage under `path`, tracked in the node's `used_space`, caching up to
+    /// `cache_capacity` bytes of chunks on top of those stored.
+    pub async fn new(
+        path: &Path,
+        used_space: &UsedSpace,
+        cache_capacity: u64,
+        config: &StoreConfig,
+    ) -> Result<Self> {
         Ok(Self {
-            chunk_storage: ChunkStorage::new(path, max_capacity).await?,
+            chunk_storage: ChunkStorage::new(path, used_space, cache_capacity, config).await?,
+            scrub_cursor: None,
+            reported_full: false,
         })
     }
 
-    pub fn keys(&self) -> Vec<BlobAddress> {
-        self.chunk_storage.keys()
+    /// Lists up to `limit` addresses of the stored chunks, starting at `cursor`, or from the first
+    /// one without it.
+    pub async fn keys_page(
+        &self,
+        cursor: Option<&KeysCursor>,
+        limit: usize,
+    ) -> Result<KeysPage<BlobAddress>> {
+        self.chunk_storage.keys_page(cursor, limit).await
     }
 
     pub async fn remove_chunk(&mut self, address: &BlobAddress) -> Result<()> {
         self.chunk_storage.delete_chunk(address).await
     }
 
-    pub fn get_chunk(&self, address: &BlobAddress) -> Result<Blob> {
-        self.chunk_storage.get_chunk(address)
+    pub async fn get_chunk(&self, address: &BlobAddress) -> Result<Blob> {
+        self.chunk_storage.get_chunk(address).await
+    }
+
+    pub async fn release_chunk(&mut self, blob: Blob) -> Result<()> {
+        self.chunk_storage.release_chunk(blob).await
     }
 
-    pub fn read(&self, read: &BlobRead, msg_id: MessageId) -> NodeDuty {
+    pub async fn read(&self, read: &BlobRead, msg_id: MessageId, origin: SrcLocation) -> NodeDuty {
         let BlobRead::Get(address) = read;
-        self.chunk_storage.get(address, msg_id)
+        self.chunk_storage.get(address, msg_id, origin).await
+    }
+
+    /// Responds to the storage challenge `msg_id` for the chunk at `address`, sent by `origin`.
+    pub async fn prove_storage(
+        &self,
+        address: &BlobAddress,
+        msg_id: MessageId,
+        origin: SrcLocation,
+    ) -> NodeDuty {
+        self.chunk_storage.prove(address, msg_id, origin).await
     }
 
     pub async fn write(
@@ -70,13 +117,54 @@ impl Chunks {
         }
     }
 
-    pub async fn check_storage(&self) -> Result<NodeDuties> {
+    /// Checks our used space after storing or removing chunks, reporting reaching max capacity.
+    pub async fn check_storage(&mut self) -> NodeDuties {
         info!("Checking used storage");
-        if self.chunk_storage.used_space_ratio().await > MAX_STORAGE_USAGE_RATIO {
-            Ok(NodeDuties::from(NodeDuty::ReachingMaxCapacity))
+        let used_space_ratio = self.chunk_storage.used_space_ratio().await;
+        if used_space_ratio > MAX_STORAGE_USAGE_RATIO {
+            self.reported_full = true;
+            return NodeDuties::from(NodeDuty::ReachingMaxCapacity);
+        }
+        if self.reported_full && used_space_ratio < MIN_STORAGE_USAGE_RATIO {
+            info!("Storage back under the low watermark, we're no longer full");
+            self.reported_full = false;
+        }
+        vec![]
+    }
+
+    /// Renews our report of reaching max capacity until we're back under the low watermark. The
+    /// Elders stop counting us as full once the reports stop.
+    pub async fn renew_full_report(&mut self) -> NodeDuties {
+        let duties = self.check_storage().await;
+        if duties.is_empty() && self.reported_full {
+            NodeDuties::from(NodeDuty::ReachingMaxCapacity)
         } else {
-            Ok(vec![])
+            duties
+        }
+    }
+
+    /// Verifies the next `count` stored chunks, removing those which can't be read back intact,
+    /// and returns their addresses. Once all chunks have been verified, a new round is started.
+    pub async fn scrub(&mut self, count: usize) -> Result<Vec<BlobAddress>> {
+        let page = self.keys_page(self.scrub_cursor.as_ref(), count).await?;
+        self.scrub_cursor = page.next;
+
+        let mut corrupt = vec![];
+        for address in page.ids {
+            match self.get_chunk(&address).await {
+                Err(Error::CorruptChunk(_)) | Err(Error::Bincode(_)) => {
+                    warn!("Removing corrupt chunk {:?}", address);
+                    if let Err(err) = self.remove_chunk(&address).await {
+                        warn!("Error removing corrupt chunk {:?}: {:?}", address, err);
+                        continue;
+                    }
+                    corrupt.push(address);
+                }
+                // Intact, or deleted since the round started.
+                _ => (),
+            }
         }
+        Ok(corrupt)
     }
 
     /// Stores a chunk that Elders sent to it for replication.
@@ -91,3 +179,70 @@ impl Display for Chunks {
         write!(formatter, "Chunks")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk_store::CHUNK_STORE_DIR, utils};
+    use sn_data_types::PublicBlob;
+    use std::{fs, path::PathBuf};
+    use tempdir::TempDir;
+
+    fn temp_dir() -> Result<TempDir> {
+        TempDir::new("test").map_err(|e| Error::TempDirCreationFailed(e.to_string()))
+    }
+
+    // Finds the file under `dir` holding exactly `contents`.
+    fn find_file(dir: &Path, contents: &[u8]) -> Result<Option<PathBuf>> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let found = if path.is_dir() {
+                find_file(&path, contents)?
+            } else if fs::read(&path)? == contents {
+                Some(path)
+            } else {
+                None
+            };
+            if found.is_some() {
+                return Ok(found);
+            }
+        }
+        Ok(None)
+    }
+
+    #[tokio::test]
+    async fn scrub_removes_and_reports_corrupt_chunks() -> Result<()> {
+        let root = temp_dir()?;
+        let mut chunks = Chunks::new(
+            root.path(),
+            &UsedSpace::new(u64::MAX),
+            0,
+            &StoreConfig::default(),
+        )
+        .await?;
+        let intact = Blob::Public(PublicBlob::new(vec![1, 2, 3]));
+        let corrupt = Blob::Public(PublicBlob::new(vec![4, 5, 6]));
+        for blob in &[&intact, &corrupt] {
+            let _ = chunks.store_for_replication((*blob).clone()).await?;
+        }
+
+        let path = find_file(
+            &root.path().join(CHUNK_STORE_DIR),
+            &utils::serialise(&corrupt)?,
+        )?
+        .ok_or_else(|| Error::Logic("Chunk file not found".to_string()))?;
+        let mut contents = fs::read(&path)?;
+        if let Some(byte) = contents.last_mut() {
+            *byte ^= 1;
+        }
+        fs::write(&path, &contents)?;
+
+        assert_eq!(chunks.scrub(10).await?, vec![*corrupt.address()]);
+        assert!(chunks.get_chunk(corrupt.address()).await.is_err());
+        assert!(chunks.get_chunk(intact.address()).await.is_ok());
+        // A new round finds nothing left to remove.
+        assert!(chunks.scrub(10).await?.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Proofs that an Adult holds a chunk: the hash of a nonce picked by the Elders followed by the
+//! chunk's content, which can't be computed ahead of the challenge without keeping the content.
+//!
+//! The Elders challenge the holders of a chunk they have a copy of with a
+//! `NodeSystemQuery::GetChunk` query, the id of which is the nonce. The holders respond with a
+//! `NodeSystemQueryResponse::GetChunk`, carrying the proof as the content of a public blob.
+//!
+//! The node messages have no room for storage challenges, and can't be extended from here. These
+//! two were meant to fetch chunks for replication, which the nodes never use them for: chunks are
+//! replicated with `NodeSystemCmd::ReplicateChunk`, and read with `NodeQuery::Chunks`. They are
+//! thus dedicated to storage challenges, and the blob of a `GetChunk` response is never a chunk.
+//! The messages are only built here, with `StorageChallenge::query` and `proof_response`.
+//!
+//! They also carry the existence checks of the replica audit, which only ask the holders whether
+//! they still have a chunk, without them reading it. Those are told apart from challenges by the
+//! `EXISTENCE_CHECK_MARKER` their id starts with, and the blob of their response is non-empty if
+//! the holder has the chunk. They are built with `existence_check` and `existence_response`.
+
+use ring::digest::{Context, SHA256, SHA256_OUTPUT_LEN};
+use sn_data_types::{Blob, BlobAddress, PublicBlob};
+use sn_messaging::{
+    node::{NodeMsg, NodeQuery, NodeQueryResponse, NodeSystemQuery, NodeSystemQueryResponse},
+    MessageId, MESSAGE_ID_LEN,
+};
+
+// The ids of existence checks start with this, followed by random bytes.
+const EXISTENCE_CHECK_MARKER: &[u8] = b"sn-exist";
+
+/// Proof of holding a chunk, for a given nonce.
+pub type StorageProof = [u8; SHA256_OUTPUT_LEN];
+
+/// Computes the proof of holding a chunk with the given `content`, for the challenge `msg_id`.
+pub fn storage_proof(msg_id: &MessageId, content: &[u8]) -> StorageProof {
+    let nonce: &[u8; MESSAGE_ID_LEN] = msg_id.as_ref();
+    let mut context = Context::new(&SHA256);
+    context.update(nonce);
+    context.update(content);
+    let mut proof = [0; SHA256_OUTPUT_LEN];
+    proof.copy_from_slice(context.finish().as_ref());
+    proof
+}
+
+/// Wraps `proof` to be carried in a `NodeSystemQueryResponse::GetChunk`. A holder which doesn't
+/// have the chunk responds with an empty blob.
+pub fn proof_blob(proof: Option<StorageProof>) -> Blob {
+    let content = proof.map(|proof| proof.to_vec()).unwrap_or_default();
+    Blob::Public(PublicBlob::new(content))
+}
+
+/// The response to the challenge `msg_id`, with the proof of holding the chunk, if we have it.
+pub fn proof_response(msg_id: MessageId, proof: Option<StorageProof>) -> NodeMsg {
+    NodeMsg::NodeQueryResponse {
+        response: NodeQueryResponse::System(NodeSystemQueryResponse::GetChunk(proof_blob(proof))),
+        id: MessageId::in_response_to(&msg_id),
+        correlation_id: msg_id,
+    }
+}
+
+/// A fresh id for an existence check.
+pub fn existence_check_id() -> MessageId {
+    let mut id = [0; MESSAGE_ID_LEN];
+    id[EXISTENCE_CHECK_MARKER.len()..].copy_from_slice(&rand::random::<[u8; 24]>());
+    id[..EXISTENCE_CHECK_MARKER.len()].copy_from_slice(EXISTENCE_CHECK_MARKER);
+    MessageId::with(id)
+}
+
+/// Whether `msg_id` is that of an existence check, rather than of a storage challenge.
+pub fn is_existence_check(msg_id: &MessageId) -> bool {
+    let id: &[u8; MESSAGE_ID_LEN] = msg_id.as_ref();
+    id.starts_with(EXISTENCE_CHECK_MARKER)
+}
+
+/// The query asking the holders of the chunk at `address` whether they still have it.
+pub fn existence_check(address: BlobAddress, msg_id: MessageId) -> NodeMsg {
+    NodeMsg::NodeQuery {
+        query: NodeQuery::System(NodeSystemQuery::GetChunk(address)),
+        id: msg_id,
+    }
+}
+
+/// The response to the existence check `msg_id`, telling whether we have the chunk.
+pub fn existence_response(msg_id: MessageId, held: bool) -> NodeMsg {
+    let content = if held { vec![1] } else { vec![] };
+    NodeMsg::NodeQueryResponse {
+        response: NodeQueryResponse::System(NodeSystemQueryResponse::GetChunk(Blob::Public(
+            PublicBlob::new(content),
+        ))),
+        id: MessageId::in_response_to(&msg_id),
+        correlation_id: msg_id,
+    }
+}
+
+/// A challenge for the holders of a chunk to prove they hold it.
+#[derive(Debug)]
+pub(crate) struct StorageChallenge {
+    pub address: BlobAddress,
+    pub msg_id: MessageId,
+    expected: StorageProof,
+}
+
+impl StorageChallenge {
+    /// Picks a fresh nonce to challenge the holders of `chunk` with, a copy of which was read.
+    pub fn new(chunk: &Blob) -> Self {
+        let msg_id = MessageId::new();
+        Self {
+            address: *chunk.address(),
+            msg_id,
+            expected: storage_proof(&msg_id, chunk.value()),
+        }
+    }
+
+    /// The query challenging the holders of the chunk.
+    pub fn query(&self) -> NodeMsg {
+        NodeMsg::NodeQuery {
+            query: NodeQuery::System(NodeSystemQuery::GetChunk(self.address)),
+            id: self.msg_id,
+        }
+    }
+
+    /// Whether the content of the blob a holder responded with proves holding the chunk.
+    pub fn verify(&self, proof: &[u8]) -> bool {
+        proof == self.expected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proofs_need_the_content_and_the_nonce() {
+        let chunk = Blob::Public(PublicBlob::new(vec![1; 100]));
+        let challenge = StorageChallenge::new(&chunk);
+        assert!(challenge.verify(&storage_proof(&challenge.msg_id, chunk.value())));
+
+        assert!(!challenge.verify(&storage_proof(&challenge.msg_id, &[1; 99])));
+        assert!(!challenge.verify(&storage_proof(&MessageId::new(), chunk.value())));
+        assert!(
+            !StorageChallenge::new(&chunk).verify(&storage_proof(&challenge.msg_id, chunk.value()))
+        );
+        assert!(!challenge.verify(proof_blob(None).value()));
+        let proof = storage_proof(&challenge.msg_id, chunk.value());
+        assert!(challenge.verify(proof_blob(Some(proof)).value()));
+    }
+
+    #[test]
+    fn existence_checks_are_told_apart_from_challenges() {
+        let chunk = Blob::Public(PublicBlob::new(vec![1; 100]));
+        assert!(!is_existence_check(&StorageChallenge::new(&chunk).msg_id));
+        let msg_id = existence_check_id();
+        assert!(is_existence_check(&msg_id));
+        assert_ne!(msg_id, existence_check_id());
+    }
+}
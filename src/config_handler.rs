@@ -8,23 +8,33 @@
 
 #![allow(trivial_numeric_casts)] // FIXME
 
-use crate::{Error, Result};
+use crate::{
+    chunk_store::{
+        self, DataDir, StorageBackendKind, StoreConfig, DEFAULT_DIR_DEPTH, DEFAULT_MAX_DISK_OPS,
+    },
+    metadata::{BlobStorageMode, LivenessPolicy, ReadStrategy},
+    Error, Result,
+};
 use log::{debug, Level};
 use serde::{Deserialize, Serialize};
 use sn_routing::TransportConfig as NetworkConfig;
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
     fs::{self, File},
     io::{self, BufReader},
     net::{IpAddr, Ipv4Addr, SocketAddr},
     path::PathBuf,
+    sync::Arc,
 };
 use structopt::StructOpt;
+use tokio::sync::Semaphore;
 
 const CONFIG_FILE: &str = "node.config";
 const CONNECTION_INFO_FILE: &str = "node_connection_info.config";
 const DEFAULT_ROOT_DIR_NAME: &str = "root_dir";
 const DEFAULT_MAX_CAPACITY: u64 = 2 * 1024 * 1024 * 1024;
+const DEFAULT_CHUNK_CACHE_CAPACITY: u64 = 64 * 1024 * 1024;
+const DEFAULT_SCRUB_RATE: u64 = 1;
 
 /// Node configuration
 #[derive(Default, Clone, Debug, Serialize, Deserialize, Eq, PartialEq, StructOpt)]
@@ -45,6 +55,82 @@ pub struct Config {
     /// MacOS: $HOME/.safe/node/root_dir
     #[structopt(short, long, parse(from_os_str))]
     pub root_dir: Option<PathBuf>,
+    /// Dir to spread chunks over, with the maximum space in bytes they may take in it, as
+    /// `<path>=<max capacity>`, e.g. one per disk. Can be passed several times. Chunks are placed
+    /// in the dir with the most space left, and only those in a dir which fails are lost. When
+    /// set, the node's storage capacity is the sum of the dirs' capacities, instead of
+    /// `max_capacity`, while the rest of the node's state stays in the root dir.
+    #[structopt(long = "data-dir", number_of_values = 1)]
+    pub data_dirs: Vec<DataDir>,
+    /// Number of directory levels chunk files are spread over within each ChunkStore, e.g. a
+    /// depth of 2 stores chunks under `ab/cd/`. Existing stores are migrated on start. Defaults
+    /// to 2, while 0 keeps all of a store's chunks in a single directory.
+    #[structopt(long)]
+    pub chunk_dir_depth: Option<u8>,
+    /// Where chunks are stored: "fs" for a file per chunk (the default), "kv" for a single
+    /// embedded key-value database per store, or "memory" to keep them in memory only, losing
+    /// them on restart.
+    #[structopt(long)]
+    pub storage_backend: Option<StorageBackendKind>,
+    /// Read back every stored chunk on start, quarantining those which can't be deserialised, and
+    /// only counting the others against the record of used space. Otherwise only their sizes are
+    /// counted, and only empty ones are quarantined.
+    #[structopt(long)]
+    pub rescan_chunks: bool,
+    /// Comma separated names of the stores whose new chunks are compressed: any of "immutable",
+    /// "mutable", "sequence" and "register". Chunks already stored are read back either way.
+    #[structopt(long, use_delimiter = true)]
+    pub compressed_stores: Vec<String>,
+    /// File whose contents the key used to encrypt stored chunks and transfers is derived from.
+    /// Without it, the key is derived from the passphrase in the SN_NODE_STORAGE_PASSPHRASE
+    /// environment variable, if set. Once the data is encrypted, the node won't start without the
+    /// same key.
+    #[structopt(long, parse(from_os_str))]
+    pub storage_key_file: Option<PathBuf>,
+    /// Maximum number of chunk store disk operations run at once. Operations beyond it wait for
+    /// one to finish, so that heavy replication can't starve message handling. Defaults to 32.
+    #[structopt(long)]
+    pub max_disk_ops: Option<usize>,
+    /// Maximum total size in bytes of the chunks an Adult keeps cached in memory, for chunks it
+    /// stopped holding and recently read ones. Not counted against `max_capacity`. Defaults to
+    /// 64 MiB, while 0 disables the cache.
+    #[structopt(long)]
+    pub chunk_cache_capacity: Option<u64>,
+    /// Number of stored chunks an Adult reads back per second to check their integrity, removing
+    /// corrupt ones so that the Elders replicate them back. Defaults to 1, while 0 disables the
+    /// checks.
+    #[structopt(long)]
+    pub scrub_rate: Option<u64>,
+    /// Maximum space in bytes the stored blobs may take, within `max_capacity`. Without it, blobs
+    /// may take all of the space the other data types leave.
+    #[structopt(long)]
+    pub blob_quota: Option<u64>,
+    /// Maximum space in bytes the stored maps may take, within `max_capacity`.
+    #[structopt(long)]
+    pub map_quota: Option<u64>,
+    /// Maximum space in bytes the stored sequences may take, within `max_capacity`.
+    #[structopt(long)]
+    pub sequence_quota: Option<u64>,
+    /// Maximum space in bytes the stored registers may take, within `max_capacity`.
+    #[structopt(long)]
+    pub register_quota: Option<u64>,
+    /// How Elders pick the Adults to propose offline: `pending-ops` for those with far more reads
+    /// pending than their closest neighbours, `score[:<min score>]` for those whose score for
+    /// timely responses has fallen below a minimum, in percent (50 by default), or `disabled`.
+    /// Defaults to `pending-ops`.
+    #[structopt(long)]
+    pub liveness_policy: Option<LivenessPolicy>,
+    /// How Elders query the holders of a chunk when it's read: `all-holders` at once, or
+    /// `hedged[:<delay in ms>]` for the healthiest holder first and the others only if it fails,
+    /// or hasn't responded after the delay (2000 ms by default). Defaults to `all-holders`.
+    #[structopt(long)]
+    pub read_strategy: Option<ReadStrategy>,
+    /// How Elders store blobs on the Adults: `replicated` for full copies on each holder, or
+    /// `erasure-coded[:<data shards>+<parity shards>]` to split them into shards, any data shards
+    /// of which rebuild the blob (4+2 by default). Blobs already stored keep their mode. Defaults
+    /// to `replicated`.
+    #[structopt(long)]
+    pub blob_storage: Option<BlobStorageMode>,
     /// Verbose output. `-v` is equivalent to logging with `warn`, `-vv` to `info`, `-vvv` to
     /// `debug`, `-vvvv` to `trace`. This flag overrides RUST_LOG.
     #[structopt(short, long, parse(from_occurrences))]
@@ -118,6 +204,46 @@ pub struct Config {
     #[structopt(skip)]
     #[allow(missing_docs)]
     pub network_config: NetworkConfig,
+    /// Task to run on the node's stored data, instead of starting the node.
+    #[structopt(subcommand)]
+    #[serde(skip)]
+    pub command: Option<Command>,
+}
+
+/// Maintenance tasks on a node's stored data. The node must not be running meanwhile.
+#[derive(Clone, Debug, Eq, PartialEq, StructOpt)]
+pub enum Command {
+    /// Writes the node's chunk stores, used space records, transfer stores and keys to a single
+    /// archive.
+    Export {
+        /// Path of the archive to write.
+        #[structopt(parse(from_os_str))]
+        archive: PathBuf,
+    },
+    /// Restores the node's data from an archive written by `export`, verifying every chunk. The
+    /// root dir must not hold any data yet.
+    Import {
+        /// Path of the archive to read.
+        #[structopt(parse(from_os_str))]
+        archive: PathBuf,
+    },
+    /// Brings the node's data to the format of this version of the node. This is also done when
+    /// the node starts.
+    Migrate {
+        /// Only lists the migrations which would be run.
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Finds the files in the chunk stores which aren't chunks, and moves them to the quarantine
+    /// dir, reporting their total size.
+    Gc {
+        /// Only reports them.
+        #[structopt(long)]
+        dry_run: bool,
+        /// Removes them instead of quarantining them.
+        #[structopt(long, conflicts_with = "dry-run")]
+        remove: bool,
+    },
 }
 
 impl Config {
@@ -146,6 +272,35 @@ impl Config {
     }
 
     fn validate(&mut self) -> Result<(), Error> {
+        if let Some(name) = self
+            .compressed_stores
+            .iter()
+            .find(|name| !chunk_store::is_store_name(name))
+        {
+            return Err(Error::Configuration(format!(
+                "--compressed-stores passed an unknown store: {}",
+                name
+            )));
+        }
+
+        for (index, data_dir) in self.data_dirs.iter().enumerate() {
+            if self.data_dirs[..index]
+                .iter()
+                .any(|other| other.path == data_dir.path)
+            {
+                return Err(Error::Configuration(format!(
+                    "--data-dir passed {} more than once",
+                    data_dir.path.display()
+                )));
+            }
+        }
+
+        if self.max_disk_ops == Some(0) {
+            return Err(Error::Configuration(
+                "--max-disk-ops must allow at least one operation".to_string(),
+            ));
+        }
+
         if let Some(_external_addr) = self.public_addr {
             if self.first.is_none() && self.local_addr.is_none() {
                 return Err(Error::Configuration("--public-addr passed without specifing local address using --first or --local-addr".to_string()));
@@ -178,6 +333,18 @@ impl Config {
             self.root_dir = Some(root_dir.clone());
         }
 
+        if !config.data_dirs.is_empty() {
+            self.data_dirs = config.data_dirs;
+        }
+
+        if let Some(chunk_dir_depth) = config.chunk_dir_depth {
+            self.chunk_dir_depth = Some(chunk_dir_depth);
+        }
+
+        if let Some(storage_backend) = config.storage_backend {
+            self.storage_backend = Some(storage_backend);
+        }
+
         if config.verbose > 0 {
             self.verbose = config.verbose;
         }
@@ -193,6 +360,59 @@ impl Config {
         self.update = config.update || self.update;
         self.update_only = config.update_only || self.update_only;
         self.clear_data = config.clear_data || self.clear_data;
+        self.rescan_chunks = config.rescan_chunks || self.rescan_chunks;
+
+        if !config.compressed_stores.is_empty() {
+            self.compressed_stores = config.compressed_stores;
+        }
+
+        if let Some(storage_key_file) = &config.storage_key_file {
+            self.storage_key_file = Some(storage_key_file.clone());
+        }
+
+        if let Some(max_disk_ops) = config.max_disk_ops {
+            self.max_disk_ops = Some(max_disk_ops);
+        }
+
+        if let Some(chunk_cache_capacity) = config.chunk_cache_capacity {
+            self.chunk_cache_capacity = Some(chunk_cache_capacity);
+        }
+
+        if let Some(scrub_rate) = config.scrub_rate {
+            self.scrub_rate = Some(scrub_rate);
+        }
+
+        if let Some(liveness_policy) = config.liveness_policy {
+            self.liveness_policy = Some(liveness_policy);
+        }
+
+        if let Some(read_strategy) = config.read_strategy {
+            self.read_strategy = Some(read_strategy);
+        }
+
+        if let Some(blob_storage) = config.blob_storage {
+            self.blob_storage = Some(blob_storage);
+        }
+
+        if let Some(blob_quota) = config.blob_quota {
+            self.blob_quota = Some(blob_quota);
+        }
+
+        if let Some(map_quota) = config.map_quota {
+            self.map_quota = Some(map_quota);
+        }
+
+        if let Some(sequence_quota) = config.sequence_quota {
+            self.sequence_quota = Some(sequence_quota);
+        }
+
+        if let Some(register_quota) = config.register_quota {
+            self.register_quota = Some(register_quota);
+        }
+
+        if let Some(command) = config.command {
+            self.command = Some(command);
+        }
 
         if let Some(socket_addr) = config.first {
             self.first = Some(socket_addr);
@@ -248,9 +468,21 @@ impl Config {
         self.first.is_some()
     }
 
-    /// Upper limit in bytes for allowed network storage on this node.
+    /// Upper limit in bytes for allowed network storage on this node: the sum of the capacities
+    /// of the data dirs, if any.
     pub fn max_capacity(&self) -> u64 {
-        self.max_capacity.unwrap_or(DEFAULT_MAX_CAPACITY)
+        if self.data_dirs.is_empty() {
+            self.max_capacity.unwrap_or(DEFAULT_MAX_CAPACITY)
+        } else {
+            self.data_dirs.iter().fold(0, |total, data_dir| {
+                total.saturating_add(data_dir.max_capacity)
+            })
+        }
+    }
+
+    /// Dirs chunks are spread over, besides the root dir.
+    pub fn data_dirs(&self) -> &[DataDir] {
+        &self.data_dirs
     }
 
     /// Root directory for `ChunkStore`s and cached state. If not set, it defaults to
@@ -263,6 +495,85 @@ impl Config {
         })
     }
 
+    /// Number of directory levels chunk files are spread over within each `ChunkStore`.
+    pub fn chunk_dir_depth(&self) -> u8 {
+        self.chunk_dir_depth.unwrap_or(DEFAULT_DIR_DEPTH)
+    }
+
+    /// Where chunks are stored.
+    pub fn storage_backend(&self) -> StorageBackendKind {
+        self.storage_backend.unwrap_or(StorageBackendKind::Fs)
+    }
+
+    /// Read back every stored chunk on start?
+    pub fn rescan_chunks(&self) -> bool {
+        self.rescan_chunks
+    }
+
+    /// File the key used to encrypt stored data is derived from.
+    pub fn storage_key_file(&self) -> Option<&PathBuf> {
+        self.storage_key_file.as_ref()
+    }
+
+    /// Maximum number of chunk store disk operations run at once.
+    pub fn max_disk_ops(&self) -> usize {
+        self.max_disk_ops.unwrap_or(DEFAULT_MAX_DISK_OPS)
+    }
+
+    /// Maximum total size of the chunks an Adult keeps cached in memory.
+    pub fn chunk_cache_capacity(&self) -> u64 {
+        self.chunk_cache_capacity
+            .unwrap_or(DEFAULT_CHUNK_CACHE_CAPACITY)
+    }
+
+    /// Number of stored chunks an Adult checks the integrity of per second.
+    pub fn scrub_rate(&self) -> u64 {
+        self.scrub_rate.unwrap_or(DEFAULT_SCRUB_RATE)
+    }
+
+    /// How Elders pick the Adults to propose offline.
+    pub fn liveness_policy(&self) -> LivenessPolicy {
+        self.liveness_policy.unwrap_or_default()
+    }
+
+    /// How Elders query the holders of a chunk when it's read.
+    pub fn read_strategy(&self) -> ReadStrategy {
+        self.read_strategy.unwrap_or_default()
+    }
+
+    /// How Elders store new blobs on the Adults.
+    pub fn blob_storage(&self) -> BlobStorageMode {
+        self.blob_storage.unwrap_or_default()
+    }
+
+    /// Maximum space the stored data of each type may take, by type name, for the types with a
+    /// quota.
+    pub fn quotas(&self) -> BTreeMap<String, u64> {
+        [
+            ("blob", self.blob_quota),
+            ("map", self.map_quota),
+            ("sequence", self.sequence_quota),
+            ("register", self.register_quota),
+        ]
+        .iter()
+        .filter_map(|(name, quota)| quota.map(|quota| (name.to_string(), quota)))
+        .collect()
+    }
+
+    /// Settings for the node's `ChunkStore`s.
+    pub(crate) fn store_config(&self) -> StoreConfig {
+        StoreConfig {
+            backend: self.storage_backend(),
+            dir_depth: self.chunk_dir_depth(),
+            full_rescan: self.rescan_chunks(),
+            compressed_stores: self.compressed_stores.clone(),
+            storage_key: None,
+            quotas: self.quotas(),
+            data_dirs: self.data_dirs.clone(),
+            disk_ops: Arc::new(Semaphore::new(self.max_disk_ops())),
+        }
+    }
+
     /// Set the root directory for `ChunkStore`s and cached state.
     pub fn set_root_dir<P: Into<PathBuf>>(&mut self, path: P) {
         self.root_dir = Some(path.into())
@@ -314,6 +625,11 @@ impl Config {
         self.update_only
     }
 
+    /// Task to run instead of starting the node, if any.
+    pub fn command(&self) -> Option<&Command> {
+        self.command.as_ref()
+    }
+
     /// Set the Quic-P2P `ip` configuration to 127.0.0.1.
     pub fn listen_on_loopback(&mut self) {
         self.network_config.local_ip = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
@@ -326,6 +642,12 @@ impl Config {
             if path.exists() {
                 std::fs::remove_dir_all(&path)?;
             }
+            for data_dir in &self.data_dirs {
+                let path = data_dir.path.join(chunk_store::CHUNK_STORE_DIR);
+                if path.exists() {
+                    fs::remove_dir_all(&path)?;
+                }
+            }
         }
         Ok(())
     }
@@ -428,7 +750,7 @@ fn smoke() {
     // NOTE: IF this value is being changed due to a change in the config,
     // the change in config also be handled in Config::merge()
     // and in examples/config_handling.rs
-    let expected_size = 504;
+    let expected_size = 736;
 
     assert_eq!(std::mem::size_of::<Config>(), expected_size);
 }
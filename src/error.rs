@@ -34,6 +34,12 @@ pub enum Error {
     /// Not enough space in `ChunkStore` to perform `put`.
     #[error("Not enough space")]
     NotEnoughSpace,
+    /// Not enough space left within the quota of a data type to perform `put`.
+    #[error("Not enough space within the {0} quota")]
+    NotEnoughSpaceFor(String),
+    /// None of the holders of a chunk returned it before the read timed out.
+    #[error("No holder returned {0:?} in time")]
+    ReadTimedOut(DataAddress),
     /// Not enough storage available on the network.
     #[error("Not enough storage available on the network")]
     NetworkFull,
@@ -100,12 +106,24 @@ pub enum Error {
     /// Key, Value pair not found in `ChunkStore`.
     #[error("No such chunk: {0:?}")]
     NoSuchChunk(DataAddress),
+    /// The content of a chunk in `ChunkStore` no longer matches its address.
+    #[error("Chunk failed its integrity check: {0:?}")]
+    CorruptChunk(DataAddress),
     /// Unable to process fund churn message.
     #[error("Cannot process fund churn message")]
     NotChurningFunds,
     /// Creating temp directory failed.
     #[error("Could not create temp store: {0}")]
     TempDirCreationFailed(String),
+    /// The storage key doesn't match the one the node's data was encrypted with.
+    #[error("The storage key doesn't match the one the node's data was encrypted with")]
+    WrongStorageKey,
+    /// The node's data is encrypted at rest, but no storage key was provided.
+    #[error("The node's data is encrypted at rest, but no storage key was provided")]
+    StorageKeyRequired,
+    /// Data encrypted at rest could not be decrypted.
+    #[error("Could not decrypt stored data")]
+    Decryption,
     /// Chunk Store Id could not be found
     #[error("Could not fetch StoreId")]
     NoStoreId,
@@ -133,6 +151,9 @@ pub enum Error {
     /// Network message error.
     #[error("Network message error:: {0}")]
     Message(#[from] sn_messaging::Error),
+    /// Reed-Solomon erasure coding error.
+    #[error("Erasure coding error: {0}")]
+    ErasureCoding(#[from] reed_solomon_erasure::Error),
     /// PickleDb error.
     #[error("PickleDb error:: {0}")]
     PickleDb(#[from] pickledb::error::Error),
@@ -175,6 +196,12 @@ pub enum Error {
     /// Configuration error.
     #[error("Configuration error: {0}")]
     Configuration(String),
+    /// Root dir was written by a newer version of the node.
+    #[error("Root dir is at schema version {0}, newer than the supported version {1}")]
+    UnsupportedSchemaVersion(u32, u32),
+    /// Archive of a node's data is invalid or doesn't match the node.
+    #[error("Archive error: {0}")]
+    Archive(String),
     /// Failed to send message to connection.
     #[error("Failed to send message to connection: {{0.0}}")]
     UnableToSend(MsgType),
@@ -188,7 +215,10 @@ pub(crate) fn convert_to_error_message(error: Error) -> sn_messaging::client::Er
         Error::InvalidSignedTransfer(_) => ErrorMessage::InvalidSignature,
         Error::TransferAlreadyRegistered => ErrorMessage::TransactionIdExists,
         Error::NoSuchChunk(address) => ErrorMessage::DataNotFound(address),
+        Error::CorruptChunk(address) => ErrorMessage::DataNotFound(address),
+        Error::ReadTimedOut(address) => ErrorMessage::DataNotFound(address),
         Error::NotEnoughSpace => ErrorMessage::NotEnoughSpace,
+        Error::NotEnoughSpaceFor(_) => ErrorMessage::NotEnoughSpace,
         Error::TempDirCreationFailed(_) => ErrorMessage::FailedToWriteFile,
         Error::DataExists => ErrorMessage::DataExists,
         Error::NetworkData(error) => convert_dt_error_to_error_message(error),
@@ -12,11 +12,12 @@ use crate::{
     node_ops::{MsgType, NodeDuty, OutgoingMsg},
     Error,
 };
-use log::debug;
+use log::{debug, warn};
 use sn_messaging::{
     node::{
-        NodeCmd, NodeDataQueryResponse, NodeMsg, NodeQuery, NodeQueryResponse, NodeRewardQuery,
-        NodeSystemCmd, NodeSystemQuery, NodeTransferCmd, NodeTransferQuery,
+        NodeCmd, NodeCmdError, NodeDataError, NodeDataQueryResponse, NodeMsg, NodeQuery,
+        NodeQueryResponse, NodeRewardQuery, NodeSystemCmd, NodeSystemQuery,
+        NodeSystemQueryResponse, NodeTransferCmd, NodeTransferQuery,
     },
     Aggregation, DstLocation, MessageId, SrcLocation,
 };
@@ -172,6 +173,33 @@ fn match_node_msg(msg: NodeMsg, origin: SrcLocation) -> NodeDuty {
         } => NodeDuty::ReadChunk {
             read: query,
             msg_id: id,
+            origin,
+        },
+        // Storage challenge from the Elders, see `chunks::storage_proof`
+        NodeMsg::NodeQuery {
+            query: NodeQuery::System(NodeSystemQuery::GetChunk(address)),
+            id,
+            ..
+        } => NodeDuty::ProveChunkStorage {
+            address,
+            msg_id: id,
+            origin,
+        },
+        // A holder found its copy of a chunk corrupt
+        NodeMsg::NodeCmdError {
+            error: NodeCmdError::Data(NodeDataError::ChunkReplication { address, .. }),
+            id,
+            ..
+        } => match origin {
+            SrcLocation::Node(holder) => NodeDuty::RepairChunk {
+                address,
+                holder,
+                msg_id: id,
+            },
+            _ => {
+                warn!("Ignoring repair request of {:?} from {:?}", address, origin);
+                NodeDuty::NoOp
+            }
         },
         NodeMsg::NodeCmd {
             cmd: NodeCmd::Chunks {
@@ -225,6 +253,16 @@ fn match_node_msg(msg: NodeMsg, origin: SrcLocation) -> NodeDuty {
             correlation_id,
             src: origin.name(),
         },
+        // Proof of storage from a holder, rather than a chunk
+        NodeMsg::NodeQueryResponse {
+            response: NodeQueryResponse::System(NodeSystemQueryResponse::GetChunk(proof)),
+            correlation_id,
+            ..
+        } => NodeDuty::RecordStorageProof {
+            proof,
+            correlation_id,
+            src: origin.name(),
+        },
         _ => {
             let msg_id = msg.id();
             let error = convert_to_error_message(Error::InvalidMessage(
@@ -0,0 +1,57 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Garbage collection of what a node's chunk stores hold besides chunks, like files left over by
+//! interrupted writes, copied in by hand, or named after ids the store can't read.
+
+use crate::{
+    chunk_store::{
+        BlobChunkStore, Garbage, GcAction, MapChunkStore, RegisterChunkStore, SequenceChunkStore,
+        UsedSpace,
+    },
+    storage_key, Config, Result,
+};
+
+/// Goes through the chunk stores of the node configured in `config`, and deals with the entries
+/// which aren't chunks according to `action`. Returns what was found over all stores.
+pub async fn collect_garbage(config: &Config, action: GcAction) -> Result<Garbage> {
+    let root_dir = config.root_dir()?;
+    let store_config = {
+        let mut store_config = config.store_config();
+        store_config.storage_key = storage_key::load(&root_dir, config)?;
+        store_config
+    };
+    let used_space = UsedSpace::new(u64::MAX);
+
+    let mut garbage = Garbage::default();
+    garbage.merge(
+        BlobChunkStore::new(&root_dir, &used_space, &store_config)
+            .await?
+            .collect_garbage(action)
+            .await?,
+    );
+    garbage.merge(
+        MapChunkStore::new(&root_dir, &used_space, &store_config)
+            .await?
+            .collect_garbage(action)
+            .await?,
+    );
+    garbage.merge(
+        SequenceChunkStore::new(&root_dir, &used_space, &store_config)
+            .await?
+            .collect_garbage(action)
+            .await?,
+    );
+    garbage.merge(
+        RegisterChunkStore::new(&root_dir, &used_space, &store_config)
+            .await?
+            .collect_garbage(action)
+            .await?,
+    );
+    Ok(garbage)
+}
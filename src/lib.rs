@@ -29,6 +29,7 @@
 // For quick_error
 #![recursion_limit = "256"]
 
+mod archive;
 mod capacity;
 mod chaos;
 mod chunk_store;
@@ -36,11 +37,14 @@ mod chunks;
 mod config_handler;
 mod error;
 mod event_mapping;
+mod gc;
 mod metadata;
+mod migrations;
 mod network;
 mod node;
 mod node_ops;
 mod section_funds;
+mod storage_key;
 mod to_db_key;
 mod transfers;
 
@@ -50,7 +54,12 @@ pub mod state_db;
 pub mod utils;
 
 pub use crate::{
-    config_handler::{add_connection_info, set_connection_info, Config},
+    archive::{export, import},
+    chunk_store::{Garbage, GcAction, StorageBackendKind},
+    config_handler::{add_connection_info, set_connection_info, Command, Config},
     error::{Error, Result},
+    gc::collect_garbage,
+    metadata::{AdultStats, BlobStorageMode, LivenessPolicy, ReadStrategy, LATENCY_BUCKETS_MS},
+    migrations::{migrate, Migration},
     node::Node,
 };
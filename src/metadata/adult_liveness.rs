@@ -12,34 +12,106 @@ use itertools::Itertools;
 use sn_data_types::BlobAddress;
 use sn_messaging::{EndUser, MessageId};
 use sn_routing::XorName;
-use std::collections::hash_map::Entry;
+use std::{
+    cmp::Ordering,
+    collections::hash_map::Entry,
+    mem,
+    time::{Duration, Instant},
+};
 
-use crate::capacity::CHUNK_COPY_COUNT;
+use super::liveness_stats::{AdultStats, LivenessPolicy};
 
 const NEIGHBOUR_COUNT: usize = 2;
 const MIN_PENDING_OPS: usize = 10;
 const PENDING_OP_TOLERANCE_RATIO: f64 = 0.1;
 
+/// Time after which the targets of a read operation which haven't responded are given up on.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Who a chunk is being read for.
+#[derive(Clone, Copy, Debug)]
+pub enum ReadRequester {
+    /// A client, to which the chunk is returned.
+    Client(EndUser),
+    /// A holder which lost its copy, to which the chunk is replicated.
+    Holder(XorName),
+    /// The replica audit, which found the chunk short of copies and republishes it.
+    Republish(BlobAddress),
+}
+
+impl ReadRequester {
+    /// Origin of the chunk queries sent for the requester. The holders ignore it, so a requesting
+    /// holder, or the chunk itself, is used as such.
+    pub fn origin(&self) -> EndUser {
+        match self {
+            Self::Client(end_user) => *end_user,
+            Self::Holder(holder) => EndUser {
+                xorname: *holder,
+                socket_id: *holder,
+            },
+            Self::Republish(address) => EndUser {
+                xorname: *address.name(),
+                socket_id: *address.name(),
+            },
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct ReadOperation {
     address: BlobAddress,
-    origin: EndUser,
+    requester: ReadRequester,
     targets: BTreeSet<XorName>,
+    // Holders yet to be queried, if the read is hedged.
+    standby: BTreeSet<XorName>,
+    // When the holders on standby are to be queried, if the read is hedged.
+    hedge_at: Option<Instant>,
+    // Targets which responded they don't have the chunk, to be repaired once it's read.
+    missing: BTreeSet<XorName>,
     responded_with_success: bool,
+    started: Instant,
+}
+
+/// A read operation which timed out before all its targets responded.
+#[derive(Clone, Debug)]
+pub struct ExpiredRead {
+    pub msg_id: MessageId,
+    pub address: BlobAddress,
+    pub requester: ReadRequester,
+    /// Whether a target responded with the chunk before the operation timed out.
+    pub responded_with_success: bool,
+}
+
+/// A hedged read which is to be sent to the holders which were on standby.
+#[derive(Clone, Debug)]
+pub struct FanOut {
+    pub msg_id: MessageId,
+    pub address: BlobAddress,
+    pub requester: ReadRequester,
+    pub targets: BTreeSet<XorName>,
 }
 
 pub struct AdultLiveness {
     ops: HashMap<MessageId, ReadOperation>,
     pending_ops: HashMap<XorName, usize>,
+    // Responses which never came, by Adult. They count towards its pending ops, one being forgiven
+    // each time the Adult responds.
+    missed_responses: HashMap<XorName, usize>,
     closest_adults: HashMap<XorName, Vec<XorName>>,
+    // How each Adult has responded so far.
+    stats: HashMap<XorName, AdultStats>,
+    policy: LivenessPolicy,
 }
 
 impl AdultLiveness {
-    pub fn new() -> Self {
+    pub fn new(policy: LivenessPolicy) -> Self {
         Self {
             ops: HashMap::default(),
             pending_ops: HashMap::default(),
+            missed_responses: HashMap::default(),
             closest_adults: HashMap::default(),
+            stats: HashMap::default(),
+            policy,
         }
     }
 
@@ -49,15 +121,49 @@ impl AdultLiveness {
         &mut self,
         msg_id: MessageId,
         address: BlobAddress,
-        origin: EndUser,
+        requester: ReadRequester,
+        targets: BTreeSet<XorName>,
+    ) -> bool {
+        self.insert_read(msg_id, address, requester, targets, BTreeSet::new(), None)
+    }
+
+    // Inserts a new read operation sent to `first` only, the holders on `standby` being queried
+    // after `delay`, or as soon as `first` responds with an error.
+    // Returns false if the operation already existed.
+    pub fn new_hedged_read(
+        &mut self,
+        msg_id: MessageId,
+        address: BlobAddress,
+        requester: ReadRequester,
+        first: XorName,
+        standby: BTreeSet<XorName>,
+        delay: Duration,
+    ) -> bool {
+        let mut targets = BTreeSet::new();
+        let _ = targets.insert(first);
+        let hedge_at = Instant::now() + delay;
+        self.insert_read(msg_id, address, requester, targets, standby, Some(hedge_at))
+    }
+
+    fn insert_read(
+        &mut self,
+        msg_id: MessageId,
+        address: BlobAddress,
+        requester: ReadRequester,
         targets: BTreeSet<XorName>,
+        standby: BTreeSet<XorName>,
+        hedge_at: Option<Instant>,
     ) -> bool {
         let new_operation = if let Entry::Vacant(entry) = self.ops.entry(msg_id) {
             let _ = entry.insert(ReadOperation {
                 address,
-                origin,
+                requester,
                 targets: targets.clone(),
+                standby,
+                hedge_at,
+                missing: BTreeSet::new(),
                 responded_with_success: false,
+                started: Instant::now(),
             });
             true
         } else {
@@ -74,7 +180,9 @@ impl AdultLiveness {
         for name in old_members {
             if !current_members.contains(&name) {
                 let _ = self.pending_ops.remove(&name);
+                let _ = self.missed_responses.remove(&name);
                 let _ = self.closest_adults.remove(&name);
+                let _ = self.stats.remove(&name);
                 let message_ids = self.ops.keys().cloned().collect::<Vec<_>>();
                 // TODO(after T4): For write operations perhaps we need to write it to a different Adult
                 for msg_id in message_ids {
@@ -86,19 +194,19 @@ impl AdultLiveness {
     }
 
     pub fn remove_target(&mut self, msg_id: MessageId, name: &XorName) {
-        if let Some(count) = self.pending_ops.get_mut(name) {
-            let counter = *count;
-            if counter > 0 {
-                *count -= 1;
-            }
-        }
-        let complete = if let Some(operation) = self.ops.get_mut(&msg_id) {
+        // The op may have expired already, its pending count having been released then.
+        let (was_pending, complete) = if let Some(operation) = self.ops.get_mut(&msg_id) {
             let ReadOperation { targets, .. } = operation;
-            let _ = targets.remove(name);
-            targets.is_empty()
+            let was_pending = targets.remove(name);
+            (was_pending, targets.is_empty())
         } else {
-            true
+            (false, true)
         };
+        if was_pending {
+            if let Some(count) = self.pending_ops.get_mut(name) {
+                *count = count.saturating_sub(1);
+            }
+        }
         if complete {
             let _ = self.ops.remove(&msg_id);
         }
@@ -109,24 +217,159 @@ impl AdultLiveness {
         correlation_id: MessageId,
         src: &XorName,
         success: bool,
-    ) -> Option<(BlobAddress, EndUser)> {
-        self.remove_target(correlation_id, src);
-        let op = self.ops.get_mut(&correlation_id);
-        op.and_then(|op| {
-            let ReadOperation {
-                address,
-                origin,
-                targets,
-                responded_with_success,
-            } = op;
-
-            if targets.len() < CHUNK_COPY_COUNT && *responded_with_success {
+    ) -> Option<(BlobAddress, ReadRequester)> {
+        // The response is looked at before `src` is removed from the targets, as that completes
+        // the operation if `src` was the last of them.
+        let stats = &mut self.stats;
+        let response = self.ops.get_mut(&correlation_id).and_then(|op| {
+            if op.targets.contains(src) {
+                stats
+                    .entry(*src)
+                    .or_default()
+                    .record_response(success, op.started.elapsed());
+            }
+            // Errors to hedged reads are held back while another holder may still have the chunk.
+            let others_pending =
+                op.targets.iter().any(|target| target != src) || !op.standby.is_empty();
+            if op.responded_with_success || (!success && op.hedge_at.is_some() && others_pending) {
                 None
             } else {
-                *responded_with_success = success;
-                Some((*address, *origin))
+                op.responded_with_success = success;
+                Some((op.address, op.requester))
+            }
+        });
+        self.remove_target(correlation_id, src);
+        if let Entry::Occupied(mut entry) = self.missed_responses.entry(*src) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                let _ = entry.remove();
             }
-        })
+        }
+        response
+    }
+
+    /// Records that `holder`, a target of the read `msg_id`, doesn't have the chunk. Returns the
+    /// chunk's address if another target already responded with it, for the holder to be
+    /// repaired by a read of its own. Otherwise it's repaired once the chunk is read.
+    pub fn chunk_missing(&mut self, msg_id: MessageId, holder: &XorName) -> Option<BlobAddress> {
+        let op = self.ops.get_mut(&msg_id)?;
+        if !op.targets.contains(holder) {
+            return None;
+        }
+        if op.responded_with_success {
+            Some(op.address)
+        } else {
+            let _ = op.missing.insert(*holder);
+            None
+        }
+    }
+
+    /// Returns the targets of the read `msg_id` which don't have the chunk, for it to be
+    /// replicated to them, forgetting them.
+    pub fn take_missing(&mut self, msg_id: MessageId) -> BTreeSet<XorName> {
+        self.ops
+            .get_mut(&msg_id)
+            .map(|op| mem::take(&mut op.missing))
+            .unwrap_or_default()
+    }
+
+    /// Records that a chunk `holder` lost is being replicated back to it.
+    pub fn record_repair(&mut self, holder: &XorName) {
+        self.stats.entry(*holder).or_default().record_repair();
+    }
+
+    /// Records whether `adult` proved holding a chunk it was challenged for. A failed proof counts
+    /// as a missed response, until the Adult next responds to a read.
+    pub fn record_storage_proof(&mut self, adult: &XorName, valid: bool) {
+        self.stats.entry(*adult).or_default().record_proof(valid);
+        if !valid {
+            *self.missed_responses.entry(*adult).or_insert(0) += 1;
+        }
+    }
+
+    /// Queries the holders on standby for the hedged read `msg_id`, if it has any and none has
+    /// responded with the chunk yet.
+    pub fn fan_out(&mut self, msg_id: MessageId) -> Option<FanOut> {
+        let op = self.ops.get_mut(&msg_id)?;
+        if op.standby.is_empty() || op.responded_with_success {
+            return None;
+        }
+        let targets = mem::take(&mut op.standby);
+        op.targets.extend(targets.iter().copied());
+        let fan_out = FanOut {
+            msg_id,
+            address: op.address,
+            requester: op.requester,
+            targets,
+        };
+        self.increment_pending_op(&fan_out.targets);
+        Some(fan_out)
+    }
+
+    /// Queries the holders on standby for the hedged reads whose first holder hasn't responded
+    /// by `now`.
+    pub fn hedge_reads(&mut self, now: Instant) -> Vec<FanOut> {
+        let due_ids = self
+            .ops
+            .iter()
+            .filter(|(_, op)| {
+                !op.standby.is_empty() && matches!(op.hedge_at, Some(at) if at <= now)
+            })
+            .map(|(msg_id, _)| *msg_id)
+            .collect::<Vec<_>>();
+        due_ids
+            .into_iter()
+            .filter_map(|msg_id| self.fan_out(msg_id))
+            .collect()
+    }
+
+    /// Orders `holders` from the healthiest to the least healthy by score, the closest to `name`
+    /// first among equals. Adults yet to respond to any read are deemed healthy.
+    pub fn rank_holders(&self, holders: &BTreeSet<XorName>, name: &XorName) -> Vec<XorName> {
+        let score = |adult: &XorName| self.stats.get(adult).map(|stats| stats.score);
+        holders
+            .iter()
+            .sorted_by(|lhs, rhs| {
+                let (lhs_score, rhs_score) = (score(lhs).unwrap_or(1.0), score(rhs).unwrap_or(1.0));
+                rhs_score
+                    .partial_cmp(&lhs_score)
+                    .unwrap_or(Ordering::Equal)
+                    .then_with(|| name.cmp_distance(lhs, rhs))
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Removes the read operations started at least `READ_TIMEOUT` before `now`, counting a missed
+    /// response for each of their targets yet to respond, and returns them.
+    pub fn expire_reads(&mut self, now: Instant) -> Vec<ExpiredRead> {
+        let expired_ids = self
+            .ops
+            .iter()
+            .filter(|(_, op)| now.saturating_duration_since(op.started) >= READ_TIMEOUT)
+            .map(|(msg_id, _)| *msg_id)
+            .collect::<Vec<_>>();
+        let mut expired = vec![];
+        for msg_id in expired_ids {
+            let op = match self.ops.remove(&msg_id) {
+                Some(op) => op,
+                None => continue,
+            };
+            for target in &op.targets {
+                if let Some(count) = self.pending_ops.get_mut(target) {
+                    *count = count.saturating_sub(1);
+                }
+                *self.missed_responses.entry(*target).or_insert(0) += 1;
+                self.stats.entry(*target).or_default().record_miss();
+            }
+            expired.push(ExpiredRead {
+                msg_id,
+                address: op.address,
+                requester: op.requester,
+                responded_with_success: op.responded_with_success,
+            });
+        }
+        expired
     }
 
     fn increment_pending_op(&mut self, targets: &BTreeSet<XorName>) {
@@ -160,19 +403,52 @@ impl AdultLiveness {
         self.closest_adults.extend(closest_adults_collection);
     }
 
-    pub fn find_unresponsive_adults(&self) -> Vec<(XorName, usize)> {
+    /// Returns how each Adult has responded to the reads sent to it.
+    pub fn stats(&self) -> Vec<(XorName, AdultStats)> {
+        self.stats
+            .iter()
+            .map(|(adult, stats)| (*adult, stats.clone()))
+            .collect()
+    }
+
+    /// Returns the Adults which the policy deems unresponsive.
+    pub fn find_unresponsive_adults(&self) -> Vec<XorName> {
+        match self.policy {
+            LivenessPolicy::PendingOps => self.adults_with_most_pending_ops(),
+            LivenessPolicy::Score { min_score } => self.adults_below_score(min_score),
+            LivenessPolicy::Disabled => vec![],
+        }
+    }
+
+    fn adults_below_score(&self, min_score: u8) -> Vec<XorName> {
+        self.stats
+            .iter()
+            .filter(|(_, stats)| stats.is_below(min_score))
+            .map(|(adult, stats)| {
+                log::info!(
+                    "Score of {}: {:.2}, below the minimum of {}%",
+                    adult,
+                    stats.score,
+                    min_score
+                );
+                *adult
+            })
+            .collect()
+    }
+
+    fn adults_with_most_pending_ops(&self) -> Vec<XorName> {
         let mut unresponsive_adults = Vec::new();
         for (adult, neighbours) in &self.closest_adults {
             if let Some(max_pending_by_neighbours) = neighbours
                 .iter()
-                .map(|neighbour| self.pending_ops.get(neighbour).unwrap_or(&0))
+                .map(|neighbour| self.unanswered_ops(neighbour))
                 .max()
             {
-                let adult_pending_ops = *self.pending_ops.get(adult).unwrap_or(&0);
+                let adult_pending_ops = self.unanswered_ops(adult);
                 if adult_pending_ops > MIN_PENDING_OPS
-                    && max_pending_by_neighbours > &MIN_PENDING_OPS
+                    && max_pending_by_neighbours > MIN_PENDING_OPS
                     && adult_pending_ops as f64 * PENDING_OP_TOLERANCE_RATIO
-                        > *max_pending_by_neighbours as f64
+                        > max_pending_by_neighbours as f64
                 {
                     log::info!(
                         "Pending ops for {}: {} Neighbour max: {}",
@@ -180,10 +456,185 @@ impl AdultLiveness {
                         adult_pending_ops,
                         max_pending_by_neighbours
                     );
-                    unresponsive_adults.push((*adult, adult_pending_ops));
+                    unresponsive_adults.push(*adult);
                 }
             }
         }
         unresponsive_adults
     }
+
+    // Ops the Adult is yet to respond to, or never did.
+    fn unanswered_ops(&self, adult: &XorName) -> usize {
+        self.pending_ops.get(adult).copied().unwrap_or(0)
+            + self.missed_responses.get(adult).copied().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::btree_set;
+    use sn_data_types::PublicBlob;
+
+    #[test]
+    fn reads_expire_and_count_as_missed_responses() {
+        let mut liveness = AdultLiveness::new(LivenessPolicy::default());
+        let (a, b) = (XorName::random(), XorName::random());
+        let address = *PublicBlob::new(vec![1; 10]).address();
+        let requester = ReadRequester::Client(EndUser {
+            xorname: a,
+            socket_id: a,
+        });
+        let msg_id = MessageId::new();
+        assert!(liveness.new_read(msg_id, address, requester, btree_set!(a, b)));
+        let start = Instant::now();
+
+        assert!(liveness.expire_reads(start).is_empty());
+        let expired = liveness.expire_reads(start + READ_TIMEOUT);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].msg_id, msg_id);
+        assert!(!expired[0].responded_with_success);
+        assert!(liveness.ops.is_empty());
+        assert_eq!(liveness.unanswered_ops(&a), 1);
+        assert_eq!(liveness.unanswered_ops(&b), 1);
+
+        // A late response is too late for the requester, but forgives the missed one.
+        assert!(liveness
+            .record_adult_read_liveness(msg_id, &a, true)
+            .is_none());
+        assert_eq!(liveness.unanswered_ops(&a), 0);
+        assert_eq!(liveness.unanswered_ops(&b), 1);
+    }
+
+    #[test]
+    fn adults_missing_reads_fall_below_the_minimum_score() {
+        let mut liveness = AdultLiveness::new(LivenessPolicy::Score { min_score: 50 });
+        let (good, bad) = (XorName::random(), XorName::random());
+        let address = *PublicBlob::new(vec![1; 10]).address();
+        let requester = ReadRequester::Holder(good);
+        for _ in 0..20 {
+            let msg_id = MessageId::new();
+            let _ = liveness.new_read(msg_id, address, requester, btree_set!(good, bad));
+            let _ = liveness.record_adult_read_liveness(msg_id, &good, true);
+            let _ = liveness.expire_reads(Instant::now() + READ_TIMEOUT);
+        }
+        assert_eq!(liveness.find_unresponsive_adults(), vec![bad]);
+
+        let stats: HashMap<_, _> = liveness.stats().into_iter().collect();
+        assert_eq!(stats[&good].successes, 20);
+        assert_eq!(stats[&bad].misses, 20);
+    }
+
+    #[test]
+    fn adults_failing_storage_proofs_are_unresponsive() {
+        let mut liveness = AdultLiveness::new(LivenessPolicy::Score { min_score: 50 });
+        let (honest, cheat) = (XorName::random(), XorName::random());
+        let address = *PublicBlob::new(vec![1; 10]).address();
+        for _ in 0..20 {
+            let msg_id = MessageId::new();
+            let targets = btree_set!(honest, cheat);
+            let _ = liveness.new_read(msg_id, address, ReadRequester::Holder(honest), targets);
+            let _ = liveness.record_adult_read_liveness(msg_id, &honest, true);
+            let _ = liveness.record_adult_read_liveness(msg_id, &cheat, true);
+            liveness.record_storage_proof(&honest, true);
+            liveness.record_storage_proof(&cheat, false);
+        }
+        assert_eq!(liveness.find_unresponsive_adults(), vec![cheat]);
+        assert_eq!(
+            liveness.stats.get(&cheat).map(|stats| stats.failed_proofs),
+            Some(20)
+        );
+    }
+
+    #[test]
+    fn holders_missing_the_chunk_are_repaired() {
+        let mut liveness = AdultLiveness::new(LivenessPolicy::default());
+        let (lost, early, late) = (XorName::random(), XorName::random(), XorName::random());
+        let address = *PublicBlob::new(vec![1; 10]).address();
+        let requester = ReadRequester::Holder(early);
+        let msg_id = MessageId::new();
+        assert!(liveness.new_read(msg_id, address, requester, btree_set!(lost, early, late)));
+
+        // Missing before the chunk was read, the holder is repaired with it.
+        assert_eq!(liveness.chunk_missing(msg_id, &lost), None);
+        let _ = liveness.record_adult_read_liveness(msg_id, &lost, false);
+        assert_eq!(liveness.take_missing(msg_id), btree_set!(lost));
+        let _ = liveness.record_adult_read_liveness(msg_id, &early, true);
+        assert!(liveness.take_missing(msg_id).is_empty());
+
+        // Missing after, it's repaired by a read of its own.
+        assert_eq!(liveness.chunk_missing(msg_id, &late), Some(address));
+        assert_eq!(liveness.chunk_missing(msg_id, &XorName::random()), None);
+
+        liveness.record_repair(&lost);
+        let stats: HashMap<_, _> = liveness.stats().into_iter().collect();
+        assert_eq!(stats[&lost].repairs, 1);
+        assert!(stats[&lost].score < stats[&early].score);
+    }
+
+    #[test]
+    fn hedged_reads_fan_out_after_the_delay_or_an_error() {
+        let mut liveness = AdultLiveness::new(LivenessPolicy::default());
+        let (first, second, third) = (XorName::random(), XorName::random(), XorName::random());
+        let address = *PublicBlob::new(vec![1; 10]).address();
+        let requester = ReadRequester::Holder(first);
+        let delay = Duration::from_secs(1);
+
+        // The first holder doesn't respond, so the others are queried after the delay.
+        let silent = MessageId::new();
+        assert!(liveness.new_hedged_read(
+            silent,
+            address,
+            requester,
+            first,
+            btree_set!(second, third),
+            delay
+        ));
+        let start = Instant::now();
+        assert!(liveness.hedge_reads(start).is_empty());
+        let fan_outs = liveness.hedge_reads(start + delay);
+        assert_eq!(fan_outs.len(), 1);
+        assert_eq!(fan_outs[0].targets, btree_set!(second, third));
+        assert!(liveness
+            .record_adult_read_liveness(silent, &second, true)
+            .is_some());
+        assert!(liveness
+            .record_adult_read_liveness(silent, &third, true)
+            .is_none());
+        let _ = liveness.expire_reads(start + READ_TIMEOUT);
+        assert_eq!(liveness.unanswered_ops(&first), 1);
+
+        // The first holder fails, so the others are queried straight away and its error held back.
+        let failed = MessageId::new();
+        assert!(liveness.new_hedged_read(
+            failed,
+            address,
+            requester,
+            first,
+            btree_set!(second, third),
+            delay
+        ));
+        assert!(liveness.fan_out(failed).is_some());
+        assert!(liveness
+            .record_adult_read_liveness(failed, &first, false)
+            .is_none());
+        assert!(liveness.fan_out(failed).is_none());
+
+        // The first holder responds with the chunk, so the others are never queried.
+        let answered = MessageId::new();
+        assert!(liveness.new_hedged_read(
+            answered,
+            address,
+            requester,
+            second,
+            btree_set!(first, third),
+            delay
+        ));
+        assert!(liveness
+            .record_adult_read_liveness(answered, &second, true)
+            .is_some());
+        assert!(liveness.hedge_reads(start + READ_TIMEOUT).is_empty());
+        assert!(!liveness.ops.contains_key(&answered));
+        assert_eq!(liveness.unanswered_ops(&third), 1);
+    }
 }
@@ -28,6 +28,11 @@ impl AdultReader {
         self.network.our_prefix().await
     }
 
+    /// The Adults of our section
+    pub async fn our_adults(&self) -> BTreeSet<XorName> {
+        self.network.our_adults().await
+    }
+
     /// Dynamic state
     pub async fn non_full_adults_closest_to(
         &self,
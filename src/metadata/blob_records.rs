@@ -9,41 +9,96 @@
 use crate::{
     btree_set,
     capacity::{Capacity, CHUNK_COPY_COUNT},
+    chunks::storage_proof::{
+        existence_check, existence_check_id, is_existence_check, StorageChallenge,
+    },
     error::convert_to_error_message,
     node_ops::{NodeDuties, NodeDuty},
     Error, Result,
 };
-use log::{info, warn};
-use sn_data_types::{Blob, BlobAddress, PublicKey};
+use log::{error, info, warn};
+use sn_data_types::{Blob, BlobAddress, DataAddress, Map, MapAddress, PublicKey};
 use sn_messaging::{
-    client::{BlobDataExchange, BlobRead, BlobWrite, ClientSigned, CmdError, QueryResponse},
+    client::{
+        BlobDataExchange, BlobRead, BlobWrite, ClientSigned, CmdError, Error as ErrorMessage,
+        QueryResponse,
+    },
     node::{NodeCmd, NodeMsg, NodeQuery, NodeSystemCmd},
     Aggregation, EndUser, MessageId,
 };
 use sn_routing::Prefix;
 
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet},
     fmt::{self, Display, Formatter},
+    iter,
+    path::Path,
+    time::Instant,
 };
 use xor_name::XorName;
 
 use super::{
-    adult_liveness::AdultLiveness, build_client_error_response, build_client_query_response,
+    adult_liveness::{AdultLiveness, ReadRequester, READ_TIMEOUT},
+    blob_shards::{
+        encode, is_shard, BlobStorageMode, ShardIndex, ShardManifest, ShardRead, ShardReadPurpose,
+        ShardRepair, SHARD_REPAIR_RETRY_INTERVAL,
+    },
+    build_client_error_response, build_client_query_response,
+    full_adult_leases::FullAdultLeases,
+    handover::{self, AUDIT_TAG, MANIFEST_TAG},
+    liveness_stats::{AdultStats, LivenessPolicy},
+    read_strategy::ReadStrategy,
+    replica_audit::{CheckOutcome, ReplicaAudit},
+    settled_adults::SettledAdults,
+    storage_challenges::{ProofOutcome, StorageChallenges},
 };
 
+const SHARD_INDEX_FILENAME: &str = "blob_shards.db";
+const REPLICA_AUDIT_FILENAME: &str = "blob_audits.redb";
+
 /// Operations over the data type Blob.
 pub(super) struct BlobRecords {
     capacity: Capacity,
+    full_adult_leases: FullAdultLeases,
     adult_liveness: AdultLiveness,
+    read_strategy: ReadStrategy,
+    replica_audit: ReplicaAudit,
+    // Who of the holders of a chunk are only challenged once they had time to receive it.
+    settled_adults: SettledAdults,
+    storage_challenges: StorageChallenges,
+    storage_mode: BlobStorageMode,
+    shard_index: ShardIndex,
+    shard_reads: BTreeMap<MessageId, ShardRead>,
+    // Erasure-coded blobs whose shards were lost with some of their holders.
+    shard_repairs: BTreeMap<BlobAddress, ShardRepair>,
 }
 
 impl BlobRecords {
-    pub(super) fn new(capacity: Capacity) -> Self {
-        Self {
+    pub(super) fn new(
+        path: &Path,
+        capacity: Capacity,
+        liveness_policy: LivenessPolicy,
+        read_strategy: ReadStrategy,
+        storage_mode: BlobStorageMode,
+    ) -> Result<Self> {
+        Ok(Self {
             capacity,
-            adult_liveness: AdultLiveness::new(),
-        }
+            full_adult_leases: FullAdultLeases::default(),
+            adult_liveness: AdultLiveness::new(liveness_policy),
+            read_strategy,
+            replica_audit: ReplicaAudit::open(&path.join(REPLICA_AUDIT_FILENAME))?,
+            settled_adults: SettledAdults::default(),
+            storage_challenges: StorageChallenges::default(),
+            storage_mode,
+            shard_index: ShardIndex::open(&path.join(SHARD_INDEX_FILENAME))?,
+            shard_reads: BTreeMap::new(),
+            shard_repairs: BTreeMap::new(),
+        })
+    }
+
+    /// How each Adult has responded to the chunk reads sent to it.
+    pub fn adult_stats(&self) -> Vec<(XorName, AdultStats)> {
+        self.adult_liveness.stats()
     }
 
     pub async fn get_data_of(&self, prefix: Prefix) -> BlobDataExchange {
@@ -52,9 +107,49 @@ impl BlobRecords {
         BlobDataExchange { full_adults }
     }
 
-    pub async fn update(&self, blob_data: BlobDataExchange) {
+    /// The manifests of the erasure-coded blobs within `prefix`, and the blobs registered with
+    /// the replica audit, to hand over along the Maps.
+    pub fn handover_maps_of(&self, prefix: Prefix) -> Result<BTreeMap<MapAddress, Map>> {
+        let mut maps = self.shard_index.export(&prefix)?;
+        for page in self.replica_audit.export(&prefix)? {
+            // Named after their first blob, for each page to be a Map of its own.
+            if let Some(first) = page.first() {
+                let map = handover::to_map(*first.name(), AUDIT_TAG, &page)?;
+                let _ = maps.insert(*map.address(), map);
+            }
+        }
+        Ok(maps)
+    }
+
+    pub async fn update(
+        &mut self,
+        blob_data: BlobDataExchange,
+        handover_maps: impl IntoIterator<Item = Map>,
+    ) {
         let BlobDataExchange { full_adults } = blob_data;
-        self.capacity.insert_full_adults(full_adults).await
+        // Their leases start over with us, until they next report being full.
+        let now = Instant::now();
+        for adult in &full_adults {
+            let _ = self.full_adult_leases.renew(*adult, now);
+        }
+        self.capacity.insert_full_adults(full_adults).await;
+
+        let mut manifests = vec![];
+        for map in handover_maps {
+            match map.address().tag() {
+                MANIFEST_TAG => manifests.push(map),
+                AUDIT_TAG => match handover::from_map::<Vec<BlobAddress>>(&map, AUDIT_TAG) {
+                    Some(page) => {
+                        if let Err(err) = self.replica_audit.add(page) {
+                            warn!("Could not register the blobs handed over: {}", err);
+                        }
+                    }
+                    None => warn!("{:?} doesn't carry blobs to audit", map.address()),
+                },
+                _ => warn!("{:?} doesn't carry a record", map.address()),
+            }
+        }
+        self.shard_index.import(manifests)
     }
 
     /// Registered holders not present in provided list of members
@@ -62,6 +157,23 @@ impl BlobRecords {
     pub async fn retain_members_only(&mut self, members: BTreeSet<XorName>) -> Result<()> {
         // full adults
         self.capacity.retain_members_only(&members).await;
+        self.full_adult_leases.retain_members_only(&members);
+
+        // regenerate the shards lost with absent holders
+        for manifest in self.shard_index.manifests() {
+            let lost = manifest
+                .holders()
+                .difference(&members)
+                .copied()
+                .collect::<BTreeSet<_>>();
+            if !lost.is_empty() {
+                self.shard_repairs
+                    .entry(manifest.address)
+                    .or_default()
+                    .lost
+                    .extend(lost);
+            }
+        }
 
         // stop tracking liveness of absent holders
         self.adult_liveness.retain_members_only(members);
@@ -75,7 +187,7 @@ impl BlobRecords {
         msg_id: MessageId,
         client_signed: ClientSigned,
         origin: EndUser,
-    ) -> Result<NodeDuty> {
+    ) -> Result<NodeDuties> {
         use BlobWrite::*;
         match write {
             New(data) => self.store(data, msg_id, client_signed, origin).await,
@@ -83,20 +195,25 @@ impl BlobRecords {
         }
     }
 
-    /// Adds a given node to the list of full nodes.
-    pub async fn increase_full_node_count(&mut self, node_id: PublicKey) {
+    /// Adds a given node to the list of full nodes, or renews its lease if it's there already.
+    /// Returns whether it wasn't counted as full already.
+    pub async fn increase_full_node_count(&mut self, node_id: PublicKey) -> bool {
+        let node_name = XorName::from(node_id);
+        if !self.full_adult_leases.renew(node_name, Instant::now()) {
+            return false;
+        }
         info!(
             "No. of full Adults: {:?}",
             self.capacity.full_adults_count().await
         );
         info!("Increasing full Adults count");
         self.capacity
-            .insert_full_adults(btree_set!(XorName::from(node_id)))
+            .insert_full_adults(btree_set!(node_name))
             .await;
+        true
     }
 
     /// Removes a given node from the list of full nodes.
-    #[allow(unused)] // TODO: Remove node from full list at 50% ?
     async fn decrease_full_adults_count_if_present(&mut self, node_name: XorName) {
         info!(
             "No. of Full Nodes: {:?}",
@@ -108,6 +225,14 @@ impl BlobRecords {
             .await;
     }
 
+    /// Un-flags the full Adults which stopped reporting being full, having freed up space.
+    async fn expire_full_adults(&mut self, now: Instant) {
+        for adult in self.full_adult_leases.expire(now) {
+            info!("{} no longer reports being full", adult);
+            self.decrease_full_adults_count_if_present(adult).await;
+        }
+    }
+
     async fn send_chunks_to_adults(
         &mut self,
         data: Blob,
@@ -151,13 +276,94 @@ impl BlobRecords {
         msg_id: MessageId,
         client_signed: ClientSigned,
         origin: EndUser,
-    ) -> Result<NodeDuty> {
+    ) -> Result<NodeDuties> {
         if let Err(error) = validate_data_owner(&data, &client_signed.public_key) {
-            return self.send_error(error, msg_id, origin).await;
+            return Ok(vec![self.send_error(error, msg_id, origin).await?]);
+        }
+        if is_shard(&data) {
+            let error = Error::InvalidOperation("Blobs can't start like shards do".to_string());
+            return Ok(vec![self.send_error(error, msg_id, origin).await?]);
         }
+        if let BlobStorageMode::ErasureCoded {
+            data_shards,
+            parity_shards,
+        } = self.storage_mode
+        {
+            return self
+                .send_shards_to_adults(
+                    data,
+                    data_shards,
+                    parity_shards,
+                    msg_id,
+                    client_signed,
+                    origin,
+                )
+                .await;
+        }
+        self.register(*data.address());
 
-        self.send_chunks_to_adults(data, msg_id, client_signed, origin)
-            .await
+        Ok(vec![
+            self.send_chunks_to_adults(data, msg_id, client_signed, origin)
+                .await?,
+        ])
+    }
+
+    // Splits the blob into shards, each stored on its own Adult, and keeps their manifest.
+    async fn send_shards_to_adults(
+        &mut self,
+        data: Blob,
+        data_shards: u8,
+        parity_shards: u8,
+        msg_id: MessageId,
+        client_signed: ClientSigned,
+        origin: EndUser,
+    ) -> Result<NodeDuties> {
+        // Storing a blob again is a no-op, as it is for replicated blobs.
+        if self.shard_index.get(data.address()).is_some() {
+            info!("{:?} is already stored as shards", data.address());
+            return Ok(vec![]);
+        }
+        let count = usize::from(data_shards) + usize::from(parity_shards);
+        let holders = self
+            .capacity
+            .get_shard_holder_adults(data.name(), count)
+            .await;
+
+        info!("Storing {} shards of the data", holders.len());
+
+        if holders.len() < count {
+            return Ok(vec![
+                self.send_error(
+                    Error::NoAdults(self.capacity.our_prefix().await),
+                    msg_id,
+                    origin,
+                )
+                .await?,
+            ]);
+        }
+
+        let (manifest, shards) = ShardManifest::new(&data, data_shards, parity_shards, &holders)?;
+        self.shard_index.insert(&manifest)?;
+        self.register(manifest.address);
+
+        let mut duties = vec![];
+        for ((address, holder), shard) in manifest.shards.iter().zip(shards) {
+            // Each shard is a write of its own, the same for all Elders for it to be aggregated.
+            let id = MessageId::from_content(&(msg_id, address))?;
+            duties.push(NodeDuty::SendToNodes {
+                targets: btree_set!(*holder),
+                msg: NodeMsg::NodeCmd {
+                    cmd: NodeCmd::Chunks {
+                        cmd: BlobWrite::New(shard),
+                        client_signed: client_signed.clone(),
+                        origin,
+                    },
+                    id,
+                },
+                aggregation: Aggregation::AtDestination,
+            });
+        }
+        Ok(duties)
     }
 
     pub async fn record_adult_read_liveness(
@@ -172,36 +378,645 @@ impl BlobRecords {
                 response
             )));
         }
+        if self.shard_reads.contains_key(&correlation_id) {
+            let shard = match response {
+                QueryResponse::GetBlob(Ok(shard)) => Some(shard),
+                _ => None,
+            };
+            return Ok(self.record_shard(correlation_id, &src, shard).await);
+        }
         let mut duties = vec![];
-        if let Some((_address, end_user)) = self.adult_liveness.record_adult_read_liveness(
+        // A hedged read the holder failed is sent to the other holders straight away.
+        if !response.is_success() {
+            if let Some(fan_out) = self.adult_liveness.fan_out(correlation_id) {
+                info!(
+                    "{} failed to read {:?}, querying the remaining holders",
+                    src, fan_out.address
+                );
+                duties.push(query_holders(
+                    fan_out.msg_id,
+                    fan_out.address,
+                    fan_out.requester.origin(),
+                    fan_out.targets,
+                ));
+            }
+        }
+        // A holder without the chunk has lost it, unless it couldn't store it for being full.
+        let not_found = matches!(
+            response,
+            QueryResponse::GetBlob(Err(ErrorMessage::DataNotFound(_)))
+        );
+        if not_found && !self.capacity.is_full(&src).await {
+            if let Some(address) = self.adult_liveness.chunk_missing(correlation_id, &src) {
+                duties.push(self.repair(address, src, MessageId::new()).await?);
+            }
+        }
+        if let QueryResponse::GetBlob(Ok(data)) = &response {
+            let missing = self.adult_liveness.take_missing(correlation_id);
+            if !missing.is_empty() {
+                duties.push(self.replicate_chunk(data.clone(), missing));
+            }
+        }
+        match self.adult_liveness.record_adult_read_liveness(
             correlation_id,
             &src,
             response.is_success(),
         ) {
-            // If a full adult responds with error. Drop the response
-            if !response.is_success() && self.capacity.is_full(&src).await {
-                // We've already responded already with a success
-                // so do nothing
-            } else {
-                duties.push(NodeDuty::Send(build_client_query_response(
-                    response,
-                    correlation_id,
-                    end_user,
-                )));
+            Some((_address, ReadRequester::Client(end_user))) => {
+                if let QueryResponse::GetBlob(Ok(data)) = &response {
+                    self.register(*data.address());
+                    duties.extend(self.challenge_holders(data, &src).await);
+                }
+                // If a full adult responds with error. Drop the response
+                if !response.is_success() && self.capacity.is_full(&src).await {
+                    // We've already responded already with a success
+                    // so do nothing
+                } else {
+                    duties.push(NodeDuty::Send(build_client_query_response(
+                        response,
+                        correlation_id,
+                        end_user,
+                    )));
+                }
+            }
+            Some((_address, ReadRequester::Holder(holder))) => {
+                if let QueryResponse::GetBlob(Ok(data)) = response {
+                    duties.push(self.replicate_chunk(data, btree_set!(holder)));
+                }
+            }
+            Some((_address, ReadRequester::Republish(_))) => {
+                if let QueryResponse::GetBlob(Ok(data)) = response {
+                    duties.push(self.republish_chunk(data).await?);
+                }
             }
+            None => (),
         }
-        let mut unresponsive_adults = Vec::new();
-        for (name, count) in self.adult_liveness.find_unresponsive_adults() {
+        duties.extend(self.propose_unresponsive_adults());
+        Ok(duties)
+    }
+
+    /// Sends the hedged chunk reads the first holder didn't respond to in time to the remaining
+    /// holders, and gives up on the reads which timed out, responding with an error to the clients
+    /// yet to get the chunk. The Adults which didn't respond are counted as having missed a
+    /// response, and those which didn't respond to storage challenges in time as failing them.
+    /// Those which didn't respond to existence checks count as missing the chunk. Full Adults
+    /// which stopped reporting being full are un-flagged along the way.
+    pub async fn expire_reads(&mut self) -> NodeDuties {
+        let now = Instant::now();
+        let mut duties = self
+            .adult_liveness
+            .hedge_reads(now)
+            .into_iter()
+            .map(|fan_out| {
+                query_holders(
+                    fan_out.msg_id,
+                    fan_out.address,
+                    fan_out.requester.origin(),
+                    fan_out.targets,
+                )
+            })
+            .collect::<NodeDuties>();
+        for read in self.adult_liveness.expire_reads(now) {
+            if read.responded_with_success {
+                continue;
+            }
+            match read.requester {
+                ReadRequester::Client(end_user) => {
+                    warn!("Read {:?} of {:?} timed out", read.msg_id, read.address);
+                    let error = convert_to_error_message(Error::ReadTimedOut(DataAddress::Blob(
+                        read.address,
+                    )));
+                    duties.push(NodeDuty::Send(build_client_error_response(
+                        CmdError::Data(error),
+                        read.msg_id,
+                        end_user,
+                    )));
+                }
+                ReadRequester::Holder(holder) => warn!(
+                    "Timed out fetching {:?} to replicate back to {}",
+                    read.address, holder
+                ),
+                ReadRequester::Republish(_) => {
+                    warn!("Timed out fetching {:?} to republish it", read.address)
+                }
+            }
+        }
+        for outcome in self.replica_audit.expire(now) {
+            duties.extend(self.record_check_outcome(outcome).await);
+        }
+        for outcome in self.storage_challenges.expire(now) {
+            if !self.holds(&outcome.address, &outcome.holder).await {
+                continue;
+            }
             warn!(
-                "Adult {} has {} pending ops. It might be unresponsive",
-                name, count
+                "{} didn't respond to the storage challenge for {:?}",
+                outcome.holder, outcome.address
             );
-            unresponsive_adults.push(name);
+            self.adult_liveness
+                .record_storage_proof(&outcome.holder, outcome.proven);
+        }
+        self.expire_full_adults(now).await;
+        duties.extend(self.expire_shard_reads(now).await);
+        duties.extend(self.start_shard_repairs(now));
+        duties.extend(self.propose_unresponsive_adults());
+        duties
+    }
+
+    /// Checks the proof `src` responded to the storage challenge `correlation_id` with, repairing
+    /// its copy if it failed, and proposes the unresponsive Adults offline. The response to an
+    /// existence check is recorded instead, the chunk being republished if copies are missing.
+    pub async fn record_storage_proof(
+        &mut self,
+        correlation_id: MessageId,
+        proof: &Blob,
+        src: XorName,
+    ) -> NodeDuties {
+        if is_existence_check(&correlation_id) {
+            let held = !proof.value().is_empty();
+            return match self.replica_audit.record(correlation_id, &src, held) {
+                Some(outcome) => self
+                    .record_check_outcome(outcome)
+                    .await
+                    .into_iter()
+                    .collect(),
+                None => vec![],
+            };
         }
-        if !unresponsive_adults.is_empty() {
-            duties.push(NodeDuty::ProposeOffline(unresponsive_adults));
+        let outcome =
+            match self
+                .storage_challenges
+                .record_proof(correlation_id, &src, proof.value())
+            {
+                Some(outcome) => outcome,
+                None => return vec![],
+            };
+        let mut duties = vec![];
+        duties.extend(self.record_proof_outcome(outcome).await);
+        duties.extend(self.propose_unresponsive_adults());
+        duties
+    }
+
+    // Challenges the holders of `data` other than `responder`, which just read it, to prove they
+    // hold it too. Returns `None` if they are being challenged already, or there are none.
+    async fn challenge_holders(&mut self, data: &Blob, responder: &XorName) -> Option<NodeDuty> {
+        if self.storage_challenges.is_challenging(data.address()) {
+            return None;
+        }
+        let mut holders = self.settled_holders(data.address()).await;
+        let _ = holders.remove(responder);
+        self.challenge(StorageChallenge::new(data), holders)
+    }
+
+    // The holders of the chunk at `address` which had time to receive it, the chunks being
+    // replicated to their new holders by the Adults after churn. Only those are challenged, so
+    // that new holders which may not have the chunk yet don't fail the challenges.
+    async fn settled_holders(&mut self, address: &BlobAddress) -> BTreeSet<XorName> {
+        let now = Instant::now();
+        self.settled_adults
+            .update(self.capacity.our_adults().await, now);
+        let settled_adults = &self.settled_adults;
+        self.capacity
+            .get_chunk_holder_adults(address.name())
+            .await
+            .into_iter()
+            .filter(|holder| settled_adults.has_settled(holder, address.name(), now))
+            .collect()
+    }
+
+    // Whether `adult` is still a holder of the chunk at `address`. Those which stopped holding it
+    // while being challenged may have dropped it, so their failures aren't held against them.
+    async fn holds(&self, address: &BlobAddress, adult: &XorName) -> bool {
+        let holds = self
+            .capacity
+            .get_chunk_holder_adults(address.name())
+            .await
+            .contains(adult);
+        if !holds {
+            info!(
+                "{} no longer holds {:?}, ignoring its storage challenge",
+                adult, address
+            );
+        }
+        holds
+    }
+
+    // Sends `challenge` to `holders`, awaiting their proofs. Returns `None` if there are none.
+    fn challenge(
+        &mut self,
+        challenge: StorageChallenge,
+        holders: BTreeSet<XorName>,
+    ) -> Option<NodeDuty> {
+        if holders.is_empty() {
+            return None;
+        }
+        let duty = NodeDuty::SendToNodes {
+            msg: challenge.query(),
+            targets: holders.clone(),
+            aggregation: Aggregation::None,
+        };
+        self.storage_challenges.start(challenge, holders);
+        Some(duty)
+    }
+
+    // Records whether a holder proved holding a chunk, fetching the chunk from the other holders
+    // to replicate it back to a holder which failed, unless it's full.
+    async fn record_proof_outcome(&mut self, outcome: ProofOutcome) -> Option<NodeDuty> {
+        let ProofOutcome {
+            address,
+            holder,
+            proven,
+        } = outcome;
+        if !proven && !self.holds(&address, &holder).await {
+            return None;
+        }
+        self.adult_liveness.record_storage_proof(&holder, proven);
+        if proven {
+            return None;
+        }
+        warn!("{} failed to prove it holds {:?}", holder, address);
+        if self.capacity.is_full(&holder).await {
+            return None;
+        }
+        self.repair(address, holder, MessageId::new())
+            .await
+            .map_err(|err| error!("Error repairing {:?} on {}: {}", address, holder, err))
+            .ok()
+    }
+
+    /// Asks the holders of the next page of registered blobs whether they still have them, those
+    /// found short of copies being republished once all holders responded.
+    pub async fn audit_replicas(&mut self) -> NodeDuties {
+        let page = match self.replica_audit.next_page() {
+            Ok(page) => page,
+            Err(err) => {
+                error!("Could not read the blobs to audit: {}", err);
+                return vec![];
+            }
+        };
+        let mut duties = vec![];
+        for address in page {
+            if self.replica_audit.is_checking(&address) {
+                continue;
+            }
+            if let Some(manifest) = self.shard_index.get(&address) {
+                duties.extend(self.check_shards(manifest));
+                continue;
+            }
+            let holders = self.settled_holders(&address).await;
+            if holders.is_empty() {
+                continue;
+            }
+            let msg_id = existence_check_id();
+            duties.push(NodeDuty::SendToNodes {
+                msg: existence_check(address, msg_id),
+                targets: holders.clone(),
+                aggregation: Aggregation::None,
+            });
+            self.replica_audit.start(msg_id, address, holders);
+        }
+        duties
+    }
+
+    // Asks each holder of a shard of an erasure-coded blob whether it still has it, unless the
+    // blob is already due to have lost shards regenerated.
+    fn check_shards(&mut self, manifest: ShardManifest) -> NodeDuties {
+        if self.shard_repairs.contains_key(&manifest.address) {
+            return vec![];
+        }
+        let msg_id = existence_check_id();
+        let duties = manifest
+            .shards
+            .iter()
+            .map(|(shard, holder)| NodeDuty::SendToNodes {
+                msg: existence_check(*shard, msg_id),
+                targets: btree_set!(*holder),
+                aggregation: Aggregation::None,
+            })
+            .collect();
+        self.replica_audit
+            .start(msg_id, manifest.address, manifest.holders());
+        duties
+    }
+
+    // Republishes a blob which has fewer than `CHUNK_COPY_COUNT` copies left, the holders checked
+    // missing it, reading it from those which still have it. Holders which have since stopped
+    // holding it aren't expected to have it. The shards the holders of an erasure-coded blob are
+    // missing are regenerated instead.
+    async fn record_check_outcome(&mut self, outcome: CheckOutcome) -> Option<NodeDuty> {
+        let CheckOutcome {
+            address,
+            present,
+            missing,
+        } = outcome;
+        if let Some(manifest) = self.shard_index.get(&address) {
+            let lost = missing
+                .intersection(&manifest.holders())
+                .copied()
+                .collect::<BTreeSet<_>>();
+            if !lost.is_empty() {
+                warn!("The shards of {:?} on {:?} are missing", address, lost);
+                self.shard_repairs
+                    .entry(address)
+                    .or_default()
+                    .lost
+                    .extend(lost);
+            }
+            return None;
+        }
+        let holders = self.capacity.get_chunk_holder_adults(address.name()).await;
+        let missing = missing.intersection(&holders).count();
+        if missing == 0 || present.len() >= CHUNK_COPY_COUNT {
+            return None;
+        }
+        if present.is_empty() {
+            error!("None of the holders checked have {:?} anymore", address);
+            return None;
+        }
+        warn!(
+            "{:?} is down to {} copies out of {}, republishing it",
+            address,
+            present.len(),
+            CHUNK_COPY_COUNT
+        );
+        let requester = ReadRequester::Republish(address);
+        self.start_read(MessageId::new(), address, requester, present)
+    }
+
+    // Registers the blob at `address` with the replica audit.
+    fn register(&mut self, address: BlobAddress) {
+        if let Err(err) = self.replica_audit.add(iter::once(address)) {
+            warn!("Could not register {:?} to be audited: {}", address, err);
+        }
+    }
+
+    // Sends the chunk to holders which have lost it, recording the repair against them.
+    fn replicate_chunk(&mut self, data: Blob, holders: BTreeSet<XorName>) -> NodeDuty {
+        for holder in &holders {
+            info!("Repairing {:?} on {}", data.address(), holder);
+            self.adult_liveness.record_repair(holder);
+        }
+        NodeDuty::SendToNodes {
+            targets: holders,
+            msg: NodeMsg::NodeCmd {
+                cmd: NodeCmd::System(NodeSystemCmd::ReplicateChunk(data)),
+                id: MessageId::new(),
+            },
+            aggregation: Aggregation::None,
+        }
+    }
+
+    // Queries each of `holders` for its shard of the blob. Returns nothing if the read is already
+    // in progress.
+    fn read_shards(
+        &mut self,
+        msg_id: MessageId,
+        manifest: ShardManifest,
+        purpose: ShardReadPurpose,
+        holders: BTreeSet<XorName>,
+    ) -> NodeDuties {
+        if self.shard_reads.contains_key(&msg_id) {
+            info!(
+                "Operation with MessageId {:?} is already in progress",
+                msg_id
+            );
+            return vec![];
+        }
+        let origin = match &purpose {
+            ShardReadPurpose::Client(end_user) => *end_user,
+            ShardReadPurpose::Repair(_) => match holders.iter().next() {
+                Some(holder) => EndUser {
+                    xorname: *holder,
+                    socket_id: *holder,
+                },
+                None => return vec![],
+            },
+        };
+        let duties = manifest
+            .shards
+            .iter()
+            .filter(|(_, holder)| holders.contains(holder))
+            .map(|(address, holder)| query_holders(msg_id, *address, origin, btree_set!(*holder)))
+            .collect();
+        let _ = self
+            .shard_reads
+            .insert(msg_id, ShardRead::new(manifest, purpose, holders));
+        duties
+    }
+
+    async fn record_shard(
+        &mut self,
+        msg_id: MessageId,
+        src: &XorName,
+        shard: Option<Blob>,
+    ) -> NodeDuties {
+        let complete = match self.shard_reads.get_mut(&msg_id) {
+            Some(read) => {
+                read.record(src, shard);
+                read.is_complete()
+            }
+            None => false,
+        };
+        match self.shard_reads.remove(&msg_id) {
+            Some(read) if complete => self.complete_shard_read(msg_id, read).await,
+            Some(read) => {
+                let _ = self.shard_reads.insert(msg_id, read);
+                vec![]
+            }
+            None => vec![],
+        }
+    }
+
+    // Rebuilds the blob from the shards received, for the client or to regenerate lost shards.
+    async fn complete_shard_read(&mut self, msg_id: MessageId, read: ShardRead) -> NodeDuties {
+        let address = read.manifest.address;
+        let result = read.manifest.reconstruct(&read.received);
+        match (read.purpose, result) {
+            (ShardReadPurpose::Client(end_user), Ok(blob)) => {
+                vec![NodeDuty::Send(build_client_query_response(
+                    QueryResponse::GetBlob(Ok(blob)),
+                    msg_id,
+                    end_user,
+                ))]
+            }
+            (ShardReadPurpose::Client(end_user), Err(err)) => {
+                warn!("Could not rebuild {:?} from its shards: {}", address, err);
+                let error = if read.pending.is_empty() {
+                    Error::NoSuchChunk(DataAddress::Blob(address))
+                } else {
+                    Error::ReadTimedOut(DataAddress::Blob(address))
+                };
+                vec![NodeDuty::Send(build_client_error_response(
+                    CmdError::Data(convert_to_error_message(error)),
+                    msg_id,
+                    end_user,
+                ))]
+            }
+            (ShardReadPurpose::Repair(lost), Ok(blob)) => {
+                self.regenerate_shards(read.manifest, &blob, lost).await
+            }
+            (ShardReadPurpose::Repair(_), Err(err)) => {
+                error!(
+                    "Could not rebuild {:?} to repair its shards: {}",
+                    address, err
+                );
+                self.retry_shard_repair(&address);
+                vec![]
+            }
+        }
+    }
+
+    // Ends the shard reads which timed out, with the shards received so far.
+    async fn expire_shard_reads(&mut self, now: Instant) -> NodeDuties {
+        let expired_ids = self
+            .shard_reads
+            .iter()
+            .filter(|(_, read)| now.saturating_duration_since(read.started) >= READ_TIMEOUT)
+            .map(|(msg_id, _)| *msg_id)
+            .collect::<Vec<_>>();
+        let mut duties = vec![];
+        for msg_id in expired_ids {
+            if let Some(read) = self.shard_reads.remove(&msg_id) {
+                duties.extend(self.complete_shard_read(msg_id, read).await);
+            }
+        }
+        duties
+    }
+
+    // Reads the shards left of the blobs which lost some, unless already doing so, or waiting to
+    // retry after a failed attempt. The repairs are kept until all lost shards are regenerated.
+    fn start_shard_repairs(&mut self, now: Instant) -> NodeDuties {
+        let repairing = self
+            .shard_reads
+            .values()
+            .filter(|read| matches!(read.purpose, ShardReadPurpose::Repair(_)))
+            .map(|read| read.manifest.address)
+            .collect::<BTreeSet<_>>();
+        let due = self
+            .shard_repairs
+            .iter()
+            .filter(|(address, repair)| !repairing.contains(address) && repair.is_due(now))
+            .map(|(address, repair)| (*address, repair.lost.clone()))
+            .collect::<Vec<_>>();
+        let mut duties = vec![];
+        for (address, lost) in due {
+            let manifest = match self.shard_index.get(&address) {
+                Some(manifest) => manifest,
+                None => {
+                    let _ = self.shard_repairs.remove(&address);
+                    continue;
+                }
+            };
+            let holders = manifest.holders().difference(&lost).copied().collect();
+            info!(
+                "Regenerating the shards of {:?} lost with {:?}",
+                address, lost
+            );
+            let purpose = ShardReadPurpose::Repair(lost);
+            duties.extend(self.read_shards(MessageId::new(), manifest, purpose, holders));
+        }
+        duties
+    }
+
+    // Puts off the repair of the shards of the blob at `address` after a failed attempt.
+    fn retry_shard_repair(&mut self, address: &BlobAddress) {
+        if let Some(repair) = self.shard_repairs.get_mut(address) {
+            repair.retry_at = Some(Instant::now() + SHARD_REPAIR_RETRY_INTERVAL);
+        }
+    }
+
+    // Computes the shards `lost` holders had anew from the blob, and stores them on new holders,
+    // which replace them in the manifest. Those which couldn't be replaced stay to be repaired.
+    async fn regenerate_shards(
+        &mut self,
+        mut manifest: ShardManifest,
+        blob: &Blob,
+        lost: BTreeSet<XorName>,
+    ) -> NodeDuties {
+        // The blob was deleted while its shards were being read.
+        if !self.shard_repairs.contains_key(&manifest.address) {
+            return vec![];
+        }
+        let shards = match encode(blob, manifest.data_shards, manifest.parity_shards) {
+            Ok(shards) => shards,
+            Err(err) => {
+                error!("Error encoding {:?}: {}", manifest.address, err);
+                self.retry_shard_repair(&manifest.address);
+                return vec![];
+            }
+        };
+        let current = manifest.holders();
+        let mut candidates = self
+            .capacity
+            .get_shard_holder_adults(blob.name(), manifest.shards.len() + lost.len())
+            .await
+            .into_iter()
+            .filter(|adult| !current.contains(adult));
+        let mut duties = vec![];
+        let mut replaced = BTreeSet::new();
+        for ((_, holder), shard) in manifest.shards.iter_mut().zip(shards) {
+            if !lost.contains(holder) {
+                continue;
+            }
+            let new_holder = match candidates.next() {
+                Some(adult) => adult,
+                None => {
+                    warn!(
+                        "No Adults left to hold the shards of {:?}",
+                        manifest.address
+                    );
+                    break;
+                }
+            };
+            info!(
+                "Storing {:?} on {} instead of {}",
+                shard.address(),
+                new_holder,
+                holder
+            );
+            let _ = replaced.insert(*holder);
+            *holder = new_holder;
+            duties.push(NodeDuty::SendToNodes {
+                targets: btree_set!(new_holder),
+                msg: NodeMsg::NodeCmd {
+                    cmd: NodeCmd::System(NodeSystemCmd::ReplicateChunk(shard)),
+                    id: MessageId::new(),
+                },
+                aggregation: Aggregation::None,
+            });
+        }
+        if let Err(err) = self.shard_index.insert(&manifest) {
+            error!(
+                "Error updating the manifest of {:?}: {}",
+                manifest.address, err
+            );
+            self.retry_shard_repair(&manifest.address);
+            return vec![];
+        }
+        let done = match self.shard_repairs.get_mut(&manifest.address) {
+            Some(repair) => {
+                repair.lost.retain(|holder| !replaced.contains(holder));
+                repair.lost.is_empty()
+            }
+            None => true,
+        };
+        if done {
+            let _ = self.shard_repairs.remove(&manifest.address);
+        } else if replaced.len() < lost.len() {
+            self.retry_shard_repair(&manifest.address);
+        }
+        duties
+    }
+
+    fn propose_unresponsive_adults(&self) -> Option<NodeDuty> {
+        let unresponsive_adults = self.adult_liveness.find_unresponsive_adults();
+        for name in &unresponsive_adults {
+            warn!("Adult {} might be unresponsive", name);
+        }
+        if unresponsive_adults.is_empty() {
+            None
+        } else {
+            Some(NodeDuty::ProposeOffline(unresponsive_adults))
         }
-        Ok(duties)
     }
 
     async fn send_error(
@@ -224,8 +1039,16 @@ impl BlobRecords {
         msg_id: MessageId,
         client_signed: ClientSigned,
         origin: EndUser,
-    ) -> Result<NodeDuty> {
+    ) -> Result<NodeDuties> {
+        if let Some(manifest) = self.shard_index.get(&address) {
+            return self
+                .delete_shards(manifest, msg_id, client_signed, origin)
+                .await;
+        }
         let targets = self.capacity.get_chunk_holder_adults(address.name()).await;
+        if let Err(err) = self.replica_audit.remove(&address) {
+            warn!("Could not stop auditing {:?}: {}", address, err);
+        }
 
         let msg = NodeMsg::NodeCmd {
             cmd: NodeCmd::Chunks {
@@ -235,14 +1058,52 @@ impl BlobRecords {
             },
             id: msg_id,
         };
-        Ok(NodeDuty::SendToNodes {
+        Ok(vec![NodeDuty::SendToNodes {
             msg,
             targets,
             aggregation: Aggregation::AtDestination,
-        })
+        }])
+    }
+
+    // The manifest is only dropped for the owner, as the holders would keep the shards otherwise.
+    async fn delete_shards(
+        &mut self,
+        manifest: ShardManifest,
+        msg_id: MessageId,
+        client_signed: ClientSigned,
+        origin: EndUser,
+    ) -> Result<NodeDuties> {
+        if manifest.owner != Some(client_signed.public_key) {
+            let error = Error::InvalidOwner(client_signed.public_key);
+            return Ok(vec![self.send_error(error, msg_id, origin).await?]);
+        }
+        self.shard_index.remove(&manifest.address)?;
+        let _ = self.shard_repairs.remove(&manifest.address);
+        if let Err(err) = self.replica_audit.remove(&manifest.address) {
+            warn!("Could not stop auditing {:?}: {}", manifest.address, err);
+        }
+
+        Ok(manifest
+            .shards
+            .into_iter()
+            .map(|(address, holder)| NodeDuty::SendToNodes {
+                msg: NodeMsg::NodeCmd {
+                    cmd: NodeCmd::Chunks {
+                        cmd: BlobWrite::DeletePrivate(address),
+                        client_signed: client_signed.clone(),
+                        origin,
+                    },
+                    id: msg_id,
+                },
+                targets: btree_set!(holder),
+                aggregation: Aggregation::AtDestination,
+            })
+            .collect())
     }
 
     pub(super) async fn republish_chunk(&mut self, data: Blob) -> Result<NodeDuty> {
+        // Blobs stored before they were registered are registered as they get republished.
+        self.register(*data.address());
         let owner = data.owner();
         let target_holders = self.capacity.get_chunk_holder_adults(data.name()).await;
         // deterministic msg id for aggregation
@@ -265,14 +1126,65 @@ impl BlobRecords {
         })
     }
 
+    /// Fetches a chunk from its other holders, to replicate it back to `holder`, which reported
+    /// its copy corrupt. The report is ignored unless `holder` is a member Adult holding the chunk.
+    pub(super) async fn repair_corrupt(
+        &mut self,
+        address: BlobAddress,
+        holder: XorName,
+        msg_id: MessageId,
+    ) -> Result<NodeDuty> {
+        let holders = self.capacity.get_chunk_holder_adults(address.name()).await;
+        if !holders.contains(&holder) {
+            warn!(
+                "Ignoring the report of {:?} being corrupt from {}, which doesn't hold it",
+                address, holder
+            );
+            return Ok(NodeDuty::NoOp);
+        }
+        self.repair(address, holder, msg_id).await
+    }
+
+    /// Fetches a chunk from its other holders, to replicate it back to `holder`,
+    /// which has lost its copy.
+    async fn repair(
+        &mut self,
+        address: BlobAddress,
+        holder: XorName,
+        msg_id: MessageId,
+    ) -> Result<NodeDuty> {
+        let holders = self.capacity.get_chunk_holder_adults(address.name()).await;
+        let targets = match repair_sources(holders, &holder) {
+            Some(targets) => targets,
+            None => {
+                warn!(
+                    "Can't fetch {:?} for {} from other holders",
+                    address, holder
+                );
+                return Ok(NodeDuty::NoOp);
+            }
+        };
+
+        Ok(self
+            .start_read(msg_id, address, ReadRequester::Holder(holder), targets)
+            .unwrap_or(NodeDuty::NoOp))
+    }
+
     pub(super) async fn read(
         &mut self,
         read: &BlobRead,
         msg_id: MessageId,
         origin: EndUser,
-    ) -> Result<NodeDuty> {
+    ) -> Result<NodeDuties> {
         match read {
-            BlobRead::Get(address) => self.get(*address, msg_id, origin).await,
+            BlobRead::Get(address) => {
+                if let Some(manifest) = self.shard_index.get(address) {
+                    let holders = manifest.holders();
+                    let purpose = ShardReadPurpose::Client(origin);
+                    return Ok(self.read_shards(msg_id, manifest, purpose, holders));
+                }
+                Ok(vec![self.get(*address, msg_id, origin).await?])
+            }
         }
     }
 
@@ -294,23 +1206,9 @@ impl BlobRecords {
                 .await;
         }
 
-        if self
-            .adult_liveness
-            .new_read(msg_id, address, origin, targets.clone())
+        if let Some(duty) = self.start_read(msg_id, address, ReadRequester::Client(origin), targets)
         {
-            let msg = NodeMsg::NodeQuery {
-                query: NodeQuery::Chunks {
-                    query: BlobRead::Get(address),
-                    origin,
-                },
-                id: msg_id,
-            };
-
-            Ok(NodeDuty::SendToNodes {
-                msg,
-                targets,
-                aggregation: Aggregation::None,
-            })
+            Ok(duty)
         } else {
             info!(
                 "Operation with MessageId {:?} is already in progress",
@@ -319,6 +1217,69 @@ impl BlobRecords {
             Ok(NodeDuty::NoOp)
         }
     }
+
+    // Queries `holders` for the chunk, all at once or the healthiest first if reads are hedged.
+    // Returns `None` if the read is already in progress.
+    fn start_read(
+        &mut self,
+        msg_id: MessageId,
+        address: BlobAddress,
+        requester: ReadRequester,
+        holders: BTreeSet<XorName>,
+    ) -> Option<NodeDuty> {
+        let (started, targets) = match self.read_strategy.hedge_delay() {
+            Some(delay) if holders.len() > 1 => {
+                let mut ranked = self
+                    .adult_liveness
+                    .rank_holders(&holders, address.name())
+                    .into_iter();
+                let first = ranked.next()?;
+                let standby = ranked.collect();
+                let started = self
+                    .adult_liveness
+                    .new_hedged_read(msg_id, address, requester, first, standby, delay);
+                (started, btree_set!(first))
+            }
+            _ => (
+                self.adult_liveness
+                    .new_read(msg_id, address, requester, holders.clone()),
+                holders,
+            ),
+        };
+        if !started {
+            return None;
+        }
+        Some(query_holders(msg_id, address, requester.origin(), targets))
+    }
+}
+
+fn query_holders(
+    msg_id: MessageId,
+    address: BlobAddress,
+    origin: EndUser,
+    targets: BTreeSet<XorName>,
+) -> NodeDuty {
+    NodeDuty::SendToNodes {
+        msg: NodeMsg::NodeQuery {
+            query: NodeQuery::Chunks {
+                query: BlobRead::Get(address),
+                origin,
+            },
+            id: msg_id,
+        },
+        targets,
+        aggregation: Aggregation::None,
+    }
+}
+
+// The other holders of a chunk, to fetch it from for `holder` which lost its copy. None if
+// `holder` isn't one of `holders`, or is the only one.
+fn repair_sources(mut holders: BTreeSet<XorName>, holder: &XorName) -> Option<BTreeSet<XorName>> {
+    if holders.remove(holder) && !holders.is_empty() {
+        Some(holders)
+    } else {
+        None
+    }
 }
 
 fn validate_data_owner(data: &Blob, requester: &PublicKey) -> Result<()> {
@@ -342,3 +1303,22 @@ impl Display for BlobRecords {
         write!(formatter, "BlobRecords")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::btree_set;
+
+    #[test]
+    fn repair_fetches_from_the_other_holders() {
+        let (lost, other, stranger) = (XorName::random(), XorName::random(), XorName::random());
+        let holders = btree_set!(lost, other);
+
+        assert_eq!(
+            repair_sources(holders.clone(), &lost),
+            Some(btree_set!(other))
+        );
+        assert_eq!(repair_sources(holders, &stranger), None);
+        assert_eq!(repair_sources(btree_set!(lost), &lost), None);
+    }
+}
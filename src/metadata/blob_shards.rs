@@ -0,0 +1,549 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Erasure coding of blobs, as an alternative to storing full copies of them.
+//!
+//! A blob is split into data shards, and Reed-Solomon parity shards are computed over them, any
+//! `data_shards` of the shards being enough to rebuild the blob. Each shard is stored as a blob of
+//! its own on a distinct Adult. The Elders keep a manifest of the shards of each blob, to find
+//! them on reads and to regenerate those lost to churn.
+//!
+//! The manifests are handed over to new Elders with the rest of the section's metadata, see
+//! `handover`.
+
+use super::handover::{self, MANIFEST_TAG};
+use crate::{to_db_key::ToDbKey, Error, Result};
+use log::warn;
+use pickledb::{PickleDb, PickleDbDumpPolicy};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use serde::{Deserialize, Serialize};
+use sn_data_types::{
+    Blob, BlobAddress, DataAddress, Map, MapAddress, PrivateBlob, PublicBlob, PublicKey,
+};
+use sn_messaging::EndUser;
+use sn_routing::Prefix;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+    fs,
+    path::Path,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use xor_name::{XorName, XOR_NAME_LEN};
+
+/// Number of data shards blobs are split into, unless configured otherwise.
+pub const DEFAULT_DATA_SHARDS: u8 = 4;
+
+/// Number of parity shards computed for each blob, unless configured otherwise.
+pub const DEFAULT_PARITY_SHARDS: u8 = 2;
+
+/// Time before the regeneration of lost shards is attempted again, after failing for lack of
+/// shards left to rebuild the blob or of Adults to hold the new shards.
+pub const SHARD_REPAIR_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+// Shards start with this, then the name of their blob and their index among its shards. Clients
+// can't store blobs starting with it, so only shards do.
+const SHARD_MAGIC: &[u8] = b"sn-shard";
+const HEADER_LEN: usize = SHARD_MAGIC.len() + XOR_NAME_LEN + 1;
+
+/// How the Elders store blobs on the Adults.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlobStorageMode {
+    /// `CHUNK_COPY_COUNT` full copies of each blob are stored.
+    #[default]
+    Replicated,
+    /// Each blob is split into `data_shards` shards, `parity_shards` more being computed for it to
+    /// be rebuilt from any `data_shards` of them.
+    ErasureCoded {
+        /// Number of data shards.
+        data_shards: u8,
+        /// Number of parity shards.
+        parity_shards: u8,
+    },
+}
+
+/// Parses `replicated`, `erasure-coded` or `erasure-coded:<data shards>+<parity shards>`.
+impl FromStr for BlobStorageMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "replicated" => return Ok(Self::Replicated),
+            "erasure-coded" => {
+                return Ok(Self::ErasureCoded {
+                    data_shards: DEFAULT_DATA_SHARDS,
+                    parity_shards: DEFAULT_PARITY_SHARDS,
+                })
+            }
+            _ => (),
+        }
+        let counts = s.strip_prefix("erasure-coded:").and_then(|counts| {
+            let (data, parity) = counts.split_at(counts.find('+')?);
+            Some((data.parse::<u8>().ok()?, parity[1..].parse::<u8>().ok()?))
+        });
+        match counts {
+            Some((data_shards, parity_shards))
+                if data_shards > 0
+                    && parity_shards > 0
+                    && data_shards.checked_add(parity_shards).is_some() =>
+            {
+                Ok(Self::ErasureCoded {
+                    data_shards,
+                    parity_shards,
+                })
+            }
+            _ => Err(format!(
+                "Unknown blob storage mode '{}', expected one of: replicated, erasure-coded, \
+                 erasure-coded:<data shards>+<parity shards>",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for BlobStorageMode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Replicated => write!(f, "replicated"),
+            Self::ErasureCoded {
+                data_shards,
+                parity_shards,
+            } => write!(f, "erasure-coded:{}+{}", data_shards, parity_shards),
+        }
+    }
+}
+
+/// Whether `blob` is a shard of an erasure-coded blob, rather than a chunk of its own. The Elders
+/// don't store blobs for clients which this holds for, as they would be taken for shards.
+pub(crate) fn is_shard(blob: &Blob) -> bool {
+    blob.value().starts_with(SHARD_MAGIC)
+}
+
+/// Where the shards of an erasure-coded blob are.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub(super) struct ShardManifest {
+    pub address: BlobAddress,
+    pub owner: Option<PublicKey>,
+    pub data_shards: u8,
+    pub parity_shards: u8,
+    /// Length of the blob's content, the last data shard being padded beyond it.
+    pub len: u64,
+    /// Address and holder of each shard, the data shards first.
+    pub shards: Vec<(BlobAddress, XorName)>,
+}
+
+impl ShardManifest {
+    /// Splits `blob` into shards, to be stored on the respective `holders`. Returns the manifest
+    /// with the shards.
+    pub fn new(
+        blob: &Blob,
+        data_shards: u8,
+        parity_shards: u8,
+        holders: &BTreeSet<XorName>,
+    ) -> Result<(Self, Vec<Blob>)> {
+        let shards = encode(blob, data_shards, parity_shards)?;
+        if holders.len() < shards.len() {
+            return Err(Error::InvalidOperation(format!(
+                "{} shards can't be stored on {} Adults",
+                shards.len(),
+                holders.len()
+            )));
+        }
+        let manifest = Self {
+            address: *blob.address(),
+            owner: blob.owner().copied(),
+            data_shards,
+            parity_shards,
+            len: blob.value().len() as u64,
+            shards: shards
+                .iter()
+                .zip(holders)
+                .map(|(shard, holder)| (*shard.address(), *holder))
+                .collect(),
+        };
+        Ok((manifest, shards))
+    }
+
+    /// The Adults holding the shards.
+    pub fn holders(&self) -> BTreeSet<XorName> {
+        self.shards.iter().map(|(_, holder)| *holder).collect()
+    }
+
+    /// Rebuilds the blob from at least `data_shards` of its shards, by index.
+    pub fn reconstruct(&self, shards: &BTreeMap<usize, Blob>) -> Result<Blob> {
+        let corrupt = || Error::CorruptChunk(DataAddress::Blob(self.address));
+        let mut slots = vec![None; self.shards.len()];
+        for (index, shard) in shards {
+            match self.shards.get(*index) {
+                // Shard addresses are derived from their content, which is thus as expected.
+                Some((address, _)) if address == shard.address() => {
+                    slots[*index] = shard.value().get(HEADER_LEN..).map(<[u8]>::to_vec);
+                }
+                _ => warn!("Ignoring unexpected shard {} of {:?}", index, self.address),
+            }
+        }
+        codec(self.data_shards, self.parity_shards)?.reconstruct_data(&mut slots)?;
+
+        let mut value = slots
+            .into_iter()
+            .take(usize::from(self.data_shards))
+            .flatten()
+            .flatten()
+            .collect::<Vec<_>>();
+        if (value.len() as u64) < self.len {
+            return Err(corrupt());
+        }
+        value.truncate(self.len as usize);
+        let blob = match self.owner {
+            Some(owner) => Blob::Private(PrivateBlob::new(value, owner)),
+            None => Blob::Public(PublicBlob::new(value)),
+        };
+        if *blob.address() != self.address {
+            return Err(corrupt());
+        }
+        Ok(blob)
+    }
+}
+
+/// Splits `blob` into `data_shards` shards and computes `parity_shards` more, the data shards
+/// coming first. The same blob always gives the same shards.
+pub(super) fn encode(blob: &Blob, data_shards: u8, parity_shards: u8) -> Result<Vec<Blob>> {
+    let value = blob.value();
+    let data_count = usize::from(data_shards).max(1);
+    let shard_len = value.len().div_ceil(data_count).max(1);
+    let mut buffers = (0..usize::from(data_shards) + usize::from(parity_shards))
+        .map(|index| {
+            let start = (index * shard_len).min(value.len());
+            let end = ((index + 1) * shard_len).min(value.len());
+            let mut buffer = Vec::with_capacity(shard_len);
+            if index < data_count {
+                buffer.extend_from_slice(&value[start..end]);
+            }
+            buffer.resize(shard_len, 0);
+            buffer
+        })
+        .collect::<Vec<_>>();
+    codec(data_shards, parity_shards)?.encode(&mut buffers)?;
+
+    Ok(buffers
+        .into_iter()
+        .enumerate()
+        .map(|(index, buffer)| {
+            let mut content = Vec::with_capacity(HEADER_LEN + buffer.len());
+            content.extend_from_slice(SHARD_MAGIC);
+            content.extend_from_slice(&blob.name().0);
+            content.push(index as u8);
+            content.extend_from_slice(&buffer);
+            match blob.owner() {
+                Some(owner) => Blob::Private(PrivateBlob::new(content, *owner)),
+                None => Blob::Public(PublicBlob::new(content)),
+            }
+        })
+        .collect())
+}
+
+fn codec(data_shards: u8, parity_shards: u8) -> Result<ReedSolomon> {
+    Ok(ReedSolomon::new(
+        usize::from(data_shards),
+        usize::from(parity_shards),
+    )?)
+}
+
+/// Shards of an erasure-coded blob lost with their holders, kept until they're regenerated.
+#[derive(Debug, Default)]
+pub(super) struct ShardRepair {
+    pub lost: BTreeSet<XorName>,
+    /// When the repair is next attempted, if the last attempt failed.
+    pub retry_at: Option<Instant>,
+}
+
+impl ShardRepair {
+    /// Whether the repair is to be attempted at `now`.
+    pub fn is_due(&self, now: Instant) -> bool {
+        !matches!(self.retry_at, Some(retry_at) if retry_at > now)
+    }
+}
+
+/// Why the shards of a blob are read.
+#[derive(Clone, Debug)]
+pub(super) enum ShardReadPurpose {
+    /// For a client, to which the blob is returned.
+    Client(EndUser),
+    /// To regenerate the shards the given holders have lost.
+    Repair(BTreeSet<XorName>),
+}
+
+/// A read of the shards of a blob, complete once enough of them were received to rebuild it, or
+/// all holders have responded.
+pub(super) struct ShardRead {
+    pub manifest: ShardManifest,
+    pub purpose: ShardReadPurpose,
+    pub received: BTreeMap<usize, Blob>,
+    // Holders yet to respond.
+    pub pending: BTreeSet<XorName>,
+    pub started: Instant,
+}
+
+impl ShardRead {
+    /// Starts reading the shards of `manifest` from `holders`.
+    pub fn new(
+        manifest: ShardManifest,
+        purpose: ShardReadPurpose,
+        holders: BTreeSet<XorName>,
+    ) -> Self {
+        Self {
+            manifest,
+            purpose,
+            received: BTreeMap::new(),
+            pending: holders,
+            started: Instant::now(),
+        }
+    }
+
+    /// Records the response of `src`, with its shard if it has it.
+    pub fn record(&mut self, src: &XorName, shard: Option<Blob>) {
+        if !self.pending.remove(src) {
+            return;
+        }
+        let shard = match shard {
+            Some(shard) => shard,
+            None => return,
+        };
+        let index = self
+            .manifest
+            .shards
+            .iter()
+            .position(|(address, holder)| holder == src && address == shard.address());
+        if let Some(index) = index {
+            let _ = self.received.insert(index, shard);
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.received.len() >= usize::from(self.manifest.data_shards) || self.pending.is_empty()
+    }
+}
+
+/// The manifests of the erasure-coded blobs, kept on disk.
+pub(super) struct ShardIndex {
+    db: PickleDb,
+}
+
+impl ShardIndex {
+    /// Opens the index kept at `path`, or starts a new one if there's none.
+    pub fn open(path: &Path) -> Result<Self> {
+        // Blobs can't be read without their manifest, so every change is written at once.
+        let policy = || PickleDbDumpPolicy::AutoDump;
+        let db = match PickleDb::load_bin(path, policy()) {
+            Ok(db) => db,
+            Err(_) if !path.exists() => {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                PickleDb::new_bin(path, policy())
+            }
+            Err(err) => return Err(Error::PickleDb(err)),
+        };
+        Ok(Self { db })
+    }
+
+    pub fn get(&self, address: &BlobAddress) -> Option<ShardManifest> {
+        self.db.get(&address.to_db_key().ok()?)
+    }
+
+    pub fn insert(&mut self, manifest: &ShardManifest) -> Result<()> {
+        Ok(self.db.set(&manifest.address.to_db_key()?, manifest)?)
+    }
+
+    pub fn remove(&mut self, address: &BlobAddress) -> Result<()> {
+        let _ = self.db.rem(&address.to_db_key()?)?;
+        Ok(())
+    }
+
+    /// All manifests, in no particular order.
+    pub fn manifests(&self) -> Vec<ShardManifest> {
+        self.db
+            .iter()
+            .filter_map(|entry| entry.get_value::<ShardManifest>())
+            .collect()
+    }
+
+    /// The manifests of the blobs within `prefix`, wrapped in Maps to hand them over to the Elders
+    /// of its section.
+    pub fn export(&self, prefix: &Prefix) -> bincode::Result<BTreeMap<MapAddress, Map>> {
+        let mut maps = BTreeMap::new();
+        for manifest in self.manifests() {
+            if prefix.matches(manifest.address.name()) {
+                let map = handover::to_map(*manifest.address.name(), MANIFEST_TAG, &manifest)?;
+                let _ = maps.insert(*map.address(), map);
+            }
+        }
+        Ok(maps)
+    }
+
+    /// Keeps the manifests handed over by other Elders, wrapped in `maps`.
+    pub fn import(&mut self, maps: impl IntoIterator<Item = Map>) {
+        for map in maps {
+            let manifest = match handover::from_map::<ShardManifest>(&map, MANIFEST_TAG) {
+                Some(manifest) => manifest,
+                None => {
+                    warn!("{:?} doesn't carry a shard manifest", map.address());
+                    continue;
+                }
+            };
+            if let Err(err) = self.insert(&manifest) {
+                warn!(
+                    "Could not keep the manifest of {:?}: {}",
+                    manifest.address, err
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Result;
+    use bls::SecretKey;
+    use tempdir::TempDir;
+
+    fn holders(count: usize) -> BTreeSet<XorName> {
+        (0..count).map(|_| XorName::random()).collect()
+    }
+
+    #[test]
+    fn blobs_are_rebuilt_despite_lost_holders() -> Result<()> {
+        let owner = PublicKey::from(SecretKey::random().public_key());
+        for blob in &[
+            Blob::Public(PublicBlob::new((0..1000).map(|i| i as u8).collect())),
+            Blob::Private(PrivateBlob::new(vec![7; 10], owner)),
+            Blob::Public(PublicBlob::new(vec![])),
+        ] {
+            let (manifest, shards) = ShardManifest::new(blob, 4, 2, &holders(6))?;
+            assert_eq!(shards.len(), 6);
+            assert!(shards.iter().all(is_shard));
+            assert_eq!(manifest.holders().len(), 6);
+            assert_eq!(encode(blob, 4, 2)?, shards);
+
+            // Any two holders can be lost.
+            let mut received = shards
+                .iter()
+                .cloned()
+                .enumerate()
+                .collect::<BTreeMap<_, _>>();
+            let _ = received.remove(&0);
+            let _ = received.remove(&3);
+            assert_eq!(&manifest.reconstruct(&received)?, blob);
+
+            // A third one can't.
+            let _ = received.remove(&5);
+            assert!(manifest.reconstruct(&received).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn shard_reads_complete_with_enough_shards() -> Result<()> {
+        let blob = Blob::Public(PublicBlob::new(vec![1; 100]));
+        let holders = holders(3);
+        let (manifest, shards) = ShardManifest::new(&blob, 2, 1, &holders)?;
+        let mut read = ShardRead::new(
+            manifest.clone(),
+            ShardReadPurpose::Repair(BTreeSet::new()),
+            holders,
+        );
+
+        let (first, second) = (manifest.shards[0].1, manifest.shards[2].1);
+        read.record(&first, None);
+        assert!(!read.is_complete());
+        // A shard from another holder than the one expected doesn't count.
+        read.record(&second, Some(shards[1].clone()));
+        assert!(!read.is_complete());
+        read.record(&manifest.shards[1].1, Some(shards[1].clone()));
+        assert!(read.is_complete());
+        assert!(manifest.reconstruct(&read.received).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn manifests_are_kept_across_restarts() -> Result<()> {
+        let dir =
+            TempDir::new("shard_index").map_err(|e| Error::TempDirCreationFailed(e.to_string()))?;
+        let path = dir.path().join("blob_shards.db");
+        let blob = Blob::Public(PublicBlob::new(vec![1; 100]));
+        let (manifest, _) = ShardManifest::new(&blob, 2, 1, &holders(3))?;
+        ShardIndex::open(&path)?.insert(&manifest)?;
+
+        let mut index = ShardIndex::open(&path)?;
+        assert_eq!(index.get(blob.address()), Some(manifest));
+        assert_eq!(index.manifests().len(), 1);
+        index.remove(blob.address())?;
+        assert_eq!(index.get(blob.address()), None);
+        Ok(())
+    }
+
+    #[test]
+    fn blobs_are_read_after_a_handover() -> Result<()> {
+        let dir = TempDir::new("shard_index")?;
+        let blob = Blob::Public(PublicBlob::new((0..100).collect()));
+        let (manifest, shards) = ShardManifest::new(&blob, 2, 1, &holders(3))?;
+        let mut old_index = ShardIndex::open(&dir.path().join("old.db"))?;
+        old_index.insert(&manifest)?;
+
+        let prefix = Prefix::default().pushed(blob.name().bit(0));
+        assert!(old_index.export(&prefix.sibling())?.is_empty());
+        let maps = old_index.export(&prefix)?;
+        assert_eq!(maps.len(), 1);
+        assert!(maps.keys().all(handover::is_handover_map));
+
+        let mut new_index = ShardIndex::open(&dir.path().join("new.db"))?;
+        new_index.import(maps.into_values());
+        let handed_over = new_index
+            .get(blob.address())
+            .ok_or(Error::NoSuchChunk(DataAddress::Blob(*blob.address())))?;
+        assert_eq!(handed_over, manifest);
+        let received = shards.into_iter().enumerate().skip(1).collect();
+        assert_eq!(handed_over.reconstruct(&received)?, blob);
+        Ok(())
+    }
+
+    #[test]
+    fn failed_repairs_are_retried_later() {
+        let now = Instant::now();
+        let mut repair = ShardRepair::default();
+        assert!(repair.is_due(now));
+        repair.retry_at = Some(now + SHARD_REPAIR_RETRY_INTERVAL);
+        assert!(!repair.is_due(now));
+        assert!(repair.is_due(now + SHARD_REPAIR_RETRY_INTERVAL));
+    }
+
+    #[test]
+    fn modes_are_parsed() {
+        for mode in &[
+            BlobStorageMode::Replicated,
+            BlobStorageMode::ErasureCoded {
+                data_shards: 10,
+                parity_shards: 4,
+            },
+        ] {
+            assert_eq!(mode.to_string().parse(), Ok(*mode));
+        }
+        assert_eq!(
+            "erasure-coded".parse(),
+            Ok(BlobStorageMode::ErasureCoded {
+                data_shards: DEFAULT_DATA_SHARDS,
+                parity_shards: DEFAULT_PARITY_SHARDS
+            })
+        );
+        assert!("erasure-coded:4+0".parse::<BlobStorageMode>().is_err());
+        assert!("erasure-coded:200+100".parse::<BlobStorageMode>().is_err());
+    }
+}
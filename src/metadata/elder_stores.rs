@@ -7,17 +7,18 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
-    blob_records::BlobRecords, map_storage::MapStorage, register_storage::RegisterStorage,
-    sequence_storage::SequenceStorage,
+    blob_records::BlobRecords, handover::is_handover_map, map_storage::MapStorage,
+    register_storage::RegisterStorage, sequence_storage::SequenceStorage,
 };
-use crate::{node_ops::NodeDuty, Error, Result};
+use crate::{node_ops::NodeDuties, Error, Result};
 use log::info;
 use sn_data_types::PublicKey;
 use sn_messaging::{
-    client::{ClientSigned, DataCmd, DataExchange, DataQuery},
+    client::{ClientSigned, DataCmd, DataExchange, DataQuery, MapDataExchange},
     EndUser, MessageId,
 };
 use sn_routing::Prefix;
+use std::collections::BTreeMap;
 
 /// The various data type stores,
 /// that are only managed at Elders.
@@ -49,20 +50,24 @@ impl ElderStores {
         msg_id: MessageId,
         requester: PublicKey,
         origin: EndUser,
-    ) -> Result<NodeDuty> {
+    ) -> Result<NodeDuties> {
         match &query {
             DataQuery::Blob(read) => self.blob_records.read(read, msg_id, origin).await,
-            DataQuery::Map(read) => self.map_storage.read(read, msg_id, requester, origin).await,
-            DataQuery::Sequence(read) => {
+            DataQuery::Map(read) => Ok(vec![
+                self.map_storage
+                    .read(read, msg_id, requester, origin)
+                    .await?,
+            ]),
+            DataQuery::Sequence(read) => Ok(vec![
                 self.sequence_storage
                     .read(read, msg_id, requester, origin)
-                    .await
-            }
-            DataQuery::Register(read) => {
+                    .await?,
+            ]),
+            DataQuery::Register(read) => Ok(vec![
                 self.register_storage
                     .read(read, msg_id, requester, origin)
-                    .await
-            }
+                    .await?,
+            ]),
         }
     }
 
@@ -72,7 +77,7 @@ impl ElderStores {
         msg_id: MessageId,
         client_signed: ClientSigned,
         origin: EndUser,
-    ) -> Result<NodeDuty> {
+    ) -> Result<NodeDuties> {
         info!("Writing Data");
         match cmd {
             DataCmd::Blob(write) => {
@@ -83,25 +88,35 @@ impl ElderStores {
             }
             DataCmd::Map(write) => {
                 info!("Writing Map");
-                self.map_storage
-                    .write(write, msg_id, client_signed.public_key, origin)
-                    .await
+                Ok(vec![
+                    self.map_storage
+                        .write(write, msg_id, client_signed.public_key, origin)
+                        .await?,
+                ])
             }
             DataCmd::Sequence(write) => {
                 info!("Writing Sequence");
-                self.sequence_storage
-                    .write(write, msg_id, client_signed.public_key, origin)
-                    .await
+                Ok(vec![
+                    self.sequence_storage
+                        .write(write, msg_id, client_signed.public_key, origin)
+                        .await?,
+                ])
             }
             DataCmd::Register(write) => {
                 info!("Writing Register");
-                self.register_storage
-                    .write(write, msg_id, client_signed.public_key, origin)
-                    .await
+                Ok(vec![
+                    self.register_storage
+                        .write(write, msg_id, client_signed.public_key, origin)
+                        .await?,
+                ])
             }
         }
     }
 
+    pub fn blob_records(&self) -> &BlobRecords {
+        &self.blob_records
+    }
+
     pub fn blob_records_mut(&mut self) -> &mut BlobRecords {
         &mut self.blob_records
     }
@@ -110,8 +125,12 @@ impl ElderStores {
     pub async fn get_data_of(&self, prefix: Prefix) -> Result<DataExchange> {
         // Prepare blob_records, map and sequence data
         let blob_data = self.blob_records.get_data_of(prefix).await;
-        let map_data = self.map_storage.get_data_of(prefix);
-        let seq_data = self.sequence_storage.get_data_of(prefix);
+        let mut map_data = self.map_storage.get_data_of(prefix).await?;
+        // The records of the blobs are carried along the Maps, having no room of their own.
+        map_data
+            .0
+            .extend(self.blob_records.handover_maps_of(prefix)?);
+        let seq_data = self.sequence_storage.get_data_of(prefix).await?;
 
         Ok(DataExchange {
             blob_data,
@@ -121,9 +140,16 @@ impl ElderStores {
     }
 
     pub async fn update(&mut self, data: DataExchange) -> Result<(), Error> {
-        self.map_storage.update(data.map_data).await?;
+        let (records, maps) = data
+            .map_data
+            .0
+            .into_iter()
+            .partition::<BTreeMap<_, _>, _>(|(address, _)| is_handover_map(address));
+        self.map_storage.update(MapDataExchange(maps)).await?;
         self.sequence_storage.update(data.seq_data).await?;
-        self.blob_records.update(data.blob_data).await;
+        self.blob_records
+            .update(data.blob_data, records.into_values())
+            .await;
 
         Ok(())
     }
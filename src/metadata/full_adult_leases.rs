@@ -0,0 +1,85 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::chunks::FULL_REPORT_INTERVAL;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, Instant},
+};
+use xor_name::XorName;
+
+/// How long an Adult counts as full after reporting it. Full Adults renew their report every
+/// `FULL_REPORT_INTERVAL`, so a few reports can be lost before one is un-flagged by mistake.
+///
+/// There's no message to ask an Adult whether it's still full, so a lapsed lease is taken for it
+/// having freed up space. An Adult that's still full but whose last three reports were all lost is
+/// thus un-flagged, and sent new chunks until its next report gets through, at most
+/// `FULL_REPORT_INTERVAL` later. Adults report being full at `MAX_STORAGE_USAGE_RATIO` of their
+/// capacity, which leaves them room for the chunks sent meanwhile. A longer lease makes this
+/// rarer, at the cost of Adults which freed up space being counted as full for longer.
+pub const FULL_ADULT_LEASE: Duration = Duration::from_secs(3 * FULL_REPORT_INTERVAL.as_secs());
+
+/// When each of the Adults flagged as full last reported being so.
+#[derive(Default)]
+pub(super) struct FullAdultLeases {
+    leases: BTreeMap<XorName, Instant>,
+}
+
+impl FullAdultLeases {
+    /// Renews the lease of `adult` from `now`. Returns whether it wasn't flagged as full already.
+    pub fn renew(&mut self, adult: XorName, now: Instant) -> bool {
+        self.leases.insert(adult, now).is_none()
+    }
+
+    /// Ends the leases of the Adults which haven't reported being full for `FULL_ADULT_LEASE`
+    /// before `now`, and returns them.
+    pub fn expire(&mut self, now: Instant) -> BTreeSet<XorName> {
+        let expired = self
+            .leases
+            .iter()
+            .filter(|(_, renewed)| now.saturating_duration_since(**renewed) >= FULL_ADULT_LEASE)
+            .map(|(adult, _)| *adult)
+            .collect::<BTreeSet<_>>();
+        for adult in &expired {
+            let _ = self.leases.remove(adult);
+        }
+        expired
+    }
+
+    /// Drops the leases of the Adults no longer among `members`.
+    pub fn retain_members_only(&mut self, members: &BTreeSet<XorName>) {
+        self.leases.retain(|adult, _| members.contains(adult));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::btree_set;
+
+    #[test]
+    fn adults_no_longer_reporting_full_are_unflagged() {
+        let mut leases = FullAdultLeases::default();
+        let (recovered, still_full) = (XorName::random(), XorName::random());
+        let start = Instant::now();
+
+        assert!(leases.renew(recovered, start));
+        assert!(leases.renew(still_full, start));
+        // Renewals don't flag an Adult anew.
+        assert!(!leases.renew(still_full, start + FULL_REPORT_INTERVAL));
+        assert!(leases.expire(start + FULL_REPORT_INTERVAL).is_empty());
+
+        let lapsed = start + FULL_ADULT_LEASE;
+        assert_eq!(leases.expire(lapsed), btree_set!(recovered));
+        assert!(leases.expire(lapsed).is_empty());
+        assert_eq!(
+            leases.expire(lapsed + FULL_REPORT_INTERVAL),
+            btree_set!(still_full)
+        );
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Records of the Elders handed over to new Elders with the rest of the section's metadata.
+//!
+//! The data exchange has no room for them, so they are carried as unsequenced Maps with a type
+//! tag reserved for each kind of record, which clients can't store Maps with.
+//!
+//! Anyone can send a data exchange to a new Elder, so the records are sealed with the signature
+//! share of the Elder sending them, over the digest of all of them. The new Elder only keeps them
+//! if the share is one of a key of our section, or of our sibling after a split. A single share
+//! is enough, the Elders sending their data exchanges each on their own.
+
+use crate::{btree_map, network::Network, Result};
+use bls::{PublicKeySet, SignatureShare};
+use log::warn;
+use ring::digest::{digest, SHA256};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sn_data_types::{Map, MapAddress, MapKind, PublicKey, UnseqMap};
+use sn_messaging::client::DataExchange;
+use std::{collections::BTreeMap, mem};
+use xor_name::XorName;
+
+/// Type tag of the Maps carrying the manifests of erasure-coded blobs.
+pub(super) const MANIFEST_TAG: u64 = u64::MAX;
+
+/// Type tag of the Maps carrying pages of the blobs registered with the replica audit.
+pub(super) const AUDIT_TAG: u64 = u64::MAX - 1;
+
+// Type tag of the Map carrying the seal of the records.
+const SEAL_TAG: u64 = u64::MAX - 2;
+
+// The record is kept under this key of its Map.
+const RECORD_KEY: &[u8] = b"record";
+
+/// Whether the Map at `address` carries a record in a data exchange.
+pub(super) fn is_handover_map(address: &MapAddress) -> bool {
+    address.kind() == MapKind::Unseq && address.tag() >= SEAL_TAG
+}
+
+/// Wraps `record` in a Map at `name` with the type tag `tag`, to be carried in a data exchange.
+pub(super) fn to_map<T: Serialize>(name: XorName, tag: u64, record: &T) -> bincode::Result<Map> {
+    let entries = btree_map!(RECORD_KEY.to_vec() => bincode::serialize(record)?);
+    // Maps must have an owner, although these are never stored.
+    let owner = PublicKey::from(bls::SecretKey::default().public_key());
+    Ok(Map::Unseq(UnseqMap::new_with_data(
+        name,
+        tag,
+        entries,
+        BTreeMap::new(),
+        owner,
+    )))
+}
+
+/// Unwraps a record carried in a data exchange by a Map with the type tag `tag`.
+pub(super) fn from_map<T: DeserializeOwned>(map: &Map, tag: u64) -> Option<T> {
+    match map {
+        Map::Unseq(map) if map.address().tag() == tag => {
+            bincode::deserialize(map.get(RECORD_KEY)?).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Seals the records carried by `data` with our signature share as an Elder. They are left out if
+/// we can't sign them.
+pub(crate) async fn seal_records(data: &mut DataExchange, network: &Network) {
+    let mut records = take_records(data);
+    if records.is_empty() {
+        return;
+    }
+    match seal(&records, network).await {
+        Ok(seal) => {
+            let _ = records.insert(*seal.address(), seal);
+            data.map_data.0.extend(records);
+        }
+        Err(err) => warn!("Leaving out the records we couldn't seal: {}", err),
+    }
+}
+
+/// Drops the records carried by `data` unless they are sealed by an Elder holding a share of a
+/// key of our section, or of our sibling.
+pub(crate) async fn check_records(data: &mut DataExchange, network: &Network) {
+    let mut records = take_records(data);
+    let seal = records
+        .remove(&seal_address())
+        .and_then(|map| from_map::<Seal>(&map, SEAL_TAG));
+    if records.is_empty() {
+        return;
+    }
+    let chain = network.section_chain().await;
+    let sibling_key = network.sibling_public_key().await;
+    let is_trusted =
+        |key: &bls::PublicKey| chain.has_key(key) || sibling_key == Some(PublicKey::Bls(*key));
+    if matches!(seal, Some(seal) if seal.verify(&records, is_trusted)) {
+        data.map_data.0.extend(records);
+    } else {
+        warn!(
+            "Dropping the {} records handed over without a valid seal",
+            records.len()
+        );
+    }
+}
+
+// The signature share of an Elder over the digest of the records of a data exchange.
+#[derive(Deserialize, Serialize)]
+struct Seal {
+    key_set: PublicKeySet,
+    index: usize,
+    share: SignatureShare,
+}
+
+impl Seal {
+    // Whether this is the seal of the `records`, by an Elder holding a share of a key which
+    // `is_trusted` holds for.
+    fn verify(
+        &self,
+        records: &BTreeMap<MapAddress, Map>,
+        is_trusted: impl Fn(&bls::PublicKey) -> bool,
+    ) -> bool {
+        let signed = match signed_digest(records) {
+            Ok(signed) => signed,
+            Err(_) => return false,
+        };
+        is_trusted(&self.key_set.public_key())
+            && self
+                .key_set
+                .public_key_share(self.index)
+                .verify(&self.share, signed)
+    }
+}
+
+async fn seal(records: &BTreeMap<MapAddress, Map>, network: &Network) -> Result<Map> {
+    let seal = Seal {
+        key_set: network.our_public_key_set().await?,
+        index: network.our_index().await?,
+        share: network.sign_as_elder_raw(&records_digest(records)?).await?,
+    };
+    Ok(to_map(XorName::default(), SEAL_TAG, &seal)?)
+}
+
+fn seal_address() -> MapAddress {
+    MapAddress::Unseq {
+        name: XorName::default(),
+        tag: SEAL_TAG,
+    }
+}
+
+// Takes the records out of `data`, leaving the Maps.
+fn take_records(data: &mut DataExchange) -> BTreeMap<MapAddress, Map> {
+    let (records, maps) = mem::take(&mut data.map_data.0)
+        .into_iter()
+        .partition(|(address, _)| is_handover_map(address));
+    data.map_data.0 = maps;
+    records
+}
+
+fn records_digest(records: &BTreeMap<MapAddress, Map>) -> bincode::Result<Vec<u8>> {
+    Ok(digest(&SHA256, &bincode::serialize(records)?)
+        .as_ref()
+        .to_vec())
+}
+
+// The bytes the seal signs, the digest being serialised to be signed.
+fn signed_digest(records: &BTreeMap<MapAddress, Map>) -> bincode::Result<Vec<u8>> {
+    bincode::serialize(&records_digest(records)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bls::SecretKeySet;
+    use rand::thread_rng;
+
+    fn records() -> bincode::Result<BTreeMap<MapAddress, Map>> {
+        let mut records = BTreeMap::new();
+        for tag in &[MANIFEST_TAG, AUDIT_TAG] {
+            let map = to_map(XorName::random(), *tag, &vec![XorName::random()])?;
+            let _ = records.insert(*map.address(), map);
+        }
+        Ok(records)
+    }
+
+    #[test]
+    fn records_are_only_kept_with_a_valid_seal() -> bincode::Result<()> {
+        let key_set = SecretKeySet::random(2, &mut thread_rng());
+        let records = records()?;
+        let share = key_set.secret_key_share(1).sign(signed_digest(&records)?);
+        let seal = Seal {
+            key_set: key_set.public_keys(),
+            index: 1,
+            share,
+        };
+        let section_key = key_set.public_keys().public_key();
+        let is_section_key = |key: &bls::PublicKey| *key == section_key;
+
+        assert!(seal.verify(&records, is_section_key));
+        // A key which isn't one of our section's.
+        assert!(!seal.verify(&records, |_| false));
+        // Records other than those sealed.
+        assert!(!seal.verify(&self::records()?, is_section_key));
+        // Another share than the one signing.
+        let misplaced = Seal { index: 0, ..seal };
+        assert!(!misplaced.verify(&records, is_section_key));
+        Ok(())
+    }
+
+    #[test]
+    fn records_are_told_apart_from_client_maps() -> bincode::Result<()> {
+        let page = vec![XorName::random()];
+        let map = to_map(XorName::random(), AUDIT_TAG, &page)?;
+        assert!(is_handover_map(map.address()));
+        assert_eq!(from_map::<Vec<XorName>>(&map, AUDIT_TAG), Some(page));
+        assert_eq!(from_map::<Vec<XorName>>(&map, MANIFEST_TAG), None);
+        assert!(!is_handover_map(&MapAddress::Unseq {
+            name: XorName::random(),
+            tag: SEAL_TAG - 1,
+        }));
+        Ok(())
+    }
+}
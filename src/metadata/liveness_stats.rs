@@ -0,0 +1,235 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+    time::Duration,
+};
+
+/// Upper bounds of the buckets of the latency histograms, in milliseconds. Slower responses fall
+/// in one more bucket after these.
+pub const LATENCY_BUCKETS_MS: [u64; 8] = [50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000];
+
+/// Responses slower than this count as half a success towards the score.
+const SLOW_RESPONSE: Duration = Duration::from_secs(5);
+
+/// Weight of the previous score when a new outcome is recorded.
+const SCORE_DECAY: f64 = 0.9;
+
+/// Number of outcomes recorded for an Adult before its score is trusted.
+const MIN_OUTCOMES: u64 = 10;
+
+/// Minimum score in percent below which Adults are proposed offline, unless configured otherwise.
+pub const DEFAULT_MIN_SCORE: u8 = 50;
+
+/// How the Elders decide which Adults to propose offline, from their responses to chunk reads.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LivenessPolicy {
+    /// Adults with far more reads pending than their XOR-closest neighbours are proposed.
+    #[default]
+    PendingOps,
+    /// Adults whose score, in percent, has fallen below `min_score` are proposed.
+    Score {
+        /// Minimum score in percent.
+        min_score: u8,
+    },
+    /// No Adult is ever proposed.
+    Disabled,
+}
+
+/// Parses `pending-ops`, `score`, `score:<min score>` or `disabled`.
+impl FromStr for LivenessPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending-ops" => return Ok(Self::PendingOps),
+            "score" => {
+                return Ok(Self::Score {
+                    min_score: DEFAULT_MIN_SCORE,
+                })
+            }
+            "disabled" => return Ok(Self::Disabled),
+            _ => (),
+        }
+        match s.strip_prefix("score:").map(str::parse::<u8>) {
+            Some(Ok(min_score)) if min_score <= 100 => Ok(Self::Score { min_score }),
+            _ => Err(format!(
+                "Unknown liveness policy '{}', expected one of: pending-ops, score, score:<0-100>, disabled",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for LivenessPolicy {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::PendingOps => write!(f, "pending-ops"),
+            Self::Score { min_score } => write!(f, "score:{}", min_score),
+            Self::Disabled => write!(f, "disabled"),
+        }
+    }
+}
+
+/// How an Adult has responded to the chunk reads sent to it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdultStats {
+    /// Between 0 and 1, decaying towards the outcome of each read: 1 for a timely success, 0.5
+    /// for a slow one or an error, and 0 for a missed response.
+    pub score: f64,
+    /// Reads responded to with the chunk.
+    pub successes: u64,
+    /// Reads responded to with an error.
+    pub failures: u64,
+    /// Reads which timed out before the Adult responded.
+    pub misses: u64,
+    /// Chunks the Adult should have held, but didn't have when read and were replicated back.
+    pub repairs: u64,
+    /// Storage challenges the Adult proved holding the chunk for.
+    pub proofs: u64,
+    /// Storage challenges the Adult failed to prove holding the chunk for.
+    pub failed_proofs: u64,
+    /// Number of responses by latency, in the buckets of `LATENCY_BUCKETS_MS`.
+    pub latencies: [u64; LATENCY_BUCKETS_MS.len() + 1],
+}
+
+impl Default for AdultStats {
+    fn default() -> Self {
+        Self {
+            score: 1.0,
+            successes: 0,
+            failures: 0,
+            misses: 0,
+            repairs: 0,
+            proofs: 0,
+            failed_proofs: 0,
+            latencies: [0; LATENCY_BUCKETS_MS.len() + 1],
+        }
+    }
+}
+
+impl AdultStats {
+    /// Share of the reads responded to with the chunk.
+    pub fn success_ratio(&self) -> f64 {
+        ratio(self.successes, self.outcomes())
+    }
+
+    /// Share of the reads responded to with an error.
+    pub fn failure_ratio(&self) -> f64 {
+        ratio(self.failures, self.outcomes())
+    }
+
+    pub(super) fn record_response(&mut self, success: bool, latency: Duration) {
+        let millis = latency.as_millis();
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| millis <= u128::from(*bound))
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.latencies[bucket] += 1;
+
+        if success {
+            self.successes += 1;
+        } else {
+            self.failures += 1;
+        }
+        let outcome = if success && latency <= SLOW_RESPONSE {
+            1.0
+        } else {
+            0.5
+        };
+        self.decay_towards(outcome);
+    }
+
+    pub(super) fn record_miss(&mut self) {
+        self.misses += 1;
+        self.decay_towards(0.0);
+    }
+
+    /// A lost chunk costs the Adult as much as a missed response, on top of the failed read.
+    pub(super) fn record_repair(&mut self) {
+        self.repairs += 1;
+        self.decay_towards(0.0);
+    }
+
+    /// A proven storage challenge counts as a timely read, and a failed one costs the Adult as
+    /// much as a lost chunk.
+    pub(super) fn record_proof(&mut self, valid: bool) {
+        if valid {
+            self.proofs += 1;
+            self.decay_towards(1.0);
+        } else {
+            self.failed_proofs += 1;
+            self.decay_towards(0.0);
+        }
+    }
+
+    /// Whether enough outcomes were recorded for the score to be below `min_score` percent.
+    pub(super) fn is_below(&self, min_score: u8) -> bool {
+        let scored = self.outcomes() + self.proofs + self.failed_proofs;
+        scored >= MIN_OUTCOMES && self.score * 100.0 < f64::from(min_score)
+    }
+
+    fn outcomes(&self) -> u64 {
+        self.successes + self.failures + self.misses
+    }
+
+    fn decay_towards(&mut self, outcome: f64) {
+        self.score = self.score * SCORE_DECAY + outcome * (1.0 - SCORE_DECAY);
+    }
+}
+
+fn ratio(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_decays_with_missed_responses() {
+        let mut stats = AdultStats::default();
+        for _ in 0..MIN_OUTCOMES {
+            stats.record_response(true, Duration::from_millis(60));
+        }
+        assert!(!stats.is_below(DEFAULT_MIN_SCORE));
+        assert_eq!(stats.latencies[1], MIN_OUTCOMES);
+
+        while !stats.is_below(DEFAULT_MIN_SCORE) {
+            stats.record_miss();
+        }
+        assert_eq!(stats.misses, 7);
+        assert!(stats.success_ratio() > 0.5 && stats.failure_ratio() == 0.0);
+    }
+
+    #[test]
+    fn policies_are_parsed() {
+        for policy in &[
+            LivenessPolicy::PendingOps,
+            LivenessPolicy::Score { min_score: 30 },
+            LivenessPolicy::Disabled,
+        ] {
+            assert_eq!(policy.to_string().parse(), Ok(*policy));
+        }
+        assert_eq!(
+            "score".parse(),
+            Ok(LivenessPolicy::Score {
+                min_score: DEFAULT_MIN_SCORE
+            })
+        );
+        assert!("score:101".parse::<LivenessPolicy>().is_err());
+    }
+}
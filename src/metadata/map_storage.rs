@@ -6,10 +6,14 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{build_client_error_response, build_client_query_response};
+use super::{build_client_error_response, build_client_query_response, handover::is_handover_map};
 use crate::{
-    chunk_store::MapChunkStore, error::convert_to_error_message, node_ops::NodeDuty, Error, Result,
+    chunk_store::{MapChunkStore, StoreConfig, UsedSpace},
+    error::convert_to_error_message,
+    node_ops::NodeDuty,
+    Error, Result,
 };
+use futures::{pin_mut, StreamExt};
 use log::{debug, info};
 use sn_data_types::{
     Error as DtError, Map, MapAction, MapAddress, MapEntryActions, MapPermissionSet, MapValue,
@@ -21,6 +25,7 @@ use sn_messaging::{
 };
 use sn_routing::Prefix;
 use std::{
+    collections::BTreeMap,
     fmt::{self, Display, Formatter},
     path::Path,
 };
@@ -31,22 +36,30 @@ pub(super) struct MapStorage {
 }
 
 impl MapStorage {
-    pub(super) async fn new(path: &Path, max_capacity: u64) -> Result<Self> {
-        let chunks = MapChunkStore::new(path, max_capacity).await?;
+    pub(super) async fn new(
+        path: &Path,
+        used_space: &UsedSpace,
+        config: &StoreConfig,
+    ) -> Result<Self> {
+        let chunks = MapChunkStore::new(path, used_space, config).await?;
         Ok(Self { chunks })
     }
 
-    pub(super) fn get_data_of(&self, prefix: Prefix) -> MapDataExchange {
-        let store = &self.chunks;
-        let data = self
-            .chunks
-            .keys()
-            .iter()
-            .filter(|address| prefix.matches(address.name()))
-            .filter_map(|key| store.get(&key).ok())
-            .map(|map| (*map.address(), map))
-            .collect();
-        MapDataExchange(data)
+    pub(super) async fn get_data_of(&self, prefix: Prefix) -> Result<MapDataExchange> {
+        let mut data = BTreeMap::new();
+        let addresses = self.chunks.key_stream();
+        pin_mut!(addresses);
+        while let Some(address) = addresses.next().await {
+            let address = address?;
+            // Those stored with a reserved tag before it was, which would be taken for records.
+            if !prefix.matches(address.name()) || is_handover_map(&address) {
+                continue;
+            }
+            if let Ok(map) = self.chunks.get(&address).await {
+                let _ = data.insert(address, map);
+            }
+        }
+        Ok(MapDataExchange(data))
     }
 
     pub async fn update(&mut self, map_data: MapDataExchange) -> Result<()> {
@@ -133,13 +146,13 @@ impl MapStorage {
     /// Returns `Some(Result<..>)` if the flow should be continued, returns
     /// `None` if there was a logic error encountered and the flow should be
     /// terminated.
-    fn get_chunk(
+    async fn get_chunk(
         &self,
         address: &MapAddress,
         requester: PublicKey,
         action: MapAction,
     ) -> Result<Map> {
-        self.chunks.get(&address).and_then(move |map| {
+        self.chunks.get(&address).await.and_then(move |map| {
             map.check_permissions(action, &requester)
                 .map(move |_| map)
                 .map_err(|error| error.into())
@@ -157,7 +170,7 @@ impl MapStorage {
     where
         F: FnOnce(Map) -> NdResult<Map>,
     {
-        let result = match self.chunks.get(address) {
+        let result = match self.chunks.get(address).await {
             Ok(data) => match mutation_fn(data) {
                 Ok(map) => self.chunks.put(&map).await,
                 Err(error) => Err(error.into()),
@@ -170,7 +183,12 @@ impl MapStorage {
 
     /// Put Map.
     async fn create(&mut self, data: &Map, msg_id: MessageId, origin: EndUser) -> Result<NodeDuty> {
-        let result = if self.chunks.has(data.address()) {
+        let result = if is_handover_map(data.address()) {
+            Err(Error::InvalidOperation(format!(
+                "Type tag {} is reserved",
+                data.address().tag()
+            )))
+        } else if self.chunks.has(data.address()).await {
             Err(Error::DataExists)
         } else {
             self.chunks.put(&data).await
@@ -185,7 +203,7 @@ impl MapStorage {
         requester: PublicKey,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let result = match self.chunks.get(&address) {
+        let result = match self.chunks.get(&address).await {
             Ok(map) => match map.check_is_owner(&requester) {
                 Ok(()) => {
                     info!("Deleting Map");
@@ -244,7 +262,7 @@ impl MapStorage {
         requester: PublicKey,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let result = match self.get_chunk(&address, requester, MapAction::Read) {
+        let result = match self.get_chunk(&address, requester, MapAction::Read).await {
             Ok(res) => Ok(res),
             Err(error) => Err(convert_to_error_message(error)),
         };
@@ -266,6 +284,7 @@ impl MapStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(&address, requester, MapAction::Read)
+            .await
             .map(|data| data.shell())
         {
             Ok(res) => Ok(res),
@@ -289,6 +308,7 @@ impl MapStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(&address, requester, MapAction::Read)
+            .await
             .map(|data| data.version())
         {
             Ok(res) => Ok(res),
@@ -311,7 +331,7 @@ impl MapStorage {
         requester: PublicKey,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let res = self.get_chunk(&address, requester, MapAction::Read);
+        let res = self.get_chunk(&address, requester, MapAction::Read).await;
         let result = match res.and_then(|data| match data {
             Map::Seq(map) => map
                 .get(key)
@@ -345,6 +365,7 @@ impl MapStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(&address, requester, MapAction::Read)
+            .await
             .map(|data| data.keys())
         {
             Ok(res) => Ok(res),
@@ -366,7 +387,7 @@ impl MapStorage {
         requester: PublicKey,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let res = self.get_chunk(&address, requester, MapAction::Read);
+        let res = self.get_chunk(&address, requester, MapAction::Read).await;
         let result = match res.map(|data| match data {
             Map::Seq(map) => map.values().into(),
             Map::Unseq(map) => map.values().into(),
@@ -390,7 +411,7 @@ impl MapStorage {
         requester: PublicKey,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let res = self.get_chunk(&address, requester, MapAction::Read);
+        let res = self.get_chunk(&address, requester, MapAction::Read).await;
         let result = match res.map(|data| match data {
             Map::Seq(map) => map.entries().clone().into(),
             Map::Unseq(map) => map.entries().clone().into(),
@@ -416,6 +437,7 @@ impl MapStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(&address, requester, MapAction::Read)
+            .await
             .map(|data| data.permissions())
         {
             Ok(res) => Ok(res),
@@ -440,6 +462,7 @@ impl MapStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(&address, requester, MapAction::Read)
+            .await
             .and_then(|data| {
                 data.user_permissions(&user)
                     .map_err(|e| e.into())
@@ -9,22 +9,36 @@
 mod adult_liveness;
 pub mod adult_reader;
 mod blob_records;
+mod blob_shards;
 mod elder_stores;
+mod full_adult_leases;
+mod handover;
+mod liveness_stats;
 mod map_storage;
+mod read_strategy;
 mod register_storage;
+mod replica_audit;
 mod sequence_storage;
+mod settled_adults;
+mod storage_challenges;
 
 use crate::{
     capacity::Capacity,
+    chunk_store::{StoreConfig, UsedSpace},
     node_ops::{MsgType, NodeDuties, NodeDuty, OutgoingMsg},
     Result,
 };
 use blob_records::BlobRecords;
+pub(crate) use blob_shards::is_shard;
+pub use blob_shards::BlobStorageMode;
 use elder_stores::ElderStores;
+pub(crate) use handover::{check_records, seal_records};
+pub use liveness_stats::{AdultStats, LivenessPolicy, LATENCY_BUCKETS_MS};
 use map_storage::MapStorage;
+pub use read_strategy::ReadStrategy;
 use register_storage::RegisterStorage;
 use sequence_storage::SequenceStorage;
-use sn_data_types::{Blob, PublicKey};
+use sn_data_types::{Blob, BlobAddress, PublicKey};
 use sn_messaging::{
     client::{
         ClientMsg, ClientSigned, CmdError, DataCmd, DataExchange, DataQuery, ProcessMsg,
@@ -37,9 +51,17 @@ use std::{
     collections::BTreeSet,
     fmt::{self, Display, Formatter},
     path::Path,
+    time::Duration,
 };
 use xor_name::XorName;
 
+/// Time between two sweeps over the chunk reads, hedging those due to be and giving up on those
+/// which timed out.
+pub const EXPIRE_READS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Time between two rounds of the replica audit, each checking the holders of a page of blobs.
+pub const AUDIT_REPLICAS_INTERVAL: Duration = Duration::from_secs(60);
+
 /// This module is called `Metadata`
 /// as a preparation for the responsibilities
 /// it will have eventually, after `Data Hierarchy Refinement`
@@ -51,11 +73,20 @@ pub struct Metadata {
 }
 
 impl Metadata {
-    pub async fn new(path: &Path, max_capacity: u64, capacity: Capacity) -> Result<Self> {
-        let blob_records = BlobRecords::new(capacity);
-        let map_storage = MapStorage::new(path, max_capacity).await?;
-        let sequence_storage = SequenceStorage::new(path, max_capacity).await?;
-        let register_storage = RegisterStorage::new(path, max_capacity).await?;
+    pub async fn new(
+        path: &Path,
+        used_space: &UsedSpace,
+        store_config: &StoreConfig,
+        capacity: Capacity,
+        liveness_policy: LivenessPolicy,
+        read_strategy: ReadStrategy,
+        blob_storage: BlobStorageMode,
+    ) -> Result<Self> {
+        let blob_records =
+            BlobRecords::new(path, capacity, liveness_policy, read_strategy, blob_storage)?;
+        let map_storage = MapStorage::new(path, used_space, store_config).await?;
+        let sequence_storage = SequenceStorage::new(path, used_space, store_config).await?;
+        let register_storage = RegisterStorage::new(path, used_space, store_config).await?;
         let elder_stores = ElderStores::new(
             blob_records,
             map_storage,
@@ -71,7 +102,7 @@ impl Metadata {
         id: MessageId,
         requester: PublicKey,
         origin: EndUser,
-    ) -> Result<NodeDuty> {
+    ) -> Result<NodeDuties> {
         self.elder_stores.read(query, id, requester, origin).await
     }
 
@@ -87,6 +118,35 @@ impl Metadata {
             .await
     }
 
+    pub async fn record_storage_proof(
+        &mut self,
+        correlation_id: MessageId,
+        proof: &Blob,
+        src: XorName,
+    ) -> NodeDuties {
+        self.elder_stores
+            .blob_records_mut()
+            .record_storage_proof(correlation_id, proof, src)
+            .await
+    }
+
+    /// How each Adult has responded to the chunk reads sent to it.
+    pub fn adult_stats(&self) -> Vec<(XorName, AdultStats)> {
+        self.elder_stores.blob_records().adult_stats()
+    }
+
+    /// Hedges the chunk reads the first holder didn't respond to in time, and gives up on those
+    /// none of the holders did.
+    pub async fn expire_reads(&mut self) -> NodeDuties {
+        self.elder_stores.blob_records_mut().expire_reads().await
+    }
+
+    /// Checks that the holders of a page of the known blobs still have them, republishing those
+    /// short of copies.
+    pub async fn audit_replicas(&mut self) -> NodeDuties {
+        self.elder_stores.blob_records_mut().audit_replicas().await
+    }
+
     pub async fn retain_members_only(&mut self, members: BTreeSet<XorName>) -> Result<()> {
         self.elder_stores
             .blob_records_mut()
@@ -101,14 +161,15 @@ impl Metadata {
         id: MessageId,
         client_signed: ClientSigned,
         origin: EndUser,
-    ) -> Result<NodeDuty> {
+    ) -> Result<NodeDuties> {
         self.elder_stores
             .write(cmd, id, client_signed, origin)
             .await
     }
 
-    /// Adds a given node to the list of full nodes.
-    pub async fn increase_full_node_count(&mut self, node_id: PublicKey) {
+    /// Adds a given node to the list of full nodes, or renews its report if it's there already.
+    /// Returns whether it wasn't counted as full already.
+    pub async fn increase_full_node_count(&mut self, node_id: PublicKey) -> bool {
         self.elder_stores
             .blob_records_mut()
             .increase_full_node_count(node_id)
@@ -123,6 +184,19 @@ impl Metadata {
             .await
     }
 
+    // When a holder has lost a chunk, we fetch it from the other holders to replicate it back.
+    pub async fn repair_chunk(
+        &mut self,
+        address: BlobAddress,
+        holder: XorName,
+        msg_id: MessageId,
+    ) -> Result<NodeDuty> {
+        self.elder_stores
+            .blob_records_mut()
+            .repair_corrupt(address, holder, msg_id)
+            .await
+    }
+
     pub async fn get_data_exchange_packet(&self, prefix: Prefix) -> Result<DataExchange> {
         self.elder_stores.get_data_of(prefix).await
     }
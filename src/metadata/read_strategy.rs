@@ -0,0 +1,97 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+    time::Duration,
+};
+
+/// Time after which a hedged read is sent to the remaining holders, unless configured otherwise.
+pub const DEFAULT_HEDGE_DELAY_MS: u64 = 2_000;
+
+/// How the Elders query the holders of a chunk when it's read.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReadStrategy {
+    /// All holders are queried at once.
+    #[default]
+    AllHolders,
+    /// The healthiest holder is queried first, the remaining ones only if it responds with an
+    /// error, or hasn't responded after `delay_ms`.
+    Hedged {
+        /// Time in milliseconds to wait for the first holder.
+        delay_ms: u64,
+    },
+}
+
+impl ReadStrategy {
+    /// Time to wait for the first holder before querying the others, if reads are hedged.
+    pub fn hedge_delay(&self) -> Option<Duration> {
+        match self {
+            Self::AllHolders => None,
+            Self::Hedged { delay_ms } => Some(Duration::from_millis(*delay_ms)),
+        }
+    }
+}
+
+/// Parses `all-holders`, `hedged` or `hedged:<delay in ms>`.
+impl FromStr for ReadStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all-holders" => return Ok(Self::AllHolders),
+            "hedged" => {
+                return Ok(Self::Hedged {
+                    delay_ms: DEFAULT_HEDGE_DELAY_MS,
+                })
+            }
+            _ => (),
+        }
+        match s.strip_prefix("hedged:").map(str::parse::<u64>) {
+            Some(Ok(delay_ms)) => Ok(Self::Hedged { delay_ms }),
+            _ => Err(format!(
+                "Unknown read strategy '{}', expected one of: all-holders, hedged, hedged:<ms>",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for ReadStrategy {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::AllHolders => write!(f, "all-holders"),
+            Self::Hedged { delay_ms } => write!(f, "hedged:{}", delay_ms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strategies_are_parsed() {
+        for strategy in &[
+            ReadStrategy::AllHolders,
+            ReadStrategy::Hedged { delay_ms: 500 },
+        ] {
+            assert_eq!(strategy.to_string().parse(), Ok(*strategy));
+        }
+        assert_eq!(
+            "hedged".parse(),
+            Ok(ReadStrategy::Hedged {
+                delay_ms: DEFAULT_HEDGE_DELAY_MS
+            })
+        );
+        assert!("hedged:soon".parse::<ReadStrategy>().is_err());
+    }
+}
@@ -0,0 +1,370 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Audits of the number of copies of the blobs, the holders of a page of them being asked every
+//! round whether they still have them.
+//!
+//! The Elders register the blobs they learn of, on storing, reading and republishing them, and
+//! hand the registry over to new Elders. The rounds walk the registry a page at a time, starting
+//! over once they reach its end, so that every blob is audited in turn. A blob with fewer copies
+//! left than holders expected to have it is republished from the remaining copies.
+
+use super::adult_liveness::READ_TIMEOUT;
+use redb::{Database, TableDefinition};
+use sn_data_types::BlobAddress;
+use sn_messaging::MessageId;
+use sn_routing::Prefix;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs, io,
+    ops::Bound,
+    path::Path,
+    time::Instant,
+};
+use xor_name::{XorName, XOR_NAME_LEN};
+
+/// Number of blobs audited each round.
+pub const AUDIT_PAGE_SIZE: usize = 64;
+
+/// Number of blobs carried by each Map of a handover.
+pub const HANDOVER_PAGE_SIZE: usize = 1000;
+
+const BLOBS: TableDefinition<&[u8], ()> = TableDefinition::new("blobs");
+
+// Keys are the name of the blob followed by its kind, so that they are ordered by name.
+const PUBLIC: u8 = 0;
+const PRIVATE: u8 = 1;
+
+struct PendingCheck {
+    address: BlobAddress,
+    // Holders yet to respond.
+    pending: BTreeSet<XorName>,
+    present: BTreeSet<XorName>,
+    missing: BTreeSet<XorName>,
+    started: Instant,
+}
+
+/// Which of the holders of a blob which were checked have it, and which don't.
+#[derive(Debug, PartialEq)]
+pub(super) struct CheckOutcome {
+    pub address: BlobAddress,
+    pub present: BTreeSet<XorName>,
+    /// The holders which responded they don't have it, or didn't respond.
+    pub missing: BTreeSet<XorName>,
+}
+
+/// The blobs to audit, kept on disk, with the existence checks awaiting responses.
+pub(super) struct ReplicaAudit {
+    db: Database,
+    // Key of the last blob audited in the current round, unless it's yet to start.
+    cursor: Option<Vec<u8>>,
+    checks: HashMap<MessageId, PendingCheck>,
+}
+
+impl ReplicaAudit {
+    /// Opens the registry kept at `path`, or starts a new one if there's none.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let db = Database::create(path).map_err(to_error)?;
+        // Created up front, so that reads needn't tell a missing table from an empty one.
+        let txn = db.begin_write().map_err(to_error)?;
+        let _ = txn.open_table(BLOBS).map_err(to_error)?;
+        txn.commit().map_err(to_error)?;
+        Ok(Self {
+            db,
+            cursor: None,
+            checks: HashMap::new(),
+        })
+    }
+
+    /// Registers the blobs at `addresses` to be audited.
+    pub fn add(&mut self, addresses: impl IntoIterator<Item = BlobAddress>) -> io::Result<()> {
+        let txn = self.db.begin_write().map_err(to_error)?;
+        {
+            let mut table = txn.open_table(BLOBS).map_err(to_error)?;
+            for address in addresses {
+                let _ = table
+                    .insert(to_key(&address).as_slice(), ())
+                    .map_err(to_error)?;
+            }
+        }
+        txn.commit().map_err(to_error)
+    }
+
+    /// Stops auditing a deleted blob.
+    pub fn remove(&mut self, address: &BlobAddress) -> io::Result<()> {
+        let txn = self.db.begin_write().map_err(to_error)?;
+        {
+            let mut table = txn.open_table(BLOBS).map_err(to_error)?;
+            let _ = table.remove(to_key(address).as_slice()).map_err(to_error)?;
+        }
+        txn.commit().map_err(to_error)
+    }
+
+    /// The next `AUDIT_PAGE_SIZE` registered blobs of the current round, a new round being
+    /// started once the last one reached the end of the registry.
+    pub fn next_page(&mut self) -> io::Result<Vec<BlobAddress>> {
+        let mut keys = self.keys_after(self.cursor.as_deref(), AUDIT_PAGE_SIZE)?;
+        if keys.is_empty() && self.cursor.is_some() {
+            keys = self.keys_after(None, AUDIT_PAGE_SIZE)?;
+        }
+        self.cursor = keys.last().cloned();
+        Ok(keys.iter().filter_map(|key| from_key(key)).collect())
+    }
+
+    /// The registered blobs within `prefix`, in pages of up to `HANDOVER_PAGE_SIZE`, to hand them
+    /// over to the Elders of its section.
+    pub fn export(&self, prefix: &Prefix) -> io::Result<Vec<Vec<BlobAddress>>> {
+        let txn = self.db.begin_read().map_err(to_error)?;
+        let table = txn.open_table(BLOBS).map_err(to_error)?;
+        let start = prefix.lower_bound().0;
+        let range = table
+            .range::<&[u8]>((Bound::Included(&start[..]), Bound::Unbounded))
+            .map_err(to_error)?;
+        let mut pages = vec![];
+        let mut page = Vec::with_capacity(HANDOVER_PAGE_SIZE);
+        for entry in range {
+            let (key, _) = entry.map_err(to_error)?;
+            let address = match from_key(key.value()) {
+                Some(address) => address,
+                None => continue,
+            };
+            if !prefix.matches(address.name()) {
+                break;
+            }
+            page.push(address);
+            if page.len() == HANDOVER_PAGE_SIZE {
+                pages.push(page);
+                page = Vec::with_capacity(HANDOVER_PAGE_SIZE);
+            }
+        }
+        if !page.is_empty() {
+            pages.push(page);
+        }
+        Ok(pages)
+    }
+
+    /// Whether the holders of the blob at `address` are being checked already.
+    pub fn is_checking(&self, address: &BlobAddress) -> bool {
+        self.checks.values().any(|check| check.address == *address)
+    }
+
+    /// Awaits the responses of `holders` to the existence check `msg_id` for the blob at
+    /// `address`.
+    pub fn start(&mut self, msg_id: MessageId, address: BlobAddress, holders: BTreeSet<XorName>) {
+        let _ = self.checks.insert(
+            msg_id,
+            PendingCheck {
+                address,
+                pending: holders,
+                present: BTreeSet::new(),
+                missing: BTreeSet::new(),
+                started: Instant::now(),
+            },
+        );
+    }
+
+    /// Records whether `src` has the blob of the existence check `msg_id`. Returns the outcome of
+    /// the check once all its holders responded.
+    pub fn record(&mut self, msg_id: MessageId, src: &XorName, held: bool) -> Option<CheckOutcome> {
+        let check = self.checks.get_mut(&msg_id)?;
+        if !check.pending.remove(src) {
+            return None;
+        }
+        let _ = if held {
+            check.present.insert(*src)
+        } else {
+            check.missing.insert(*src)
+        };
+        if !check.pending.is_empty() {
+            return None;
+        }
+        self.checks.remove(&msg_id).map(PendingCheck::into_outcome)
+    }
+
+    /// Ends the checks started at least `READ_TIMEOUT` before `now`, the holders yet to respond
+    /// counting as missing the blob.
+    pub fn expire(&mut self, now: Instant) -> Vec<CheckOutcome> {
+        let expired_ids = self
+            .checks
+            .iter()
+            .filter(|(_, check)| now.saturating_duration_since(check.started) >= READ_TIMEOUT)
+            .map(|(msg_id, _)| *msg_id)
+            .collect::<Vec<_>>();
+        expired_ids
+            .into_iter()
+            .filter_map(|msg_id| self.checks.remove(&msg_id))
+            .map(PendingCheck::into_outcome)
+            .collect()
+    }
+
+    // Up to `limit` keys after `after`, or from the first one without it.
+    fn keys_after(&self, after: Option<&[u8]>, limit: usize) -> io::Result<Vec<Vec<u8>>> {
+        let txn = self.db.begin_read().map_err(to_error)?;
+        let table = txn.open_table(BLOBS).map_err(to_error)?;
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        let range = table
+            .range::<&[u8]>((start, Bound::Unbounded))
+            .map_err(to_error)?;
+        range
+            .take(limit)
+            .map(|entry| {
+                let (key, _) = entry.map_err(to_error)?;
+                Ok(key.value().to_vec())
+            })
+            .collect()
+    }
+}
+
+impl PendingCheck {
+    fn into_outcome(self) -> CheckOutcome {
+        let mut missing = self.missing;
+        missing.extend(self.pending);
+        CheckOutcome {
+            address: self.address,
+            present: self.present,
+            missing,
+        }
+    }
+}
+
+fn to_key(address: &BlobAddress) -> Vec<u8> {
+    let kind = match address {
+        BlobAddress::Public(_) => PUBLIC,
+        BlobAddress::Private(_) => PRIVATE,
+    };
+    let mut key = address.name().0.to_vec();
+    key.push(kind);
+    key
+}
+
+fn from_key(key: &[u8]) -> Option<BlobAddress> {
+    if key.len() != XOR_NAME_LEN + 1 {
+        return None;
+    }
+    let mut name = XorName::default();
+    name.0.copy_from_slice(&key[..XOR_NAME_LEN]);
+    match key[XOR_NAME_LEN] {
+        PUBLIC => Some(BlobAddress::Public(name)),
+        PRIVATE => Some(BlobAddress::Private(name)),
+        _ => None,
+    }
+}
+
+fn to_error(err: impl Into<redb::Error>) -> io::Error {
+    match err.into() {
+        redb::Error::Io(err) => err,
+        err => io::Error::other(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::btree_set;
+    use tempdir::TempDir;
+
+    #[test]
+    fn all_blobs_are_audited_in_turn() -> io::Result<()> {
+        let root = TempDir::new("replica_audit")?;
+        let path = root.path().join("blob_audits.redb");
+        let blobs = (0..AUDIT_PAGE_SIZE + 2)
+            .map(|i| {
+                if i % 2 == 0 {
+                    BlobAddress::Public(XorName::random())
+                } else {
+                    BlobAddress::Private(XorName::random())
+                }
+            })
+            .collect::<BTreeSet<_>>();
+        let deleted = BlobAddress::Public(XorName::random());
+        {
+            let mut audit = ReplicaAudit::open(&path)?;
+            audit.add(blobs.iter().copied())?;
+            audit.add(vec![deleted])?;
+            audit.remove(&deleted)?;
+        }
+
+        // The registry is kept across restarts.
+        let mut audit = ReplicaAudit::open(&path)?;
+        let first = audit.next_page()?;
+        let second = audit.next_page()?;
+        assert_eq!(first.len(), AUDIT_PAGE_SIZE);
+        assert_eq!(second.len(), 2);
+        let audited = first
+            .iter()
+            .chain(&second)
+            .copied()
+            .collect::<BTreeSet<_>>();
+        assert_eq!(audited, blobs);
+        // A new round starts over.
+        assert_eq!(audit.next_page()?, first);
+        Ok(())
+    }
+
+    #[test]
+    fn holders_missing_blobs_are_reported() -> io::Result<()> {
+        let root = TempDir::new("replica_audit")?;
+        let mut audit = ReplicaAudit::open(&root.path().join("blob_audits.redb"))?;
+        let address = BlobAddress::Public(XorName::random());
+        let (holder, lost, silent) = (XorName::random(), XorName::random(), XorName::random());
+
+        let msg_id = MessageId::new();
+        audit.start(msg_id, address, btree_set!(holder, lost));
+        assert!(audit.is_checking(&address));
+        assert_eq!(audit.record(msg_id, &holder, true), None);
+        // Holders which weren't asked, or already responded, aren't counted.
+        assert_eq!(audit.record(msg_id, &silent, false), None);
+        assert_eq!(audit.record(msg_id, &holder, false), None);
+        assert_eq!(
+            audit.record(msg_id, &lost, false),
+            Some(CheckOutcome {
+                address,
+                present: btree_set!(holder),
+                missing: btree_set!(lost),
+            })
+        );
+        assert!(!audit.is_checking(&address));
+
+        audit.start(MessageId::new(), address, btree_set!(holder, silent));
+        assert!(audit.expire(Instant::now()).is_empty());
+        assert_eq!(
+            audit.expire(Instant::now() + READ_TIMEOUT),
+            vec![CheckOutcome {
+                address,
+                present: BTreeSet::new(),
+                missing: btree_set!(holder, silent),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn blobs_within_a_prefix_are_handed_over() -> io::Result<()> {
+        let root = TempDir::new("replica_audit")?;
+        let mut audit = ReplicaAudit::open(&root.path().join("blob_audits.redb"))?;
+        let blobs = (0..HANDOVER_PAGE_SIZE * 2 + 10)
+            .map(|_| BlobAddress::Public(XorName::random()))
+            .collect::<BTreeSet<_>>();
+        audit.add(blobs.iter().copied())?;
+
+        let prefix = Prefix::default().pushed(false);
+        let pages = audit.export(&prefix)?;
+        assert!(pages.iter().all(|page| page.len() <= HANDOVER_PAGE_SIZE));
+        let exported = pages.into_iter().flatten().collect::<BTreeSet<_>>();
+        let expected = blobs
+            .iter()
+            .filter(|address| prefix.matches(address.name()))
+            .copied()
+            .collect::<BTreeSet<_>>();
+        assert_eq!(exported, expected);
+        Ok(())
+    }
+}
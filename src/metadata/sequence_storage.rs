@@ -8,9 +8,12 @@
 
 use super::{build_client_error_response, build_client_query_response};
 use crate::{
-    chunk_store::SequenceChunkStore, error::convert_to_error_message, node_ops::NodeDuty, Error,
-    Result,
+    chunk_store::{SequenceChunkStore, StoreConfig, UsedSpace},
+    error::convert_to_error_message,
+    node_ops::NodeDuty,
+    Error, Result,
 };
+use futures::{pin_mut, StreamExt};
 use log::{debug, info};
 use sn_data_types::{
     Error as DtError, PublicKey, Sequence, SequenceAction, SequenceAddress, SequenceEntry,
@@ -22,6 +25,7 @@ use sn_messaging::{
 };
 use sn_routing::Prefix;
 use std::{
+    collections::BTreeMap,
     fmt::{self, Display, Formatter},
     path::Path,
 };
@@ -32,22 +36,29 @@ pub(super) struct SequenceStorage {
 }
 
 impl SequenceStorage {
-    pub(super) async fn new(path: &Path, max_capacity: u64) -> Result<Self> {
-        let chunks = SequenceChunkStore::new(path, max_capacity).await?;
+    pub(super) async fn new(
+        path: &Path,
+        used_space: &UsedSpace,
+        config: &StoreConfig,
+    ) -> Result<Self> {
+        let chunks = SequenceChunkStore::new(path, used_space, config).await?;
         Ok(Self { chunks })
     }
 
-    pub fn get_data_of(&self, prefix: Prefix) -> SequenceDataExchange {
-        let store = &self.chunks;
-        let data = self
-            .chunks
-            .keys()
-            .iter()
-            .filter(|address| prefix.matches(address.name()))
-            .filter_map(|key| store.get(&key).ok())
-            .map(|seq| (*seq.address(), seq))
-            .collect();
-        SequenceDataExchange(data)
+    pub async fn get_data_of(&self, prefix: Prefix) -> Result<SequenceDataExchange> {
+        let mut data = BTreeMap::new();
+        let addresses = self.chunks.key_stream();
+        pin_mut!(addresses);
+        while let Some(address) = addresses.next().await {
+            let address = address?;
+            if !prefix.matches(address.name()) {
+                continue;
+            }
+            if let Ok(seq) = self.chunks.get(&address).await {
+                let _ = data.insert(address, seq);
+            }
+        }
+        Ok(SequenceDataExchange(data))
     }
 
     pub async fn update(&mut self, seq_data: SequenceDataExchange) -> Result<()> {
@@ -120,7 +131,7 @@ impl SequenceStorage {
         msg_id: MessageId,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let result = if self.chunks.has(data.address()) {
+        let result = if self.chunks.has(data.address()).await {
             Err(Error::DataExists)
         } else {
             self.chunks.put(&data).await
@@ -135,7 +146,10 @@ impl SequenceStorage {
         requester: PublicKey,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let result = match self.get_chunk(address, SequenceAction::Read, requester) {
+        let result = match self
+            .get_chunk(address, SequenceAction::Read, requester)
+            .await
+        {
             Ok(res) => Ok(res),
             Err(error) => Err(convert_to_error_message(error)),
         };
@@ -146,13 +160,13 @@ impl SequenceStorage {
         )))
     }
 
-    fn get_chunk(
+    async fn get_chunk(
         &self,
         address: SequenceAddress,
         action: SequenceAction,
         requester: PublicKey,
     ) -> Result<Sequence> {
-        let data = self.chunks.get(&address)?;
+        let data = self.chunks.get(&address).await?;
         data.check_permission(action, Some(requester))?;
         Ok(data)
     }
@@ -164,7 +178,7 @@ impl SequenceStorage {
         requester: PublicKey,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let result = match self.chunks.get(&address).and_then(|sequence| {
+        let result = match self.chunks.get(&address).await.and_then(|sequence| {
             // TODO - Sequence::check_permission() doesn't support Delete yet in safe-nd
             if sequence.address().is_public() {
                 return Err(Error::InvalidMessage(
@@ -198,6 +212,7 @@ impl SequenceStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, requester)
+            .await
             .and_then(|sequence| {
                 sequence
                     .in_range(range.0, range.1, Some(requester))?
@@ -223,6 +238,7 @@ impl SequenceStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, requester)
+            .await
             .and_then(|sequence| match sequence.last_entry(Some(requester))? {
                 Some(entry) => Ok((sequence.len(Some(requester))? - 1, entry.to_vec())),
                 None => Err(Error::NetworkData(DtError::NoSuchEntry)),
@@ -248,6 +264,7 @@ impl SequenceStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, requester)
+            .await
             .and_then(|sequence| {
                 sequence
                     .permissions(user, Some(requester))
@@ -273,6 +290,7 @@ impl SequenceStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, requester)
+            .await
             .and_then(|sequence| {
                 let res = if sequence.is_public() {
                     let policy = sequence.public_policy()?;
@@ -302,6 +320,7 @@ impl SequenceStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, requester)
+            .await
             .and_then(|sequence| {
                 let res = if !sequence.is_public() {
                     let policy = sequence.private_policy(Some(requester))?;
@@ -361,7 +380,7 @@ impl SequenceStorage {
         F: FnOnce(Sequence) -> Result<Sequence>,
     {
         info!("Getting Sequence chunk for Edit");
-        let result = self.get_chunk(address, action, requester)?;
+        let result = self.get_chunk(address, action, requester).await?;
         let sequence = write_fn(result)?;
         info!("Edited Sequence chunk successfully");
         self.chunks.put(&sequence).await
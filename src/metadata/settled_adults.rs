@@ -0,0 +1,102 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::capacity::CHUNK_COPY_COUNT;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, VecDeque},
+    time::{Duration, Instant},
+};
+use xor_name::XorName;
+
+/// Time the Adults are given to replicate chunks to their new holders once the Adults of the
+/// section changed. Until then, a new holder of a chunk may not have received it yet.
+pub const REORGANISATION_PERIOD: Duration = Duration::from_secs(5 * 60);
+
+/// The Adults of our section over the last `REORGANISATION_PERIOD`, to tell the holders of a
+/// chunk which must have it by now from those it may still be on its way to.
+#[derive(Default)]
+pub(super) struct SettledAdults {
+    // Each set of Adults seen, from when it was first seen, the oldest first. The first one is
+    // the last set seen at least `REORGANISATION_PERIOD` ago, once there's such a set.
+    snapshots: VecDeque<(Instant, BTreeSet<XorName>)>,
+}
+
+impl SettledAdults {
+    /// Notes the Adults of our section at `now`.
+    pub fn update(&mut self, adults: BTreeSet<XorName>, now: Instant) {
+        if self.snapshots.back().map(|(_, last)| last) != Some(&adults) {
+            self.snapshots.push_back((now, adults));
+        }
+        while matches!(
+            self.snapshots.get(1),
+            Some((seen, _)) if now.saturating_duration_since(*seen) >= REORGANISATION_PERIOD
+        ) {
+            let _ = self.snapshots.pop_front();
+        }
+    }
+
+    /// Whether `holder` was already among the closest Adults to `name` `REORGANISATION_PERIOD`
+    /// before `now`, so it must have received the chunk by now. Full Adults aren't accounted for,
+    /// which can only leave out some holders which have it.
+    pub fn has_settled(&self, holder: &XorName, name: &XorName, now: Instant) -> bool {
+        let settled = match self.snapshots.front() {
+            Some((seen, adults))
+                if now.saturating_duration_since(*seen) >= REORGANISATION_PERIOD =>
+            {
+                adults
+            }
+            _ => return false,
+        };
+        settled.contains(holder)
+            && settled
+                .iter()
+                .filter(|adult| name.cmp_distance(adult, holder) == Ordering::Less)
+                .count()
+                < CHUNK_COPY_COUNT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    #[test]
+    fn new_holders_settle_after_the_reorganisation() {
+        let name = XorName::random();
+        let adults = (0..CHUNK_COPY_COUNT + 2)
+            .map(|_| XorName::random())
+            .sorted_by(|lhs, rhs| name.cmp_distance(lhs, rhs))
+            .collect::<Vec<_>>();
+        let (closest, farthest) = (adults[0], adults[CHUNK_COPY_COUNT + 1]);
+        let (old_holder, new_holder) = (adults[1], adults[CHUNK_COPY_COUNT]);
+        let mut settled = SettledAdults::default();
+        let start = Instant::now();
+
+        let all = adults.iter().copied().collect::<BTreeSet<_>>();
+        settled.update(all.clone(), start);
+        assert!(!settled.has_settled(&closest, &name, start));
+        let reorganised = start + REORGANISATION_PERIOD;
+        settled.update(all.clone(), reorganised);
+        assert!(settled.has_settled(&closest, &name, reorganised));
+        assert!(!settled.has_settled(&new_holder, &name, reorganised));
+        assert!(!settled.has_settled(&farthest, &name, reorganised));
+
+        // Losing the closest Adult makes another one a holder, which may not have the chunk yet.
+        let mut remaining = all;
+        let _ = remaining.remove(&closest);
+        settled.update(remaining.clone(), reorganised);
+        assert!(settled.has_settled(&old_holder, &name, reorganised));
+        assert!(!settled.has_settled(&new_holder, &name, reorganised));
+        let later = reorganised + REORGANISATION_PERIOD;
+        settled.update(remaining, later);
+        assert!(settled.has_settled(&new_holder, &name, later));
+        assert!(!settled.has_settled(&closest, &name, later));
+    }
+}
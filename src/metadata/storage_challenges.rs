@@ -0,0 +1,180 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::adult_liveness::READ_TIMEOUT;
+use crate::chunks::storage_proof::StorageChallenge;
+use sn_data_types::BlobAddress;
+use sn_messaging::MessageId;
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::Instant,
+};
+use xor_name::XorName;
+
+struct PendingChallenge {
+    challenge: StorageChallenge,
+    // Holders yet to respond.
+    pending: BTreeSet<XorName>,
+    started: Instant,
+}
+
+/// Whether a holder proved holding a chunk it was challenged for.
+#[derive(Debug, PartialEq)]
+pub(super) struct ProofOutcome {
+    pub address: BlobAddress,
+    pub holder: XorName,
+    pub proven: bool,
+}
+
+/// The storage challenges sent to the holders of chunks, awaiting their proofs.
+#[derive(Default)]
+pub(super) struct StorageChallenges {
+    challenges: HashMap<MessageId, PendingChallenge>,
+}
+
+impl StorageChallenges {
+    /// Whether the holders of the chunk at `address` are being challenged already.
+    pub fn is_challenging(&self, address: &BlobAddress) -> bool {
+        self.challenges
+            .values()
+            .any(|pending| pending.challenge.address == *address)
+    }
+
+    /// Awaits the proofs of `holders`, which were sent `challenge`.
+    pub fn start(&mut self, challenge: StorageChallenge, holders: BTreeSet<XorName>) {
+        let _ = self.challenges.insert(
+            challenge.msg_id,
+            PendingChallenge {
+                challenge,
+                pending: holders,
+                started: Instant::now(),
+            },
+        );
+    }
+
+    /// Checks the `proof` `src` responded to the challenge `msg_id` with. Returns `None` if `src`
+    /// wasn't challenged with it, or already responded.
+    pub fn record_proof(
+        &mut self,
+        msg_id: MessageId,
+        src: &XorName,
+        proof: &[u8],
+    ) -> Option<ProofOutcome> {
+        let pending = self.challenges.get_mut(&msg_id)?;
+        if !pending.pending.remove(src) {
+            return None;
+        }
+        let outcome = ProofOutcome {
+            address: pending.challenge.address,
+            holder: *src,
+            proven: pending.challenge.verify(proof),
+        };
+        if pending.pending.is_empty() {
+            let _ = self.challenges.remove(&msg_id);
+        }
+        Some(outcome)
+    }
+
+    /// Ends the challenges sent at least `READ_TIMEOUT` before `now`, the holders yet to respond
+    /// failing them.
+    pub fn expire(&mut self, now: Instant) -> Vec<ProofOutcome> {
+        let expired_ids = self
+            .challenges
+            .iter()
+            .filter(|(_, pending)| now.saturating_duration_since(pending.started) >= READ_TIMEOUT)
+            .map(|(msg_id, _)| *msg_id)
+            .collect::<Vec<_>>();
+        expired_ids
+            .into_iter()
+            .filter_map(|msg_id| self.challenges.remove(&msg_id))
+            .flat_map(|pending| {
+                let address = pending.challenge.address;
+                pending.pending.into_iter().map(move |holder| ProofOutcome {
+                    address,
+                    holder,
+                    proven: false,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        btree_set,
+        chunks::storage_proof::{proof_blob, storage_proof},
+        metadata::{adult_liveness::AdultLiveness, liveness_stats::LivenessPolicy},
+    };
+    use sn_data_types::{Blob, PublicBlob};
+
+    #[test]
+    fn holders_failing_challenges_are_degraded() {
+        let mut challenges = StorageChallenges::default();
+        let mut liveness = AdultLiveness::new(LivenessPolicy::Score { min_score: 50 });
+        let chunk = Blob::Public(PublicBlob::new(vec![1; 10]));
+        let (honest, cheat, gone) = (XorName::random(), XorName::random(), XorName::random());
+
+        for _ in 0..20 {
+            let challenge = StorageChallenge::new(&chunk);
+            let msg_id = challenge.msg_id;
+            challenges.start(challenge, btree_set!(honest, cheat, gone));
+            assert!(challenges.is_challenging(chunk.address()));
+
+            let proof = storage_proof(&msg_id, chunk.value());
+            let honest_outcome = challenges.record_proof(msg_id, &honest, &proof);
+            assert_eq!(
+                honest_outcome,
+                Some(ProofOutcome {
+                    address: *chunk.address(),
+                    holder: honest,
+                    proven: true,
+                })
+            );
+            // Responding twice, or to a challenge it wasn't sent, is ignored.
+            assert_eq!(challenges.record_proof(msg_id, &honest, &proof), None);
+            assert_eq!(
+                challenges.record_proof(MessageId::new(), &cheat, &proof),
+                None
+            );
+            // Without the chunk, the cheat can't prove holding it.
+            let cheat_outcome = challenges.record_proof(msg_id, &cheat, proof_blob(None).value());
+            assert_eq!(
+                cheat_outcome.as_ref().map(|outcome| outcome.proven),
+                Some(false)
+            );
+
+            assert!(challenges.expire(Instant::now()).is_empty());
+            let expired = challenges.expire(Instant::now() + READ_TIMEOUT);
+            assert_eq!(
+                expired,
+                vec![ProofOutcome {
+                    address: *chunk.address(),
+                    holder: gone,
+                    proven: false,
+                }]
+            );
+            assert!(!challenges.is_challenging(chunk.address()));
+
+            for outcome in honest_outcome
+                .into_iter()
+                .chain(cheat_outcome)
+                .chain(expired)
+            {
+                liveness.record_storage_proof(&outcome.holder, outcome.proven);
+            }
+        }
+
+        let mut unresponsive = liveness.find_unresponsive_adults();
+        unresponsive.sort();
+        let mut failing = vec![cheat, gone];
+        failing.sort();
+        assert_eq!(unresponsive, failing);
+    }
+}
@@ -0,0 +1,169 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Versioning of the on-disk format of a node's root dir, and the migrations between versions.
+//!
+//! The version is recorded in `SCHEMA_VERSION_FILENAME`. A root dir without it was written before
+//! versioning was introduced, and is at version 0. Any change to how chunks, used space records,
+//! transfer stores or keys are stored must come with a new entry in `MIGRATIONS`.
+
+use crate::{chunk_store, Config, Error, Result};
+use futures::{future::BoxFuture, FutureExt};
+use log::info;
+use std::{
+    fmt::{self, Display, Formatter},
+    path::Path,
+};
+use tokio::fs;
+
+/// File in the root dir recording its schema version.
+pub(crate) const SCHEMA_VERSION_FILENAME: &str = "schema_version";
+
+/// Schema version of the root dirs written by this version of the node.
+pub(crate) const SCHEMA_VERSION: u32 = 1;
+
+/// A step bringing a root dir from the previous schema version to `version`.
+#[derive(Debug)]
+pub struct Migration {
+    /// Schema version of the root dir once migrated.
+    pub version: u32,
+    /// What the migration changes.
+    pub description: &'static str,
+    run: for<'a> fn(&'a Path) -> BoxFuture<'a, Result<()>>,
+}
+
+impl Display for Migration {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "v{}: {}", self.version, self.description)
+    }
+}
+
+/// All migrations, in the order they're run.
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "add a journal generation to the used space records of the chunk stores",
+    run: upgrade_used_space_records,
+}];
+
+fn upgrade_used_space_records(root_dir: &Path) -> BoxFuture<'_, Result<()>> {
+    async move {
+        let upgraded = chunk_store::upgrade_used_space_records(root_dir).await?;
+        info!("Upgraded {} used space records", upgraded);
+        Ok(())
+    }
+    .boxed()
+}
+
+/// Brings the root dir of the node configured in `config` to the current schema version, and
+/// returns the migrations run. With `dry_run`, only returns the migrations which would be run.
+pub async fn migrate(config: &Config, dry_run: bool) -> Result<Vec<&'static Migration>> {
+    let root_dir = config.root_dir()?;
+    if dry_run {
+        pending(&root_dir).await
+    } else {
+        run(&root_dir).await
+    }
+}
+
+/// Returns the migrations `root_dir` needs, in order.
+///
+/// Fails with `Error::UnsupportedSchemaVersion` if `root_dir` was written by a newer node.
+pub(crate) async fn pending(root_dir: &Path) -> Result<Vec<&'static Migration>> {
+    let version = read_version(root_dir).await?;
+    if version > SCHEMA_VERSION {
+        return Err(Error::UnsupportedSchemaVersion(version, SCHEMA_VERSION));
+    }
+    Ok(MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > version)
+        .collect())
+}
+
+/// Runs the migrations `root_dir` needs, in order, and returns them.
+///
+/// The version is recorded after each migration, so that an interrupted run picks up from the
+/// last completed one.
+pub(crate) async fn run(root_dir: &Path) -> Result<Vec<&'static Migration>> {
+    let pending = pending(root_dir).await?;
+    for migration in &pending {
+        info!("Migrating {} to {}", root_dir.display(), migration);
+        (migration.run)(root_dir).await?;
+        write_version(root_dir, migration.version).await?;
+    }
+    Ok(pending)
+}
+
+async fn read_version(root_dir: &Path) -> Result<u32> {
+    let path = root_dir.join(SCHEMA_VERSION_FILENAME);
+    if !path.is_file() {
+        return Ok(0);
+    }
+    let contents = fs::read_to_string(&path).await?;
+    contents.trim().parse().map_err(|_| {
+        Error::Logic(format!(
+            "Invalid schema version in {}: {}",
+            path.display(),
+            contents
+        ))
+    })
+}
+
+async fn write_version(root_dir: &Path, version: u32) -> Result<()> {
+    let path = root_dir.join(SCHEMA_VERSION_FILENAME);
+    let temp_path = path.with_extension("tmp");
+    fs::create_dir_all(root_dir).await?;
+    fs::write(&temp_path, version.to_string()).await?;
+    fs::rename(&temp_path, &path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_store::CHUNK_STORE_DIR;
+    use tempdir::TempDir;
+
+    #[test]
+    fn migrations_lead_to_the_current_version() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version as usize, index + 1);
+        }
+        assert_eq!(
+            MIGRATIONS.last().map(|migration| migration.version),
+            Some(SCHEMA_VERSION)
+        );
+    }
+
+    #[tokio::test]
+    async fn unversioned_root_dir_is_migrated() -> Result<()> {
+        let root = TempDir::new("migrations")?;
+        let store_dir = root.path().join(CHUNK_STORE_DIR).join("immutable");
+        std::fs::create_dir_all(&store_dir)?;
+        std::fs::write(store_dir.join("used_space"), 42_u64.to_le_bytes())?;
+
+        assert_eq!(pending(root.path()).await?.len(), MIGRATIONS.len());
+        assert_eq!(run(root.path()).await?.len(), MIGRATIONS.len());
+        assert_eq!(read_version(root.path()).await?, SCHEMA_VERSION);
+        assert!(pending(root.path()).await?.is_empty());
+
+        let record = std::fs::read(store_dir.join("used_space"))?;
+        assert_eq!(bincode::deserialize::<(u64, u64)>(&record)?, (42, 0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn newer_root_dir_is_refused() -> Result<()> {
+        let root = TempDir::new("migrations")?;
+        write_version(root.path(), SCHEMA_VERSION + 1).await?;
+        assert!(matches!(
+            run(root.path()).await,
+            Err(Error::UnsupportedSchemaVersion(_, SCHEMA_VERSION))
+        ));
+        Ok(())
+    }
+}
@@ -12,8 +12,9 @@ use super::{
     role::{AdultRole, Role},
 };
 use crate::{
-    chunks::Chunks,
+    chunks::{Chunks, FULL_REPORT_INTERVAL, SCRUB_INTERVAL},
     event_mapping::MsgContext,
+    metadata::{AUDIT_REPLICAS_INTERVAL, EXPIRE_READS_INTERVAL},
     node_ops::{NodeDuties, NodeDuty},
     section_funds::{reward_stage::RewardStage, Credits, SectionFunds},
     Error, Node, Result,
@@ -81,7 +82,9 @@ impl Node {
                         Self::update_replicas(&elder, &network).await?;
                         let msg_id =
                             MessageId::combine(&[our_prefix.name().0, XorName::from(our_key).0]);
-                        let ops = vec![push_state(&elder, our_prefix, msg_id, new_elders).await?];
+                        let ops = vec![
+                            push_state(&elder, &network, our_prefix, msg_id, new_elders).await?,
+                        ];
                         let our_adults = network.our_adults().await;
                         elder
                             .meta_data
@@ -101,11 +104,12 @@ impl Node {
             } => {
                 let our_name = self.our_name().await;
                 let mut adult_role = self.role.as_adult()?.clone();
+                let network = self.network_api.clone();
                 let handle = tokio::spawn(async move {
                     Ok(NodeTask::from(
                         adult_role
-                            .reorganize_chunks(our_name, added, removed, remaining)
-                            .await,
+                            .reorganize_chunks(&network, our_name, added, removed, remaining)
+                            .await?,
                     ))
                 });
                 Ok(NodeTask::Thread(handle))
@@ -303,13 +307,22 @@ impl Node {
             }
             NodeDuty::LevelDown => {
                 info!("Getting Demoted");
-                let capacity = self.used_space.max_capacity().await;
                 self.role = Role::Adult(AdultRole {
                     chunks: Arc::new(RwLock::new(
-                        Chunks::new(self.node_info.root_dir.as_path(), capacity).await?,
+                        Chunks::new(
+                            self.node_info.root_dir.as_path(),
+                            &self.used_space,
+                            self.chunk_cache_capacity,
+                            &self.store_config,
+                        )
+                        .await?,
                     )),
                 });
-                Ok(NodeTask::None)
+                if self.scrubbing {
+                    Ok(NodeTask::None)
+                } else {
+                    Ok(NodeTask::from(vec![NodeDuty::ScrubChunks]))
+                }
             }
             //
             // ----------- Transfers -----------
@@ -447,12 +460,48 @@ impl Node {
             }
             //
             // -------- Immutable chunks --------
-            NodeDuty::ReadChunk { read, msg_id } => {
+            NodeDuty::ReadChunk {
+                read,
+                msg_id,
+                origin,
+            } => {
                 let adult = self.role.as_adult()?.clone();
                 let handle = tokio::spawn(async move {
-                    let mut ops = vec![adult.chunks.read().await.read(&read, msg_id)];
-                    ops.extend(adult.chunks.read().await.check_storage().await?);
-                    Ok(NodeTask::from(ops))
+                    Ok(NodeTask::from(vec![
+                        adult.chunks.read().await.read(&read, msg_id, origin).await,
+                    ]))
+                });
+                Ok(NodeTask::Thread(handle))
+            }
+            NodeDuty::ProveChunkStorage {
+                address,
+                msg_id,
+                origin,
+            } => {
+                let adult = self.role.as_adult()?.clone();
+                let handle = tokio::spawn(async move {
+                    let chunks = adult.chunks.read().await;
+                    Ok(NodeTask::from(vec![
+                        chunks.prove_storage(&address, msg_id, origin).await,
+                    ]))
+                });
+                Ok(NodeTask::Thread(handle))
+            }
+            NodeDuty::RepairChunk {
+                address,
+                holder,
+                msg_id,
+            } => {
+                let elder = self.role.as_elder()?.clone();
+                let handle = tokio::spawn(async move {
+                    Ok(NodeTask::from(vec![
+                        elder
+                            .meta_data
+                            .write()
+                            .await
+                            .repair_chunk(address, holder, msg_id)
+                            .await?,
+                    ]))
                 });
                 Ok(NodeTask::Thread(handle))
             }
@@ -463,15 +512,13 @@ impl Node {
             } => {
                 let adult = self.role.as_adult()?.clone();
                 let handle = tokio::spawn(async move {
+                    let mut chunks = adult.chunks.write().await;
                     let mut ops = vec![
-                        adult
-                            .chunks
-                            .write()
-                            .await
+                        chunks
                             .write(&write, msg_id, client_signed.public_key)
                             .await?,
                     ];
-                    ops.extend(adult.chunks.read().await.check_storage().await?);
+                    ops.extend(chunks.check_storage().await);
                     Ok(NodeTask::from(ops))
                 });
                 Ok(NodeTask::Thread(handle))
@@ -500,14 +547,18 @@ impl Node {
             NodeDuty::IncrementFullNodeCount { node_id } => {
                 let elder = self.role.as_elder_mut()?.clone();
                 let handle = tokio::spawn(async move {
-                    elder
+                    let newly_full = elder
                         .meta_data
                         .write()
                         .await
                         .increase_full_node_count(node_id)
                         .await;
-                    // Accept a new node in place for the full node.
-                    Ok(NodeTask::from(vec![NodeDuty::SetNodeJoinsAllowed(true)]))
+                    if newly_full {
+                        // Accept a new node in place for the full node.
+                        Ok(NodeTask::from(vec![NodeDuty::SetNodeJoinsAllowed(true)]))
+                    } else {
+                        Ok(NodeTask::None)
+                    }
                 });
                 Ok(NodeTask::Thread(handle))
             }
@@ -572,15 +623,13 @@ impl Node {
                 let elder = self.role.as_elder_mut()?.clone();
                 let handle = tokio::spawn(async move {
                     let duties = if network_api.our_prefix().await.matches(&data_section_addr) {
-                        vec![
-                            elder
-                                .meta_data
-                                // this is a write here as we write the liveness check for each adult
-                                .write()
-                                .await
-                                .read(query, msg_id, client_signed.public_key, origin)
-                                .await?,
-                        ]
+                        elder
+                            .meta_data
+                            // this is a write here as we write the liveness check for each adult
+                            .write()
+                            .await
+                            .read(query, msg_id, client_signed.public_key, origin)
+                            .await?
                     } else {
                         let targets = network_api
                             .get_closest_elders_to(
@@ -614,14 +663,14 @@ impl Node {
             } => {
                 let elder = self.role.as_elder_mut()?.clone();
                 let handle = tokio::spawn(async move {
-                    Ok(NodeTask::from(vec![
+                    Ok(NodeTask::from(
                         elder
                             .meta_data
                             .write()
                             .await
                             .write(cmd, msg_id, client_signed, origin)
                             .await?,
-                    ]))
+                    ))
                 });
                 Ok(NodeTask::Thread(handle))
             }
@@ -644,6 +693,24 @@ impl Node {
                 });
                 Ok(NodeTask::Thread(handle))
             }
+            NodeDuty::RecordStorageProof {
+                proof,
+                correlation_id,
+                src,
+            } => {
+                let elder = self.role.as_elder_mut()?.clone();
+                let handle = tokio::spawn(async move {
+                    Ok(NodeTask::from(
+                        elder
+                            .meta_data
+                            .write()
+                            .await
+                            .record_storage_proof(correlation_id, &proof, src)
+                            .await,
+                    ))
+                });
+                Ok(NodeTask::Thread(handle))
+            }
             NodeDuty::ProcessDataPayment {
                 msg:
                     ProcessMsg::Cmd {
@@ -671,14 +738,69 @@ impl Node {
             NodeDuty::ReplicateChunk { data, .. } => {
                 let adult = self.role.as_adult_mut()?.clone();
                 let handle = tokio::spawn(async move {
-                    Ok(NodeTask::from(vec![
-                        adult
-                            .chunks
-                            .write()
-                            .await
-                            .store_for_replication(data)
-                            .await?,
-                    ]))
+                    let mut chunks = adult.chunks.write().await;
+                    let mut ops = vec![chunks.store_for_replication(data).await?];
+                    ops.extend(chunks.check_storage().await);
+                    Ok(NodeTask::from(ops))
+                });
+                Ok(NodeTask::Thread(handle))
+            }
+            NodeDuty::ScrubChunks => {
+                // Elders hold no chunks, so scrubbing stops until we're demoted.
+                let adult = match self.role.as_adult() {
+                    Ok(adult) if self.scrub_rate > 0 => adult.clone(),
+                    _ => {
+                        self.scrubbing = false;
+                        return Ok(NodeTask::None);
+                    }
+                };
+                self.scrubbing = true;
+                let count = (self.scrub_rate * SCRUB_INTERVAL.as_secs()) as usize;
+                let network_api = self.network_api.clone();
+                let handle = tokio::spawn(async move {
+                    tokio::time::sleep(SCRUB_INTERVAL).await;
+                    let mut ops = adult.scrub_chunks(&network_api, count).await;
+                    ops.push(NodeDuty::ScrubChunks);
+                    Ok(NodeTask::from(ops))
+                });
+                Ok(NodeTask::Thread(handle))
+            }
+            NodeDuty::ExpireReads => {
+                let elder = self.role.as_elder().ok().cloned();
+                let handle = tokio::spawn(async move {
+                    tokio::time::sleep(EXPIRE_READS_INTERVAL).await;
+                    let mut ops = match elder {
+                        Some(elder) => elder.meta_data.write().await.expire_reads().await,
+                        None => vec![],
+                    };
+                    ops.push(NodeDuty::ExpireReads);
+                    Ok(NodeTask::from(ops))
+                });
+                Ok(NodeTask::Thread(handle))
+            }
+            NodeDuty::AuditReplicas => {
+                let elder = self.role.as_elder().ok().cloned();
+                let handle = tokio::spawn(async move {
+                    tokio::time::sleep(AUDIT_REPLICAS_INTERVAL).await;
+                    let mut ops = match elder {
+                        Some(elder) => elder.meta_data.write().await.audit_replicas().await,
+                        None => vec![],
+                    };
+                    ops.push(NodeDuty::AuditReplicas);
+                    Ok(NodeTask::from(ops))
+                });
+                Ok(NodeTask::Thread(handle))
+            }
+            NodeDuty::RenewStorageFull => {
+                let adult = self.role.as_adult().ok().cloned();
+                let handle = tokio::spawn(async move {
+                    tokio::time::sleep(FULL_REPORT_INTERVAL).await;
+                    let mut ops = match adult {
+                        Some(adult) => adult.chunks.write().await.renew_full_report().await,
+                        None => vec![],
+                    };
+                    ops.push(NodeDuty::RenewStorageFull);
+                    Ok(NodeTask::from(ops))
                 });
                 Ok(NodeTask::Thread(handle))
             }
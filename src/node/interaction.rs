@@ -7,6 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
+    metadata::seal_records,
     network::Network,
     node_ops::{MsgType, NodeDuties, NodeDuty, OutgoingMsg},
     Node, Result,
@@ -112,6 +113,7 @@ impl Node {
 /// Push our state to the given dst
 pub(crate) async fn push_state(
     elder: &ElderRole,
+    network: &Network,
     prefix: Prefix,
     msg_id: MessageId,
     peers: BTreeSet<XorName>,
@@ -129,12 +131,13 @@ pub(crate) async fn push_state(
         .filter(|(name, _)| prefix.matches(name))
         .collect();
     // Create an aggregated map of all the metadata of the provided prefix
-    let metadata = elder
+    let mut metadata = elder
         .meta_data
         .read()
         .await
         .get_data_exchange_packet(prefix)
         .await?;
+    seal_records(&mut metadata, network).await;
 
     Ok(NodeDuty::SendToNodes {
         msg: NodeMsg::NodeCmd {
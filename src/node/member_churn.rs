@@ -9,7 +9,7 @@
 use super::role::{ElderRole, Role};
 use crate::{
     capacity::{AdultsStorageInfo, Capacity, CapacityReader, CapacityWriter, StoreCost},
-    metadata::{adult_reader::AdultReader, Metadata},
+    metadata::{adult_reader::AdultReader, check_records, Metadata},
     network::Network,
     node_ops::NodeDuty,
     section_funds::{reward_wallets::RewardWallets, SectionFunds},
@@ -46,9 +46,16 @@ impl Node {
 
         //
         // start handling metadata
-        let max_capacity = self.used_space.max_capacity().await;
-        let meta_data =
-            Metadata::new(&self.node_info.path(), max_capacity, capacity.clone()).await?;
+        let meta_data = Metadata::new(
+            &self.node_info.path(),
+            &self.used_space,
+            &self.store_config,
+            capacity.clone(),
+            self.liveness_policy,
+            self.read_strategy,
+            self.blob_storage,
+        )
+        .await?;
 
         //
         // start handling transfers
@@ -76,7 +83,7 @@ impl Node {
         network_api: &Network,
         node_wallets: BTreeMap<XorName, (NodeAge, PublicKey)>,
         user_wallets: BTreeMap<PublicKey, ActorHistory>,
-        metadata: DataExchange,
+        mut metadata: DataExchange,
     ) -> Result<NodeDuty> {
         if *elder.received_initial_sync.read().await {
             info!("We are already received the initial sync from our section. Ignoring update");
@@ -94,6 +101,7 @@ impl Node {
                 .set_node_wallet(*key, *wallet, *age)
         }
         // --------- merge in provided metadata ---------
+        check_records(&mut metadata, network_api).await;
         elder.meta_data.write().await.update(metadata).await?;
 
         *elder.received_initial_sync.write().await = true;
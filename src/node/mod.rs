@@ -14,13 +14,16 @@ mod role;
 mod split;
 
 use crate::{
-    chunk_store::UsedSpace,
+    chunk_store::{StoreConfig, UsedSpace},
     chunks::Chunks,
     error::convert_to_error_message,
     event_mapping::{map_routing_event, Mapping, MsgContext},
+    metadata::{AdultStats, BlobStorageMode, LivenessPolicy, ReadStrategy},
+    migrations,
     network::Network,
     node_ops::{MsgType, NodeDuty, OutgoingLazyError},
     state_db::{get_reward_pk, store_new_reward_keypair},
+    storage_key::{self, StorageKey},
     Config, Error, Result,
 };
 use futures::{future::BoxFuture, lock::Mutex, stream::FuturesUnordered, FutureExt, StreamExt};
@@ -52,6 +55,8 @@ pub struct NodeInfo {
     pub root_dir: PathBuf,
     /// The key used by the node to receive earned rewards.
     pub reward_key: PublicKey,
+    /// The key stored chunks and transfers are encrypted with, if any.
+    pub(crate) storage_key: Option<StorageKey>,
 }
 
 impl NodeInfo {
@@ -66,6 +71,14 @@ pub struct Node {
     network_api: Network,
     node_info: NodeInfo,
     used_space: UsedSpace,
+    store_config: StoreConfig,
+    chunk_cache_capacity: u64,
+    scrub_rate: u64,
+    // Whether a scrub of our chunks is scheduled, so that only one runs at a time.
+    scrubbing: bool,
+    liveness_policy: LivenessPolicy,
+    read_strategy: ReadStrategy,
+    blob_storage: BlobStorageMode,
     role: Role,
 }
 
@@ -76,6 +89,11 @@ impl Node {
         let root_dir = root_dir_buf.as_path();
         std::fs::create_dir_all(root_dir)?;
 
+        let _ = migrations::run(root_dir).await?;
+
+        // Checked before joining, so that a wrong or missing key is reported straight away.
+        let storage_key = storage_key::load(root_dir, config)?;
+
         let reward_key = match get_reward_pk(root_dir).await? {
             Some(public_key) => PublicKey::Ed25519(public_key),
             None => {
@@ -96,16 +114,33 @@ impl Node {
         let node_info = NodeInfo {
             root_dir: root_dir_buf,
             reward_key,
+            storage_key: storage_key.clone(),
         };
 
+        let mut store_config = config.store_config();
+        store_config.storage_key = storage_key;
+        let used_space = UsedSpace::new(config.max_capacity());
         let node = Self {
             role: Role::Adult(AdultRole {
                 chunks: Arc::new(RwLock::new(
-                    Chunks::new(node_info.root_dir.as_path(), config.max_capacity()).await?,
+                    Chunks::new(
+                        node_info.root_dir.as_path(),
+                        &used_space,
+                        config.chunk_cache_capacity(),
+                        &store_config,
+                    )
+                    .await?,
                 )),
             }),
             node_info,
-            used_space: UsedSpace::new(config.max_capacity()),
+            used_space,
+            store_config,
+            chunk_cache_capacity: config.chunk_cache_capacity(),
+            scrub_rate: config.scrub_rate(),
+            scrubbing: false,
+            liveness_policy: config.liveness_policy(),
+            read_strategy: config.read_strategy(),
+            blob_storage: config.blob_storage(),
             network_api: network_api.clone(),
         };
 
@@ -133,6 +168,15 @@ impl Node {
         self.network_api.our_prefix().await
     }
 
+    /// Returns how each Adult of our section has responded to the chunk reads sent to it, while
+    /// we're an Elder.
+    pub async fn adult_liveness_stats(&self) -> Vec<(XorName, AdultStats)> {
+        match self.role.as_elder() {
+            Ok(elder) => elder.meta_data.read().await.adult_stats(),
+            Err(_) => vec![],
+        }
+    }
+
     async fn process_routing_event(
         network_events: Arc<Mutex<EventStream>>,
         network_api: Network,
@@ -158,6 +202,14 @@ impl Node {
         ));
         let mut threads = FuturesUnordered::new();
         threads.push(routing_task_handle);
+        threads.push(tokio::spawn(async {
+            Ok(NodeTask::from(vec![
+                NodeDuty::ScrubChunks,
+                NodeDuty::ExpireReads,
+                NodeDuty::AuditReplicas,
+                NodeDuty::RenewStorageFull,
+            ]))
+        }));
         while let Some(result) = threads.next().await {
             match result {
                 Ok(Ok(NodeTask::Thread(handle))) => threads.push(handle),
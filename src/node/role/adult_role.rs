@@ -9,20 +9,28 @@
 use crate::{
     capacity::CHUNK_COPY_COUNT,
     chunks::Chunks,
+    error::convert_to_error_message,
+    metadata::is_shard,
+    network::Network,
+    node::messaging::send_to_nodes,
     node_ops::{NodeDuties, NodeDuty},
+    Error, Result,
 };
 use itertools::Itertools;
 use log::{info, trace, warn};
-use sn_data_types::{Blob, BlobAddress};
+use sn_data_types::{Blob, BlobAddress, DataAddress};
 use sn_messaging::{
-    node::{NodeCmd, NodeMsg, NodeSystemCmd},
+    node::{NodeCmd, NodeCmdError, NodeDataError, NodeMsg, NodeSystemCmd},
     Aggregation, MessageId,
 };
 use sn_routing::XorName;
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::BTreeSet;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// Number of chunk addresses listed at once when reorganising chunks.
+const REORGANIZE_PAGE_SIZE: usize = 1000;
+
 #[derive(Clone)]
 pub(crate) struct AdultRole {
     // immutable chunks
@@ -30,34 +38,92 @@ pub(crate) struct AdultRole {
 }
 
 impl AdultRole {
+    /// Republishes the chunks whose holders changed with the Adults of our section. Chunks are
+    /// listed a page at a time, and the replications of each page are sent before the next one is
+    /// listed, so that only a page of chunks is held in memory at once.
     pub async fn reorganize_chunks(
         &mut self,
+        network: &Network,
         our_name: XorName,
         new_adults: BTreeSet<XorName>,
         lost_adults: BTreeSet<XorName>,
         remaining: BTreeSet<XorName>,
-    ) -> NodeDuties {
-        let keys = self.chunks.read().await.keys();
-        let mut data_for_replication = BTreeMap::new();
-        for addr in keys.iter() {
-            if let Some((data, holders)) = self
-                .republish_and_cache(addr, &our_name, &new_adults, &lost_adults, &remaining)
+    ) -> Result<NodeDuties> {
+        let mut cursor = None;
+        loop {
+            let page = self
+                .chunks
+                .read()
                 .await
-            {
-                let _ = data_for_replication.insert(data, holders);
+                .keys_page(cursor.as_ref(), REORGANIZE_PAGE_SIZE)
+                .await?;
+            for addr in page.ids.iter() {
+                if let Some((data, targets)) = self
+                    .republish_and_cache(addr, &our_name, &new_adults, &lost_adults, &remaining)
+                    .await
+                {
+                    let msg = NodeMsg::NodeCmd {
+                        cmd: NodeCmd::System(NodeSystemCmd::ReplicateChunk(data)),
+                        id: MessageId::new(),
+                    };
+                    if let Err(err) = send_to_nodes(&msg, targets, Aggregation::None, network).await
+                    {
+                        warn!("Could not replicate chunk {:?}: {:?}", addr, err);
+                    }
+                }
+            }
+            cursor = match page.next {
+                Some(next) => Some(next),
+                None => break,
+            };
+        }
+        // The chunks we no longer hold may have freed up enough space.
+        Ok(self.chunks.write().await.check_storage().await)
+    }
+
+    /// Verifies the next `count` of our chunks. Corrupt ones are removed, and reported to all
+    /// Elders of our section, each of which fetches a healthy copy from the other holders and
+    /// replicates it back to us, so that the repair doesn't hinge on a single Elder.
+    pub async fn scrub_chunks(&self, network: &Network, count: usize) -> NodeDuties {
+        let (corrupt, mut duties) = {
+            let mut chunks = self.chunks.write().await;
+            match chunks.scrub(count).await {
+                Ok(corrupt) if corrupt.is_empty() => return vec![],
+                // Removing the corrupt chunks may have freed up enough space.
+                Ok(corrupt) => (corrupt, chunks.check_storage().await),
+                Err(err) => {
+                    warn!("Could not scrub chunks: {:?}", err);
+                    return vec![];
+                }
             }
+        };
+
+        let elders = network.our_elder_names().await;
+        if elders.is_empty() {
+            warn!("No Elders to report corrupt chunks {:?} to", corrupt);
+            return duties;
         }
-        data_for_replication
-            .into_iter()
-            .map(|(data, targets)| NodeDuty::SendToNodes {
-                msg: NodeMsg::NodeCmd {
-                    cmd: NodeCmd::System(NodeSystemCmd::ReplicateChunk(data)),
-                    id: MessageId::new(),
+        for address in corrupt {
+            info!("Reporting corrupt chunk {:?} to our Elders", address);
+            // There's no dedicated repair request among the node messages, so the failure to
+            // replicate a chunk is reported instead. The Elders only act on it from a holder.
+            let id = MessageId::new();
+            duties.push(NodeDuty::SendToNodes {
+                msg: NodeMsg::NodeCmdError {
+                    error: NodeCmdError::Data(NodeDataError::ChunkReplication {
+                        address,
+                        error: convert_to_error_message(Error::CorruptChunk(DataAddress::Blob(
+                            address,
+                        ))),
+                    }),
+                    id,
+                    correlation_id: id,
                 },
-                targets,
+                targets: elders.clone(),
                 aggregation: Aggregation::None,
-            })
-            .collect::<Vec<_>>()
+            });
+        }
+        duties
     }
 
     async fn republish_and_cache(
@@ -80,13 +146,17 @@ impl AdultRole {
         if we_are_not_holder_anymore || new_adult_is_holder || lost_old_holder {
             info!("Republishing chunk at {:?}", addr);
             trace!("We are not a holder anymore? {}, New Adult is Holder? {}, Lost Adult was holder? {}", we_are_not_holder_anymore, new_adult_is_holder, lost_old_holder);
-            let chunk = self.chunks.read().await.get_chunk(addr).ok()?;
+            let chunk = self.chunks.read().await.get_chunk(addr).await.ok()?;
+            // The Elders keep track of where shards are, and regenerate those lost themselves.
+            if is_shard(&chunk) {
+                return None;
+            }
             if we_are_not_holder_anymore {
-                if let Err(err) = self.chunks.write().await.remove_chunk(addr).await {
+                let mut chunks = self.chunks.write().await;
+                if let Err(err) = chunks.release_chunk(chunk.clone()).await {
                     warn!("Error deleting chunk during republish: {:?}", err);
                 }
             }
-            // TODO: Push to LRU cache
             Some((chunk, new_holders))
         } else {
             None
@@ -118,11 +118,11 @@ impl Node {
 
         // replicate state to our new elders
         let msg_id = MessageId::combine(&[our_prefix.name().0, XorName::from(our_key).0]);
-        ops.push(push_state(elder, our_prefix, msg_id, our_new_elders).await?);
+        ops.push(push_state(elder, network_api, our_prefix, msg_id, our_new_elders).await?);
 
         // replicate state to our neighbour's new elders
         let msg_id = MessageId::combine(&[sibling_prefix.name().0, XorName::from(sibling_key).0]);
-        ops.push(push_state(elder, sibling_prefix, msg_id, their_new_elders).await?);
+        ops.push(push_state(elder, network_api, sibling_prefix, msg_id, their_new_elders).await?);
 
         let our_adults = network_api.our_adults().await;
         // drop metadata state
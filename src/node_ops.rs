@@ -9,7 +9,7 @@
 #[cfg(feature = "simulated-payouts")]
 use sn_data_types::Transfer;
 use sn_data_types::{
-    ActorHistory, Blob, CreditAgreementProof, NodeAge, PublicKey, RewardAccumulation,
+    ActorHistory, Blob, BlobAddress, CreditAgreementProof, NodeAge, PublicKey, RewardAccumulation,
     RewardProposal, SignedTransfer, TransferAgreementProof,
 };
 use sn_messaging::client::ClientMsg;
@@ -86,6 +86,14 @@ pub enum NodeDuty {
     ReadChunk {
         read: BlobRead,
         msg_id: MessageId,
+        origin: SrcLocation,
+    },
+    /// Fetch a chunk from its other holders, for `holder`
+    /// which found its copy corrupt and removed it.
+    RepairChunk {
+        address: BlobAddress,
+        holder: XorName,
+        msg_id: MessageId,
     },
     WriteChunk {
         write: BlobWrite,
@@ -103,6 +111,20 @@ pub enum NodeDuty {
         correlation_id: MessageId,
         src: XorName,
     },
+    /// Prove holding the chunk at `address` to the Elders,
+    /// which challenged us with `msg_id` as the nonce.
+    ProveChunkStorage {
+        address: BlobAddress,
+        msg_id: MessageId,
+        origin: SrcLocation,
+    },
+    /// Run at data-section Elders on receiving the proof
+    /// of an Adult holding a chunk it was challenged for.
+    RecordStorageProof {
+        proof: Blob,
+        correlation_id: MessageId,
+        src: XorName,
+    },
     /// Get section elders.
     GetSectionElders {
         msg_id: MessageId,
@@ -234,6 +256,18 @@ pub enum NodeDuty {
     },
     /// Create proposals to vote unresponsive nodes as offline
     ProposeOffline(Vec<XorName>),
+    /// Verify a batch of stored chunks, then schedule the next batch.
+    /// This is run at Adults, and is a no-op at Elders.
+    ScrubChunks,
+    /// Give up on the chunk reads holders didn't respond to in time, then schedule the next sweep.
+    /// This is run at Elders, and is a no-op at Adults.
+    ExpireReads,
+    /// Challenge the holders of a sample of blobs, then schedule the next round.
+    /// This is run at Elders, and is a no-op at Adults.
+    AuditReplicas,
+    /// Report still being full to the Elders, unless back under the low watermark, then schedule
+    /// the next report. This is run at Adults, and is a no-op at Elders.
+    RenewStorageFull,
     NoOp,
 }
 
@@ -262,6 +296,7 @@ impl Debug for NodeDuty {
             Self::SimulatePayout { .. } => write!(f, "SimulatePayout"),
             Self::GetTransfersHistory { .. } => write!(f, "GetTransfersHistory"),
             Self::ReadChunk { .. } => write!(f, "ReadChunk"),
+            Self::RepairChunk { .. } => write!(f, "RepairChunk"),
             Self::WriteChunk { .. } => write!(f, "WriteChunk"),
             Self::ProcessRepublish { .. } => write!(f, "ProcessRepublish"),
             Self::RecordAdultReadLiveness {
@@ -273,6 +308,22 @@ impl Debug for NodeDuty {
                 "RecordAdultReadLiveness {{ correlation_id: {}, response: {:?}, src: {} }}",
                 correlation_id, response, src
             ),
+            Self::ProveChunkStorage {
+                address, msg_id, ..
+            } => write!(
+                f,
+                "ProveChunkStorage {{ address: {:?}, msg_id: {} }}",
+                address, msg_id
+            ),
+            Self::RecordStorageProof {
+                correlation_id,
+                src,
+                ..
+            } => write!(
+                f,
+                "RecordStorageProof {{ correlation_id: {}, src: {} }}",
+                correlation_id, src
+            ),
             Self::ReceiveRewardProposal { .. } => write!(f, "ReceiveRewardProposal"),
             Self::ReceiveRewardAccumulation { .. } => write!(f, "ReceiveRewardAccumulation"),
             // ------
@@ -305,6 +356,10 @@ impl Debug for NodeDuty {
             Self::ProcessDataPayment { .. } => write!(f, "ProcessDataPayment"),
             Self::ReplicateChunk { .. } => write!(f, "ReplicateChunk"),
             Self::ProposeOffline(nodes) => write!(f, "ProposeOffline({:?})", nodes),
+            Self::ScrubChunks => write!(f, "ScrubChunks"),
+            Self::ExpireReads => write!(f, "ExpireReads"),
+            Self::AuditReplicas => write!(f, "AuditReplicas"),
+            Self::RenewStorageFull => write!(f, "RenewStorageFull"),
         }
     }
 }
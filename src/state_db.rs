@@ -13,11 +13,11 @@ use std::path::Path;
 use tokio::fs;
 
 // Filename for storing the node's reward (Ed25519 hex-encoded) public key
-const REWARD_PUBLIC_KEY_FILENAME: &str = "reward_public_key";
+pub(crate) const REWARD_PUBLIC_KEY_FILENAME: &str = "reward_public_key";
 // Filename for storing the node's reward (Ed25519 hex-encoded) secret key
-const REWARD_SECRET_KEY_FILENAME: &str = "reward_secret_key";
+pub(crate) const REWARD_SECRET_KEY_FILENAME: &str = "reward_secret_key";
 
-const NETWORK_KEYPAIR_FILENAME: &str = "network_keypair";
+pub(crate) const NETWORK_KEYPAIR_FILENAME: &str = "network_keypair";
 
 /// Writes the network keypair to disk.
 pub async fn store_network_keypair(
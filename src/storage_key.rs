@@ -0,0 +1,205 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Encryption at rest of the node's chunk and transfer stores.
+
+use crate::{utils, Config, Error, Result};
+use log::info;
+use rand::{rngs::OsRng, RngCore};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    pbkdf2,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fmt::{self, Debug, Formatter},
+    fs,
+    num::NonZeroU32,
+    path::Path,
+    sync::Arc,
+};
+
+/// File in the root dir recording what's needed to check a storage key.
+pub(crate) const KEY_CHECK_FILENAME: &str = "storage_key";
+
+/// Environment variable the storage passphrase is read from, when no key file is configured. It's
+/// not a config option so that it never ends up in the config file.
+pub const PASSPHRASE_ENV_VAR: &str = "SN_NODE_STORAGE_PASSPHRASE";
+
+const PBKDF2_ITERATIONS: u32 = 100_000;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+
+/// Encrypted data starts with `MAGIC`, followed by the nonce and then the sealed data.
+const MAGIC: &[u8] = b"sne";
+
+/// Sealed with the key when it's first used, to recognise it afterwards.
+const KEY_CHECK: &[u8] = b"sn_node storage key";
+
+#[derive(Serialize, Deserialize)]
+struct KeyCheck {
+    salt: Vec<u8>,
+    sealed: Vec<u8>,
+}
+
+/// Key the node's stored data is encrypted with, derived from the operator's passphrase or key
+/// file.
+#[derive(Clone)]
+pub struct StorageKey {
+    key: Arc<LessSafeKey>,
+}
+
+impl Debug for StorageKey {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "StorageKey(..)")
+    }
+}
+
+impl StorageKey {
+    /// Derives the key for the data under `root_dir` from `secret`.
+    ///
+    /// The first time, a random salt is recorded in `root_dir` along with a value sealed with the
+    /// key. After that, the key derived from `secret` has to open that value, or
+    /// `Error::WrongStorageKey` is returned.
+    pub fn derive(root_dir: &Path, secret: &[u8]) -> Result<Self> {
+        let path = root_dir.join(KEY_CHECK_FILENAME);
+        if let Ok(bytes) = fs::read(&path) {
+            let check: KeyCheck = utils::deserialise(&bytes)?;
+            let key = Self::from_secret(secret, &check.salt)?;
+            return match key.decrypt(&check.sealed) {
+                Ok(value) if value == KEY_CHECK => Ok(key),
+                _ => Err(Error::WrongStorageKey),
+            };
+        }
+
+        let mut salt = vec![0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let key = Self::from_secret(secret, &salt)?;
+        let check = KeyCheck {
+            salt,
+            sealed: key.encrypt(KEY_CHECK)?,
+        };
+        fs::create_dir_all(root_dir)?;
+        fs::write(&path, utils::serialise(&check)?)?;
+        info!("Data under {} is encrypted at rest", root_dir.display());
+        Ok(key)
+    }
+
+    fn from_secret(secret: &[u8], salt: &[u8]) -> Result<Self> {
+        let iterations = NonZeroU32::new(PBKDF2_ITERATIONS)
+            .ok_or_else(|| Error::Logic("No PBKDF2 iterations".to_string()))?;
+        let mut key = [0; KEY_LEN];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            iterations,
+            salt,
+            secret,
+            &mut key,
+        );
+        let key = UnboundKey::new(&CHACHA20_POLY1305, &key)
+            .map_err(|_| Error::Logic("Invalid storage key length".to_string()))?;
+        Ok(Self {
+            key: Arc::new(LessSafeKey::new(key)),
+        })
+    }
+
+    /// Encrypts `plaintext` under a random nonce.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+
+        let mut sealed = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut sealed,
+            )
+            .map_err(|_| Error::Logic("Could not encrypt data".to_string()))?;
+
+        let mut contents = MAGIC.to_vec();
+        contents.extend_from_slice(&nonce);
+        contents.append(&mut sealed);
+        Ok(contents)
+    }
+
+    /// Decrypts what `encrypt` returned. Fails with `Error::Decryption` if it has been tampered
+    /// with, or was encrypted with another key.
+    pub fn decrypt(&self, contents: &[u8]) -> Result<Vec<u8>> {
+        if !is_encrypted(contents) {
+            return Err(Error::Decryption);
+        }
+        let (nonce, sealed) = contents[MAGIC.len()..].split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| Error::Decryption)?;
+        let mut sealed = sealed.to_vec();
+        let plaintext_len = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut sealed)
+            .map_err(|_| Error::Decryption)?
+            .len();
+        sealed.truncate(plaintext_len);
+        Ok(sealed)
+    }
+}
+
+/// Whether `contents` were returned by `StorageKey::encrypt`, rather than stored in the clear.
+pub fn is_encrypted(contents: &[u8]) -> bool {
+    contents.starts_with(MAGIC) && contents.len() >= MAGIC.len() + NONCE_LEN
+}
+
+/// Returns the storage key configured for the node, if any.
+///
+/// The key is derived from the contents of the configured key file, or else from the passphrase in
+/// `PASSPHRASE_ENV_VAR`. Without either, `Error::StorageKeyRequired` is returned if the data under
+/// `root_dir` is already encrypted.
+pub fn load(root_dir: &Path, config: &Config) -> Result<Option<StorageKey>> {
+    let secret = match config.storage_key_file() {
+        Some(path) => Some(fs::read(path)?),
+        None => env::var(PASSPHRASE_ENV_VAR).ok().map(String::into_bytes),
+    };
+    match secret {
+        Some(secret) => StorageKey::derive(root_dir, &secret).map(Some),
+        None if root_dir.join(KEY_CHECK_FILENAME).exists() => Err(Error::StorageKeyRequired),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn data_round_trips() -> Result<()> {
+        let root = TempDir::new("storage_key")?;
+        let key = StorageKey::derive(root.path(), b"passphrase")?;
+        let contents = key.encrypt(b"some data")?;
+        assert!(is_encrypted(&contents));
+        assert_eq!(key.decrypt(&contents)?, b"some data");
+
+        let mut tampered = contents;
+        if let Some(byte) = tampered.last_mut() {
+            *byte ^= 1;
+        }
+        assert!(matches!(key.decrypt(&tampered), Err(Error::Decryption)));
+        Ok(())
+    }
+
+    #[test]
+    fn wrong_key_is_refused() -> Result<()> {
+        let root = TempDir::new("storage_key")?;
+        let _ = StorageKey::derive(root.path(), b"passphrase")?;
+        let _ = StorageKey::derive(root.path(), b"passphrase")?;
+        assert!(matches!(
+            StorageKey::derive(root.path(), b"other passphrase"),
+            Err(Error::WrongStorageKey)
+        ));
+        Ok(())
+    }
+}
@@ -18,7 +18,7 @@ pub async fn transfer_replicas(
 ) -> Result<Replicas<ReplicaSigningImpl>> {
     let root_dir = node_info.root_dir.clone();
     let info = replica_info(network).await?;
-    Replicas::new(root_dir, info, user_wallets).await
+    Replicas::new(root_dir, node_info.storage_key.clone(), info, user_wallets).await
 }
 
 pub async fn replica_info(network: &Network) -> Result<ReplicaInfo<ReplicaSigningImpl>> {
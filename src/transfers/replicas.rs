@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{replica_signing::ReplicaSigning, store::TransferStore};
-use crate::{Error, Result};
+use crate::{storage_key::StorageKey, Error, Result};
 use bls::PublicKeySet;
 use dashmap::DashMap;
 use log::info;
@@ -51,6 +51,7 @@ where
     T: ReplicaSigning,
 {
     root_dir: PathBuf,
+    storage_key: Option<StorageKey>,
     info: ReplicaInfo<T>,
     stores: Stores,
 }
@@ -58,11 +59,13 @@ where
 impl<T: ReplicaSigning> Replicas<T> {
     pub(crate) async fn new(
         root_dir: PathBuf,
+        storage_key: Option<StorageKey>,
         info: ReplicaInfo<T>,
         user_wallets: BTreeMap<PublicKey, ActorHistory>,
     ) -> Result<Self> {
         let instance = Self {
             root_dir,
+            storage_key,
             info,
             stores: DashMap::new(),
         };
@@ -415,6 +418,7 @@ impl<T: ReplicaSigning> Replicas<T> {
                 .or_insert(Arc::new(RwLock::new(TransferStore::new(
                     key.into(),
                     &self.root_dir,
+                    self.storage_key.clone(),
                 )?)));
         Ok((*store_ref).clone())
     }
@@ -6,7 +6,12 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{to_db_key::ToDbKey, utils, Error, Result};
+use crate::{
+    storage_key::{self, StorageKey},
+    to_db_key::ToDbKey,
+    utils, Error, Result,
+};
+use log::warn;
 use pickledb::PickleDb;
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
@@ -16,13 +21,15 @@ use std::{
 };
 use xor_name::XorName;
 
-const TRANSFERS_DIR_NAME: &str = "transfers";
+pub(crate) const TRANSFERS_DIR_NAME: &str = "transfers";
 const DB_EXTENSION: &str = ".db";
 
 /// Disk storage for transfers.
 pub struct TransferStore<TEvent: Debug + Serialize + DeserializeOwned> {
     db: PickleDb,
     db_path: PathBuf,
+    // Key new events are encrypted with, if any.
+    storage_key: Option<StorageKey>,
     _phantom: PhantomData<TEvent>,
 }
 
@@ -40,13 +47,14 @@ impl<'a, TEvent: Debug + Serialize + DeserializeOwned> TransferStore<TEvent>
 where
     TEvent: 'a,
 {
-    pub fn new(id: XorName, root_dir: &Path) -> Result<Self> {
+    pub fn new(id: XorName, root_dir: &Path, storage_key: Option<StorageKey>) -> Result<Self> {
         let db_dir = root_dir.join(Path::new(TRANSFERS_DIR_NAME));
         let db_name = format!("{}{}", id.to_db_key()?, DB_EXTENSION);
         let db_path = db_dir.join(db_name.clone());
         Ok(Self {
             db: utils::new_auto_dump_db(db_dir.as_path(), db_name)?,
             db_path,
+            storage_key,
             _phantom: PhantomData::default(),
         })
     }
@@ -64,7 +72,7 @@ where
         let mut events: Vec<(usize, TEvent)> = keys
             .iter()
             .filter_map(|key| {
-                let value = self.db.get::<TEvent>(key);
+                let value = self.get(key);
                 let key = key.parse::<usize>();
                 match value {
                     Some(v) => match key {
@@ -92,14 +100,39 @@ where
                 key, event
             )));
         }
-        self.db.set(key, &event).map_err(Error::PickleDb)
+        match &self.storage_key {
+            Some(storage_key) => {
+                let value = storage_key.encrypt(&utils::serialise(&event)?)?;
+                self.db.set(key, &value).map_err(Error::PickleDb)
+            }
+            None => self.db.set(key, &event).map_err(Error::PickleDb),
+        }
+    }
+
+    // Events stored before encryption was enabled are read as they are.
+    fn get(&self, key: &str) -> Option<TEvent> {
+        match self.db.get::<Vec<u8>>(key) {
+            Some(value) if storage_key::is_encrypted(&value) => {
+                let event = match &self.storage_key {
+                    Some(storage_key) => match storage_key.decrypt(&value) {
+                        Ok(event) => utils::deserialise(&event),
+                        Err(err) => Err(err),
+                    },
+                    None => Err(Error::StorageKeyRequired),
+                };
+                event
+                    .map_err(|err| warn!("Could not read transfer event {}: {}", key, err))
+                    .ok()
+            }
+            _ => self.db.get::<TEvent>(key),
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::TransferStore;
-    use crate::{Error, Result};
+    use crate::{storage_key::StorageKey, Error, Result};
     use bls::SecretKeySet;
     use bls::{PublicKeySet, SecretKey, SecretKeyShare};
     use sn_data_types::{
@@ -114,7 +147,7 @@ mod test {
         let id = xor_name::XorName::random();
         let tmp_dir = TempDir::new("root")?;
         let root_dir = tmp_dir.into_path();
-        let mut store = TransferStore::new(id, &root_dir)?;
+        let mut store = TransferStore::new(id, &root_dir, None)?;
         let wallet_id = get_random_pk();
         let mut rng = rand::thread_rng();
         let bls_secret_key = SecretKeySet::random(0, &mut rng);
@@ -146,6 +179,60 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn encrypted_history() -> Result<()> {
+        let id = xor_name::XorName::random();
+        let tmp_dir = TempDir::new("root")?;
+        let root_dir = tmp_dir.into_path();
+        let wallet_id = get_random_pk();
+        let mut rng = rand::thread_rng();
+        let bls_secret_key = SecretKeySet::random(0, &mut rng);
+        let credit_proofs = (0..2)
+            .map(|_| {
+                get_credit(
+                    10,
+                    wallet_id,
+                    bls_secret_key.public_keys(),
+                    bls_secret_key.secret_key_share(0),
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // An event stored in the clear, followed by one stored encrypted.
+        let mut store = TransferStore::new(id, &root_dir, None)?;
+        store.try_insert(ReplicaEvent::TransferPropagated(TransferPropagated {
+            credit_proof: credit_proofs[0].clone(),
+        }))?;
+        let storage_key = StorageKey::derive(&root_dir, b"passphrase")?;
+        let mut store = TransferStore::new(id, &root_dir, Some(storage_key.clone()))?;
+        store.try_insert(ReplicaEvent::TransferPropagated(TransferPropagated {
+            credit_proof: credit_proofs[1].clone(),
+        }))?;
+
+        let store = TransferStore::<ReplicaEvent>::new(id, &root_dir, Some(storage_key))?;
+        let events = store.get_all();
+        assert_eq!(events.len(), 2);
+        for (event, expected) in events.iter().zip(&credit_proofs) {
+            match event {
+                ReplicaEvent::TransferPropagated(TransferPropagated { credit_proof, .. }) => {
+                    assert_eq!(credit_proof, expected)
+                }
+                other => {
+                    return Err(Error::Logic(format!(
+                        "Incorrect Replica event: {:?}",
+                        other
+                    )))
+                }
+            }
+        }
+
+        // Without the key, only the event stored in the clear can be read.
+        let store = TransferStore::<ReplicaEvent>::new(id, &root_dir, None)?;
+        assert_eq!(store.get_all().len(), 1);
+
+        Ok(())
+    }
+
     fn get_random_pk() -> PublicKey {
         PublicKey::from(SecretKey::random().public_key())
     }
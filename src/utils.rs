@@ -61,18 +61,18 @@ pub(crate) fn new_auto_dump_db<D: AsRef<Path>, N: AsRef<Path>>(
     db_name: N,
 ) -> Result<PickleDb> {
     let db_path = db_dir.as_ref().join(db_name);
-    match PickleDb::load_bin(db_path.clone(), PickleDbDumpPolicy::AutoDump) {
-        Ok(db) => Ok(db),
-        Err(_) => {
-            fs::create_dir_all(db_dir)?;
-            let mut db = PickleDb::new_bin(db_path.clone(), PickleDbDumpPolicy::AutoDump);
+    // An existing db which can't be loaded is reported rather than replaced with an empty one.
+    if db_path.exists() {
+        return PickleDb::load_bin(db_path, PickleDbDumpPolicy::AutoDump).map_err(Error::PickleDb);
+    }
 
-            // dump is needed to actually write the db to disk.
-            db.dump()?;
+    fs::create_dir_all(db_dir)?;
+    let mut db = PickleDb::new_bin(db_path.clone(), PickleDbDumpPolicy::AutoDump);
 
-            PickleDb::load_bin(db_path, PickleDbDumpPolicy::AutoDump).map_err(Error::PickleDb)
-        }
-    }
+    // dump is needed to actually write the db to disk.
+    db.dump()?;
+
+    PickleDb::load_bin(db_path, PickleDbDumpPolicy::AutoDump).map_err(Error::PickleDb)
 }
 
 #[allow(dead_code)]